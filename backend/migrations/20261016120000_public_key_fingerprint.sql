@@ -0,0 +1,68 @@
+-- 为用户表添加公钥指纹字段
+-- 指纹为公钥 SPKI DER 编码的 SHA-256 摘要（小写十六进制），用于用户线下核对公钥
+ALTER TABLE users ADD COLUMN public_key_fingerprint VARCHAR(64);
+
+-- DER 长度编码，公钥长度不会超过两个字节能表示的范围
+CREATE FUNCTION pg_temp.der_length(len INTEGER) RETURNS BYTEA
+LANGUAGE plpgsql IMMUTABLE AS $$
+BEGIN
+    IF len < 128 THEN
+        RETURN set_byte('\x00'::BYTEA, 0, len);
+    ELSIF len < 256 THEN
+        RETURN set_byte('\x8100'::BYTEA, 1, len);
+    END IF;
+    RETURN set_byte(set_byte('\x820000'::BYTEA, 1, len >> 8), 2, len & 255);
+END;
+$$;
+
+-- 计算已有公钥的指纹，支持 SPKI（BEGIN PUBLIC KEY）和 PKCS#1（BEGIN RSA PUBLIC KEY）两种 PEM 格式
+-- PKCS#1 公钥先按 RSA 算法标识包装为 SPKI 再计算，与应用中 keys::fingerprint 的结果一致
+-- 无法解析的公钥返回 NULL
+CREATE FUNCTION pg_temp.public_key_fingerprint(pem TEXT) RETURNS VARCHAR(64)
+LANGUAGE plpgsql IMMUTABLE AS $$
+DECLARE
+    -- RSA 算法标识：rsaEncryption OID 加 NULL 参数
+    rsa_algorithm CONSTANT BYTEA := '\x300d06092a864886f70d0101010500';
+    body TEXT;
+    der BYTEA;
+BEGIN
+    body := substring(pem FROM '-----BEGIN PUBLIC KEY-----(.*)-----END PUBLIC KEY-----');
+    IF body IS NOT NULL THEN
+        der := decode(regexp_replace(body, '\s', '', 'g'), 'base64');
+        -- 只接受 RSA 公钥
+        IF position(rsa_algorithm IN der) = 0 THEN
+            RETURN NULL;
+        END IF;
+    ELSE
+        body := substring(pem FROM '-----BEGIN RSA PUBLIC KEY-----(.*)-----END RSA PUBLIC KEY-----');
+        IF body IS NULL THEN
+            RETURN NULL;
+        END IF;
+        -- BIT STRING 以未使用位数 0 开头，再与算法标识一起包装为 SPKI 的 SEQUENCE
+        der := '\x00'::BYTEA || decode(regexp_replace(body, '\s', '', 'g'), 'base64');
+        der := rsa_algorithm || '\x03'::BYTEA || pg_temp.der_length(length(der)) || der;
+        der := '\x30'::BYTEA || pg_temp.der_length(length(der)) || der;
+    END IF;
+
+    -- DER 编码必须以 SEQUENCE 开头
+    IF length(der) < 2 OR get_byte(der, 0) <> 48 THEN
+        RETURN NULL;
+    END IF;
+
+    RETURN encode(sha256(der), 'hex');
+EXCEPTION
+    -- base64 解码失败等情况视为无法解析
+    WHEN OTHERS THEN
+        RETURN NULL;
+END;
+$$;
+
+-- 为已有公钥补齐指纹
+-- 无法解析的公钥保留原样且指纹为空，迁移到设备公钥表时会被标记为已吊销，
+-- 也可以通过 report-public-keys 命令查看
+UPDATE users
+SET public_key_fingerprint = pg_temp.public_key_fingerprint(public_key)
+WHERE public_key IS NOT NULL;
+
+DROP FUNCTION pg_temp.public_key_fingerprint(TEXT);
+DROP FUNCTION pg_temp.der_length(INTEGER);
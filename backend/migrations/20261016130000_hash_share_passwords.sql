@@ -0,0 +1,4 @@
+-- 共享链接密码改为存储 Argon2 的 PHC 格式哈希
+-- 已有的明文密码没有 `$argon2` 前缀，会在首次成功使用时重新哈希，
+-- 也可以运行 `backend migrate-share-passwords` 一次性迁移，运行 `backend report-share-passwords` 查看剩余的明文记录
+COMMENT ON COLUMN shared_links.password IS 'Argon2 PHC hash of the share password; rows without the $argon2 prefix are legacy plaintext';
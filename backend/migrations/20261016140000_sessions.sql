@@ -0,0 +1,15 @@
+-- 创建会话表，每一行对应一个签发过的刷新令牌
+-- 同一次登录中轮换产生的刷新令牌共享同一个 family_id，访问令牌中的 sid 即为 family_id
+CREATE TABLE sessions (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),                 -- 使用 uuid_generate_v4() 自动生成主键
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,   -- 用户外键，用户被删除时会话一并删除
+    family_id UUID NOT NULL,                                        -- 会话族 ID，标识同一次登录
+    refresh_token_hash VARCHAR(64) UNIQUE NOT NULL,                 -- 刷新令牌的 SHA-256 摘要（十六进制），不存储明文
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,                   -- 刷新令牌的过期时间
+    used_at TIMESTAMP WITH TIME ZONE,                               -- 刷新令牌被轮换的时间，再次使用即视为重放
+    revoked_at TIMESTAMP WITH TIME ZONE,                            -- 会话被吊销的时间
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()               -- 创建时间，默认当前时间
+);
+
+CREATE INDEX sessions_family_id_idx ON sessions (family_id);
+CREATE INDEX sessions_user_id_idx ON sessions (user_id);
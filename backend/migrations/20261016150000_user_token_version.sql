@@ -0,0 +1,3 @@
+-- 为用户表添加令牌版本号
+-- 访问令牌中携带签发时的版本号，修改密码或退出所有设备时版本号加一，之前签发的令牌全部失效
+ALTER TABLE users ADD COLUMN token_version INTEGER NOT NULL DEFAULT 0;
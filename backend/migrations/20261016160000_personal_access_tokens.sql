@@ -0,0 +1,15 @@
+-- 创建个人访问令牌表，用于脚本和 CI 等非交互式调用
+CREATE TABLE personal_access_tokens (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),                 -- 使用 uuid_generate_v4() 自动生成主键
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,   -- 用户外键，用户被删除时令牌一并删除
+    name VARCHAR(100) NOT NULL,                                     -- 令牌名称，便于用户区分用途
+    token_hash VARCHAR(64) UNIQUE NOT NULL,                         -- 令牌的 SHA-256 摘要（十六进制），不存储明文
+    token_prefix VARCHAR(16) NOT NULL,                              -- 令牌的前几个字符，用于在列表中辨认令牌
+    scopes TEXT[] NOT NULL,                                         -- 令牌的权限范围，例如 files:upload
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,                   -- 过期时间
+    last_used_at TIMESTAMP WITH TIME ZONE,                          -- 最近一次使用时间
+    revoked_at TIMESTAMP WITH TIME ZONE,                            -- 吊销时间
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()               -- 创建时间，默认当前时间
+);
+
+CREATE INDEX personal_access_tokens_user_id_idx ON personal_access_tokens (user_id);
@@ -0,0 +1,20 @@
+-- 创建 TOTP 两步验证表，每个用户最多一条记录
+-- enabled_at 为空表示用户已生成密钥但尚未用验证码确认，此时登录不要求验证码
+CREATE TABLE user_totp (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,  -- 用户外键，用户被删除时一并删除
+    secret VARCHAR(64) NOT NULL,                                      -- Base32 编码的 TOTP 密钥
+    enabled_at TIMESTAMP WITH TIME ZONE,                              -- 启用时间
+    last_used_step BIGINT,                                            -- 最近一次验证通过的时间步，防止同一验证码被重放
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()                 -- 创建时间，默认当前时间
+);
+
+-- 创建恢复码表，恢复码只能使用一次，用于丢失验证器时登录
+CREATE TABLE recovery_codes (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),                 -- 使用 uuid_generate_v4() 自动生成主键
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,   -- 用户外键，用户被删除时恢复码一并删除
+    code_hash VARCHAR(64) NOT NULL,                                 -- 恢复码的 SHA-256 摘要（十六进制），不存储明文
+    used_at TIMESTAMP WITH TIME ZONE,                               -- 使用时间，为空表示尚未使用
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()               -- 创建时间，默认当前时间
+);
+
+CREATE UNIQUE INDEX recovery_codes_user_id_code_hash_idx ON recovery_codes (user_id, code_hash);
@@ -0,0 +1,21 @@
+-- 为用户表添加邮箱验证时间，为空表示邮箱尚未验证
+ALTER TABLE users
+ADD COLUMN email_verified_at TIMESTAMP WITH TIME ZONE;
+
+-- 此前注册的用户视为已验证，避免影响已有的文件接收者
+-- created_at 可能为空，此时以迁移时间作为验证时间，确保已有用户都被标记为已验证
+UPDATE users SET email_verified_at = COALESCE(created_at, NOW());
+
+-- 创建邮件令牌表，用于邮箱验证和重置密码链接
+-- 令牌只能使用一次，且过期后失效
+CREATE TABLE email_tokens (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),                 -- 使用 uuid_generate_v4() 自动生成主键
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,   -- 用户外键，用户被删除时令牌一并删除
+    purpose VARCHAR(32) NOT NULL,                                   -- 令牌用途：verify_email 或 reset_password
+    token_hash VARCHAR(64) UNIQUE NOT NULL,                         -- 令牌的 SHA-256 摘要（十六进制），不存储明文
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,                   -- 过期时间
+    used_at TIMESTAMP WITH TIME ZONE,                               -- 使用时间，为空表示尚未使用
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()               -- 创建时间，默认当前时间
+);
+
+CREATE INDEX email_tokens_user_id_idx ON email_tokens (user_id);
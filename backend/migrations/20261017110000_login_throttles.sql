@@ -0,0 +1,9 @@
+-- 创建登录限流表，分别按账户（邮箱）和 IP 记录连续的登录失败次数
+CREATE TABLE login_throttles (
+    scope VARCHAR(16) NOT NULL,                     -- 限流维度：account 或 ip
+    throttle_key VARCHAR(255) NOT NULL,             -- 限流键：小写邮箱或 IP 地址
+    failure_count INTEGER NOT NULL DEFAULT 0,       -- 连续失败次数，超过统计窗口后重新计数
+    last_failure_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(), -- 最近一次失败的时间
+    locked_until TIMESTAMP WITH TIME ZONE,          -- 在此时间之前拒绝登录
+    PRIMARY KEY (scope, throttle_key)
+);
@@ -0,0 +1,20 @@
+-- 创建外部身份表，记录用户在外部身份提供方中的主体 ID
+CREATE TABLE user_identities (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),                 -- 使用 uuid_generate_v4() 自动生成主键
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,   -- 用户外键，用户被删除时外部身份一并删除
+    issuer VARCHAR(255) NOT NULL,                                   -- 身份提供方标识，例如 OIDC 的 issuer URL
+    subject VARCHAR(255) NOT NULL,                                  -- 用户在身份提供方中的主体 ID（OIDC 的 sub）
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),              -- 创建时间，默认当前时间
+    UNIQUE (issuer, subject)
+);
+
+CREATE INDEX user_identities_user_id_idx ON user_identities (user_id);
+
+-- 创建 OIDC 授权请求表，保存跳转到身份提供方前生成的 PKCE 校验码和 nonce
+CREATE TABLE oidc_auth_requests (
+    state_hash VARCHAR(64) PRIMARY KEY,                 -- state 参数的 SHA-256 摘要（十六进制）
+    nonce VARCHAR(255) NOT NULL,                        -- ID Token 中必须携带的 nonce
+    pkce_verifier VARCHAR(255) NOT NULL,                -- PKCE 校验码，换取令牌时提交
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,       -- 过期时间
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()   -- 创建时间，默认当前时间
+);
@@ -0,0 +1,5 @@
+-- 为文件表添加加密算法和密文格式版本
+-- 此前上传的文件均使用无认证的 AES-256-CBC（格式版本 1），新文件使用 AEAD 信封（格式版本 2）
+ALTER TABLE files
+ADD COLUMN cipher_suite VARCHAR(32) NOT NULL DEFAULT 'aes-256-cbc',  -- 加密算法：aes-256-cbc、aes-256-gcm 或 chacha20-poly1305
+ADD COLUMN format_version SMALLINT NOT NULL DEFAULT 1;              -- 密文格式版本：1 为旧版 CBC，2 为绑定文件 ID 和接收者的 AEAD 信封
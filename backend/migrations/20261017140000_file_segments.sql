@@ -0,0 +1,12 @@
+-- 流式加密格式：文件内容按固定大小分段加密，每段单独保存，上传和下载时无需在内存中持有整个文件
+CREATE TABLE file_segments (
+    file_id UUID NOT NULL REFERENCES files(id) ON DELETE CASCADE, -- 文件外键，删除文件时同时删除其分段
+    seq INTEGER NOT NULL,                                         -- 分段序号，从 0 开始，同时是分段随机数中的计数器
+    data BYTEA NOT NULL,                                          -- 加密后的分段内容，末尾附带认证标签
+    PRIMARY KEY (file_id, seq)
+);
+
+-- 流式格式的分段信息，整体加密的旧格式为空
+ALTER TABLE files
+ADD COLUMN segment_size INTEGER,   -- 每个分段的明文大小（字节），最后一段可以更短
+ADD COLUMN segment_count INTEGER;  -- 分段数量，上传完成后写入，为空表示上传尚未完成
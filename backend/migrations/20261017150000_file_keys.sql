@@ -0,0 +1,19 @@
+-- 为每个用户单独包装的文件密钥
+-- 发送者保留一份用自己公钥包装的副本，每个接收者各有一份，转发文件时只需为新接收者重新包装密钥，无需重新上传内容
+CREATE TABLE file_keys (
+    file_id UUID NOT NULL REFERENCES files(id) ON DELETE CASCADE, -- 文件外键，删除文件时同时删除其密钥
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE, -- 持有该密钥的用户（发送者或接收者）
+    encrypted_aes_key BYTEA NOT NULL,                             -- 使用该用户 RSA 公钥加密后的文件密钥
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),            -- 创建时间，默认当前时间
+    PRIMARY KEY (file_id, user_id)
+);
+
+-- 已有文件的密钥只为接收者包装过，迁移到新表中，发送者没有副本
+INSERT INTO file_keys (file_id, user_id, encrypted_aes_key, created_at)
+SELECT DISTINCT ON (f.id, sl.recipient_user_id) f.id, sl.recipient_user_id, f.encrypted_aes_key, f.created_at
+FROM files f
+JOIN shared_links sl ON sl.file_id = f.id
+WHERE sl.recipient_user_id IS NOT NULL;
+
+ALTER TABLE files
+DROP COLUMN encrypted_aes_key;
@@ -0,0 +1,64 @@
+-- 创建用户设备公钥表，每个用户可以为不同设备分别上传公钥，无需在设备之间复制私钥
+CREATE TABLE user_keys (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),                 -- 使用 uuid_generate_v4() 自动生成主键
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,   -- 用户外键，用户被删除时公钥一并删除
+    label VARCHAR(100) NOT NULL,                                    -- 设备名称，便于用户区分不同设备的公钥
+    algorithm VARCHAR(32) NOT NULL,                                 -- 包装文件密钥使用的算法，例如 RSA-OAEP-SHA256
+    public_key TEXT NOT NULL,                                       -- SPKI PEM 格式的公钥
+    fingerprint VARCHAR(64) NOT NULL,                               -- 公钥 SPKI DER 编码的 SHA-256 指纹（十六进制）
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),              -- 创建时间，默认当前时间
+    revoked_at TIMESTAMP WITH TIME ZONE                             -- 吊销时间，吊销后不再为该公钥包装新文件的密钥
+);
+
+-- 同一用户不能重复添加仍然有效的同一把公钥
+CREATE UNIQUE INDEX user_keys_active_fingerprint_idx ON user_keys (user_id, fingerprint) WHERE revoked_at IS NULL;
+
+-- 已有的公钥迁移为每个用户的第一把设备公钥
+INSERT INTO user_keys (user_id, label, algorithm, public_key, fingerprint, created_at)
+SELECT id, 'Default', 'RSA-OAEP-SHA256', public_key, public_key_fingerprint, updated_at
+FROM users
+WHERE public_key IS NOT NULL
+AND public_key_fingerprint IS NOT NULL;
+
+-- 无法解析的旧公钥（指纹为空）同样迁移过来，但标记为已吊销，不再用于包装新文件的密钥
+-- 这类公钥没有真正的指纹，以原始文本的 SHA-256 摘要占位
+INSERT INTO user_keys (user_id, label, algorithm, public_key, fingerprint, created_at, revoked_at)
+SELECT id, 'Default (unparseable)', 'RSA-OAEP-SHA256', public_key,
+       encode(sha256(convert_to(public_key, 'UTF8')), 'hex'), updated_at, NOW()
+FROM users
+WHERE public_key IS NOT NULL
+AND public_key_fingerprint IS NULL;
+
+-- 文件密钥改为按设备公钥包装，每个接收者的每把公钥各有一份
+ALTER TABLE file_keys
+ADD COLUMN user_key_id UUID REFERENCES user_keys(id) ON DELETE CASCADE;
+
+-- 已有的文件密钥都使用用户当时唯一的公钥包装
+UPDATE file_keys fk
+SET user_key_id = uk.id
+FROM user_keys uk
+WHERE uk.user_id = fk.user_id;
+
+-- 不删除任何文件密钥：持有者没有公钥时中止迁移，由管理员先行处理
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM file_keys WHERE user_key_id IS NULL) THEN
+        RAISE EXCEPTION 'file_keys has rows whose holder has no public key; restore the public key before running this migration';
+    END IF;
+END;
+$$;
+
+ALTER TABLE file_keys
+ALTER COLUMN user_key_id SET NOT NULL;
+
+ALTER TABLE file_keys
+DROP CONSTRAINT file_keys_pkey;
+
+ALTER TABLE file_keys
+ADD PRIMARY KEY (file_id, user_key_id);
+
+CREATE INDEX file_keys_user_id_idx ON file_keys (file_id, user_id);
+
+ALTER TABLE users
+DROP COLUMN public_key,
+DROP COLUMN public_key_fingerprint;
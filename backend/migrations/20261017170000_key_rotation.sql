@@ -0,0 +1,17 @@
+-- 记录设备公钥的轮换关系，被轮换的公钥保留为已吊销状态，便于审计
+ALTER TABLE user_keys
+ADD COLUMN replaced_by UUID REFERENCES user_keys(id);
+
+-- 创建公钥轮换挑战表，用户需用旧私钥解开挑战以证明持有旧公钥
+-- 挑战只能使用一次，且过期后失效
+CREATE TABLE key_rotation_challenges (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),                 -- 使用 uuid_generate_v4() 自动生成主键
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,   -- 用户外键，用户被删除时挑战一并删除
+    user_key_id UUID NOT NULL REFERENCES user_keys(id) ON DELETE CASCADE, -- 待轮换的设备公钥
+    challenge_hash VARCHAR(64) UNIQUE NOT NULL,                     -- 挑战明文的 SHA-256 摘要（十六进制），不存储明文
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,                   -- 过期时间
+    used_at TIMESTAMP WITH TIME ZONE,                               -- 使用时间，为空表示尚未使用
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()               -- 创建时间，默认当前时间
+);
+
+CREATE INDEX key_rotation_challenges_user_key_id_idx ON key_rotation_challenges (user_key_id);
@@ -0,0 +1,6 @@
+-- 记录文件密钥的包装格式版本
+-- 1：RSA-OAEP 不带标签（旧格式）
+-- 2：RSA-OAEP 标签绑定文件和持有者，包装后的文件密钥被挪到其他文件或其他用户名下时无法解开
+-- 已有的文件密钥都是旧格式，新包装的文件密钥由应用写入版本 2
+ALTER TABLE file_keys
+ADD COLUMN key_wrap_version SMALLINT NOT NULL DEFAULT 1;
@@ -0,0 +1,5 @@
+-- 记录用户是否设置过自己知道的本地密码
+-- 通过单点登录或免密码登录链接创建、接管的账户使用无人知晓的随机密码，关闭两步验证时只能以验证码确认
+-- 已有账户无法区分，统一视为有本地密码，没有密码的用户可以先通过重置密码设置
+ALTER TABLE users
+ADD COLUMN has_local_password BOOLEAN NOT NULL DEFAULT TRUE;
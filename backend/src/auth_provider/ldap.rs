@@ -0,0 +1,206 @@
+// 引入标准库中的格式化 trait 和时间类型
+use std::{fmt, time::Duration};
+
+// 引入 async-trait 库，用于在 trait 中定义异步方法
+use async_trait::async_trait;
+// 引入 axum 的 HTTP 状态码
+use axum::http::StatusCode;
+// 引入 ldap3 库，实现 LDAP 搜索和简单绑定
+use ldap3::{ldap_escape, Ldap, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+// 引入 uuid 库，用于标识用户
+use uuid::Uuid;
+
+use crate::{
+    auth_provider::{sync_external_user, AuthOutcome, AuthProvider, ExternalIdentity},
+    config::Config,
+    db::{DBClient, UserExt},
+    error::{ErrorMessage, HttpError},
+};
+
+// 连接 LDAP 服务器的超时时间
+const LDAP_CONN_TIMEOUT: Duration = Duration::from_secs(5);
+
+// LDAP 结果码 invalidCredentials，表示 DN 或密码错误
+const LDAP_INVALID_CREDENTIALS: u32 = 49;
+
+/// 先按邮箱搜索用户 DN，再以该 DN 和用户密码简单绑定来认证
+///
+/// 认证通过后将目录中的用户名和邮箱同步到 `users`，并以服务器地址和 DN 关联外部身份。
+#[derive(Debug)]
+pub struct LdapAuthProvider {
+    url: String,                   // LDAP 服务器地址，同时作为外部身份的 issuer
+    starttls: bool,                // 是否使用 StartTLS
+    bind_dn: Option<String>,       // 搜索用的服务账户 DN
+    bind_password: Option<String>, // 服务账户密码
+    base_dn: String,               // 搜索用户的基准 DN
+    user_filter: String,           // 搜索用户的过滤器模板
+    name_attr: String,             // 用户名属性
+    email_attr: String,            // 邮箱属性
+}
+
+impl LdapAuthProvider {
+    /// 创建新的 `LdapAuthProvider` 实例
+    ///
+    /// # 参数
+    /// - `config`: 应用配置，读取 `LDAP_*` 配置项。
+    pub fn new(config: &Config) -> Result<Self, String> {
+        let url = config
+            .ldap_url
+            .clone()
+            .ok_or_else(|| "LDAP_URL must be set when the ldap provider is enabled".to_string())?;
+
+        let base_dn = config.ldap_base_dn.clone().ok_or_else(|| {
+            "LDAP_BASE_DN must be set when the ldap provider is enabled".to_string()
+        })?;
+
+        if !config.ldap_user_filter.contains("{email}") {
+            return Err("LDAP_USER_FILTER must contain the {email} placeholder".to_string());
+        }
+
+        Ok(LdapAuthProvider {
+            url,
+            starttls: config.ldap_starttls,
+            bind_dn: config.ldap_bind_dn.clone(),
+            bind_password: config.ldap_bind_password.clone(),
+            base_dn,
+            user_filter: config.ldap_user_filter.clone(),
+            name_attr: config.ldap_name_attr.clone(),
+            email_attr: config.ldap_email_attr.clone(),
+        })
+    }
+
+    /// 外部身份的 issuer，即 LDAP 服务器地址
+    pub fn issuer(&self) -> &str {
+        &self.url
+    }
+
+    // 建立到 LDAP 服务器的连接
+    async fn connect(&self) -> Result<Ldap, ldap3::LdapError> {
+        let settings = LdapConnSettings::new()
+            .set_starttls(self.starttls)
+            .set_conn_timeout(LDAP_CONN_TIMEOUT);
+
+        let (conn, ldap) = LdapConnAsync::with_settings(settings, &self.url).await?;
+        ldap3::drive!(conn);
+
+        Ok(ldap)
+    }
+
+    // 使用服务账户（或匿名）按邮箱搜索用户条目
+    async fn find_entry(
+        &self,
+        ldap: &mut Ldap,
+        email: &str,
+    ) -> Result<Vec<SearchEntry>, ldap3::LdapError> {
+        if let (Some(bind_dn), Some(bind_password)) = (&self.bind_dn, &self.bind_password) {
+            ldap.simple_bind(bind_dn, bind_password).await?.success()?;
+        }
+
+        let filter = self.user_filter.replace("{email}", &ldap_escape(email));
+
+        let (entries, _) = ldap
+            .search(
+                &self.base_dn,
+                Scope::Subtree,
+                &filter,
+                vec![self.name_attr.as_str(), self.email_attr.as_str()],
+            )
+            .await?
+            .success()?;
+
+        Ok(entries.into_iter().map(SearchEntry::construct).collect())
+    }
+}
+
+// 目录服务器不可用时返回 503，详细错误只记录在日志中
+fn unavailable(e: impl fmt::Display) -> HttpError {
+    eprintln!("LDAP authentication failed: {}", e);
+    HttpError::new(
+        ErrorMessage::AuthProviderUnavailable.to_string(),
+        StatusCode::SERVICE_UNAVAILABLE,
+    )
+}
+
+// 读取条目中某个属性的第一个值
+fn first_attr(entry: &SearchEntry, attr: &str) -> Option<String> {
+    entry
+        .attrs
+        .get(attr)
+        .and_then(|values| values.first())
+        .cloned()
+}
+
+#[async_trait]
+impl AuthProvider for LdapAuthProvider {
+    async fn authenticate(
+        &self,
+        db_client: &DBClient,
+        email: &str,
+        password: &str,
+    ) -> Result<AuthOutcome, HttpError> {
+        // 空密码的简单绑定会被服务器当作匿名绑定而成功，必须直接拒绝
+        if password.is_empty() {
+            return Ok(AuthOutcome::Rejected);
+        }
+
+        let mut ldap = self.connect().await.map_err(unavailable)?;
+
+        let mut entries = self
+            .find_entry(&mut ldap, email)
+            .await
+            .map_err(unavailable)?;
+
+        let entry = match entries.len() {
+            0 => {
+                let _ = ldap.unbind().await;
+                return Ok(AuthOutcome::Unknown);
+            }
+            1 => entries.remove(0),
+            // 多个条目匹配同一邮箱时无法确定用户，拒绝登录
+            _ => {
+                eprintln!(
+                    "LDAP search for {} matched {} entries",
+                    email,
+                    entries.len()
+                );
+                let _ = ldap.unbind().await;
+                return Ok(AuthOutcome::Rejected);
+            }
+        };
+
+        // 以用户自己的 DN 和密码绑定来校验密码
+        let bind_result = ldap
+            .simple_bind(&entry.dn, password)
+            .await
+            .map_err(unavailable)?;
+
+        let _ = ldap.unbind().await;
+
+        if bind_result.rc == LDAP_INVALID_CREDENTIALS {
+            return Ok(AuthOutcome::Rejected);
+        }
+        bind_result.success().map_err(unavailable)?;
+
+        let identity = ExternalIdentity {
+            issuer: self.url.clone(),
+            email: first_attr(&entry, &self.email_attr).unwrap_or_else(|| email.to_string()),
+            name: first_attr(&entry, &self.name_attr),
+            subject: entry.dn,
+        };
+
+        let user = sync_external_user(db_client, &identity).await?;
+
+        Ok(AuthOutcome::Authenticated(user))
+    }
+    // 与该目录服务器关联过的账户由目录管理密码
+    async fn manages_password(
+        &self,
+        db_client: &DBClient,
+        user_id: Uuid,
+    ) -> Result<bool, HttpError> {
+        db_client
+            .has_user_identity(user_id, &self.url)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))
+    }
+}
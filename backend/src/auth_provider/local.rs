@@ -0,0 +1,60 @@
+// 引入 async-trait 库，用于在 trait 中定义异步方法
+use async_trait::async_trait;
+
+use crate::{
+    auth_provider::{AuthOutcome, AuthProvider},
+    db::{DBClient, UserExt},
+    error::HttpError,
+    utils::password,
+};
+
+/// 使用 `users.password` 中的 argon2 哈希认证
+#[derive(Debug)]
+pub struct LocalAuthProvider {
+    managed_issuers: Vec<String>, // 由这些身份提供方管理密码的账户不校验本地密码
+}
+
+impl LocalAuthProvider {
+    /// 创建新的 `LocalAuthProvider` 实例
+    ///
+    /// # 参数
+    /// - `managed_issuers`: 管理账户密码的外部身份提供方，例如 LDAP 服务器地址。
+    pub fn new(managed_issuers: Vec<String>) -> Self {
+        LocalAuthProvider { managed_issuers }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for LocalAuthProvider {
+    async fn authenticate(
+        &self,
+        db_client: &DBClient,
+        email: &str,
+        password: &str,
+    ) -> Result<AuthOutcome, HttpError> {
+        let Some(user) = db_client
+            .get_user(None, None, Some(email))
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?
+        else {
+            return Ok(AuthOutcome::Unknown);
+        };
+
+        for issuer in &self.managed_issuers {
+            let managed = db_client
+                .has_user_identity(user.id, issuer)
+                .await
+                .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+            if managed {
+                return Ok(AuthOutcome::Unknown);
+            }
+        }
+
+        if password::compare(password, &user.password).unwrap_or(false) {
+            Ok(AuthOutcome::Authenticated(user))
+        } else {
+            Ok(AuthOutcome::Rejected)
+        }
+    }
+}
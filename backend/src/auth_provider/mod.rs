@@ -0,0 +1,267 @@
+pub mod ldap;
+pub mod local;
+
+// 引入标准库中的格式化 trait 和 Arc
+use std::{fmt, sync::Arc};
+
+// 引入 async-trait 库，用于在 trait 中定义异步方法
+use async_trait::async_trait;
+// 引入 uuid 库，用于标识用户
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    db::{DBClient, UserExt},
+    error::{ErrorMessage, HttpError},
+    models::User,
+    utils::{password, token},
+};
+
+use self::{ldap::LdapAuthProvider, local::LocalAuthProvider};
+
+// 自动创建账户时用户名的最大长度，与 users.name 列一致
+const MAX_NAME_LENGTH: usize = 100;
+
+// 认证后端对一次登录的判定结果
+#[derive(Debug)]
+pub enum AuthOutcome {
+    Authenticated(User), // 认证成功
+    Rejected,            // 账户属于该后端，但密码错误
+    Unknown,             // 该后端中不存在此账户，交给下一个后端处理
+}
+
+/// 邮箱密码登录的认证后端
+///
+/// 通过 `AUTH_PROVIDERS` 按部署选择，多个后端按顺序尝试。
+#[async_trait]
+pub trait AuthProvider: fmt::Debug + Send + Sync {
+    /// 使用邮箱和密码认证用户
+    ///
+    /// # 参数
+    /// - `db_client`: 数据库客户端。
+    /// - `email`: 登录邮箱。
+    /// - `password`: 登录密码。
+    ///
+    /// # 返回
+    /// 返回认证结果，后端不可用时返回错误。
+    async fn authenticate(
+        &self,
+        db_client: &DBClient,
+        email: &str,
+        password: &str,
+    ) -> Result<AuthOutcome, HttpError>;
+
+    /// 判断账户的密码是否由该后端管理
+    ///
+    /// 密码由外部目录管理的账户不能在本服务修改或重置密码，默认不管理。
+    ///
+    /// # 参数
+    /// - `db_client`: 数据库客户端。
+    /// - `user_id`: 用户唯一标识符。
+    ///
+    /// # 返回
+    /// 由该后端管理密码时返回 `true`，查询失败时返回错误。
+    async fn manages_password(
+        &self,
+        _db_client: &DBClient,
+        _user_id: Uuid,
+    ) -> Result<bool, HttpError> {
+        Ok(false)
+    }
+}
+
+/// 根据配置创建认证后端
+///
+/// # 参数
+/// - `config`: 应用配置。
+///
+/// # 返回
+/// 返回按 `AUTH_PROVIDERS` 顺序组合的认证后端或错误信息。
+pub fn from_config(config: &Config) -> Result<Arc<dyn AuthProvider>, String> {
+    if config.auth_providers.is_empty() {
+        return Err("AUTH_PROVIDERS must contain at least one provider".to_string());
+    }
+
+    let ldap = if config.auth_providers.iter().any(|name| name == "ldap") {
+        Some(Arc::new(LdapAuthProvider::new(config)?))
+    } else {
+        None
+    };
+
+    // 由目录服务器管理密码的账户不再校验本地密码
+    let managed_issuers = ldap.iter().map(|ldap| ldap.issuer().to_string()).collect();
+    let local = Arc::new(LocalAuthProvider::new(managed_issuers));
+
+    let providers = config
+        .auth_providers
+        .iter()
+        .map(|name| match name.as_str() {
+            "local" => Ok(local.clone() as Arc<dyn AuthProvider>),
+            "ldap" => Ok(ldap.clone().unwrap() as Arc<dyn AuthProvider>),
+            other => Err(format!("Unknown authentication provider: {}", other)),
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(Arc::new(ChainAuthProvider { providers }))
+}
+
+/// 按顺序尝试多个认证后端
+///
+/// 第一个认证成功或明确拒绝的后端决定结果，不认识该账户时交给下一个后端。
+#[derive(Debug)]
+pub struct ChainAuthProvider {
+    providers: Vec<Arc<dyn AuthProvider>>, // 按配置顺序排列的认证后端
+}
+
+#[async_trait]
+impl AuthProvider for ChainAuthProvider {
+    async fn authenticate(
+        &self,
+        db_client: &DBClient,
+        email: &str,
+        password: &str,
+    ) -> Result<AuthOutcome, HttpError> {
+        for provider in &self.providers {
+            match provider.authenticate(db_client, email, password).await? {
+                AuthOutcome::Unknown => continue,
+                outcome => return Ok(outcome),
+            }
+        }
+
+        Ok(AuthOutcome::Unknown)
+    }
+
+    async fn manages_password(
+        &self,
+        db_client: &DBClient,
+        user_id: Uuid,
+    ) -> Result<bool, HttpError> {
+        for provider in &self.providers {
+            if provider.manages_password(db_client, user_id).await? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+// 外部身份提供方（OIDC、LDAP 等）认证通过的用户身份
+#[derive(Debug)]
+pub struct ExternalIdentity {
+    pub issuer: String,       // 身份提供方标识
+    pub subject: String,      // 用户在身份提供方中的主体 ID
+    pub email: String,        // 已验证的邮箱
+    pub name: Option<String>, // 用户名，可能为空
+}
+
+/// 将外部身份同步为本地账户
+///
+/// 已关联时同步用户名和邮箱；未关联时按邮箱关联已有账户，没有账户时自动创建。
+///
+/// # 参数
+/// - `db_client`: 数据库客户端。
+/// - `identity`: 外部身份提供方认证通过的用户身份。
+///
+/// # 返回
+/// 返回对应的本地 `User` 或错误。
+pub async fn sync_external_user(
+    db_client: &DBClient,
+    identity: &ExternalIdentity,
+) -> Result<User, HttpError> {
+    let name = identity
+        .name
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| name.chars().take(MAX_NAME_LENGTH).collect::<String>());
+
+    if let Some(user) = db_client
+        .get_user_by_identity(&identity.issuer, &identity.subject)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+    {
+        // 身份提供方中的资料有变化时同步到本地
+        let name = name.unwrap_or_else(|| user.name.clone());
+
+        if name == user.name && identity.email == user.email {
+            return Ok(user);
+        }
+
+        return db_client
+            .update_user_profile(user.id, &name, &identity.email)
+            .await
+            .map_err(map_unique_violation);
+    }
+
+    let existing_user = db_client
+        .get_user(None, None, Some(&identity.email))
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let user = match existing_user {
+        Some(user) if user.email_verified_at.is_some() => user,
+        // 邮箱未验证的账户可能由他人抢先注册，关联前在同一事务中重置其密码，
+        // 并吊销抢注者留下的会话、个人访问令牌、设备公钥和两步验证，
+        // 避免抢注者继续登录或解密之后发给邮箱所有者的文件
+        Some(user) => db_client
+            .claim_unverified_user(user.id, random_password_hash()?, false)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?
+            // 期间邮箱已被验证时按已验证的账户关联
+            .unwrap_or(user),
+        // 自动创建账户，密码为随机值，用户之后可以通过重置密码设置本地密码
+        None => {
+            let name = name.unwrap_or_else(|| {
+                identity
+                    .email
+                    .split('@')
+                    .next()
+                    .unwrap_or_default()
+                    .to_string()
+            });
+
+            let password = random_password_hash()?;
+
+            let user = db_client
+                .save_user(name, identity.email.clone(), password.clone())
+                .await
+                .map_err(map_unique_violation)?;
+
+            // 新账户同样按接管处理：标记邮箱已验证，并记录没有本地密码
+            db_client
+                .claim_unverified_user(user.id, password, false)
+                .await
+                .map_err(|e| HttpError::server_error(e.to_string()))?
+                .unwrap_or(user)
+        }
+    };
+
+    db_client
+        .link_user_identity(user.id, &identity.issuer, &identity.subject)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    // 重新读取用户，获取最新的令牌版本号
+    db_client
+        .get_user(Some(user.id), None, None)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNoLongerExist.to_string()))
+}
+
+// 邮箱唯一约束冲突时返回 409
+fn map_unique_violation(e: sqlx::Error) -> HttpError {
+    match e {
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            HttpError::unique_constraint_violation(ErrorMessage::EmailExist.to_string())
+        }
+        e => HttpError::server_error(e.to_string()),
+    }
+}
+
+/// 生成无人知晓的随机密码哈希，用于外部身份或免密码登录链接创建、接管的账户
+pub fn random_password_hash() -> Result<String, HttpError> {
+    password::hash(token::generate_random_token())
+        .map_err(|e| HttpError::server_error(e.to_string()))
+}
@@ -0,0 +1,251 @@
+// 引入数据库客户端和用户扩展接口
+use crate::{
+    db::{DBClient, UserExt},
+    models::UserKey,
+    utils::{keys, password},
+};
+
+/// 执行维护命令
+///
+/// 支持的命令：
+/// - `report-share-passwords`: 列出仍以明文存储密码的共享链接。
+/// - `migrate-share-passwords`: 将所有明文存储的共享链接密码改为 Argon2 哈希。
+/// - `report-login-lockouts`: 列出当前被锁定登录的账户和 IP。
+/// - `unlock-login <邮箱或 IP>`: 解除账户或 IP 的登录锁定并清空失败次数。
+/// - `report-public-keys`: 列出无法解析、或 PEM 与指纹需要修正的设备公钥。
+/// - `migrate-public-keys`: 将设备公钥统一为 SPKI PEM 并重新计算指纹，吊销无法解析的公钥。
+///
+/// # 参数
+/// - `command`: 命令名称。
+/// - `args`: 命令参数。
+/// - `db_client`: 数据库客户端。
+///
+/// # 返回
+/// 返回执行结果，命令不存在或执行失败时返回错误信息。
+pub async fn run(command: &str, args: &[String], db_client: &DBClient) -> Result<(), String> {
+    match command {
+        "report-share-passwords" => report_share_passwords(db_client).await,
+        "migrate-share-passwords" => migrate_share_passwords(db_client).await,
+        "report-login-lockouts" => report_login_lockouts(db_client).await,
+        "unlock-login" => {
+            let throttle_key = args
+                .first()
+                .ok_or_else(|| "Usage: unlock-login <email or IP address>".to_string())?;
+            unlock_login(db_client, throttle_key).await
+        }
+        "report-public-keys" => report_public_keys(db_client).await,
+        "migrate-public-keys" => migrate_public_keys(db_client).await,
+        _ => Err(format!(
+            "Unknown command: {}. Available commands: report-share-passwords, migrate-share-passwords, report-login-lockouts, unlock-login, report-public-keys, migrate-public-keys",
+            command
+        )),
+    }
+}
+
+// 列出仍以明文存储密码的共享链接
+async fn report_share_passwords(db_client: &DBClient) -> Result<(), String> {
+    let legacy_links = db_client
+        .get_legacy_shared_links()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if legacy_links.is_empty() {
+        println!("✅ All shared link passwords are hashed with Argon2.");
+        return Ok(());
+    }
+
+    println!(
+        "⚠️ {} shared link(s) still store a plaintext password:",
+        legacy_links.len()
+    );
+
+    for link in &legacy_links {
+        println!(
+            "  shared_id={} file_id={} expiration_date={}",
+            link.id,
+            link.file_id.map(|id| id.to_string()).unwrap_or_default(),
+            link.expiration_date
+                .map(|date| date.to_rfc3339())
+                .unwrap_or_default(),
+        );
+    }
+
+    Ok(())
+}
+
+// 将所有明文存储的共享链接密码改为 Argon2 哈希
+async fn migrate_share_passwords(db_client: &DBClient) -> Result<(), String> {
+    let legacy_links = db_client
+        .get_legacy_shared_links()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut migrated = 0;
+    let mut failed = 0;
+
+    for link in legacy_links {
+        // 超长或为空的旧密码无法哈希，保留原样并在报告中继续显示
+        let hash_password = match password::hash(&link.password) {
+            Ok(hash_password) => hash_password,
+            Err(e) => {
+                println!("  skipped shared_id={}: {}", link.id, e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        db_client
+            .update_shared_password(link.id, hash_password)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        migrated += 1;
+    }
+
+    println!(
+        "✅ Migrated {} shared link password(s), skipped {}.",
+        migrated, failed
+    );
+
+    Ok(())
+}
+
+// 列出当前被锁定登录的账户和 IP
+async fn report_login_lockouts(db_client: &DBClient) -> Result<(), String> {
+    let throttles = db_client
+        .get_locked_login_throttles()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if throttles.is_empty() {
+        println!("✅ No accounts or IP addresses are locked.");
+        return Ok(());
+    }
+
+    println!("⚠️ {} locked login(s):", throttles.len());
+
+    for throttle in &throttles {
+        println!(
+            "  {}={} failures={} locked_until={}",
+            throttle.scope,
+            throttle.throttle_key,
+            throttle.failure_count,
+            throttle.locked_until
+                .map(|date| date.to_rfc3339())
+                .unwrap_or_default(),
+        );
+    }
+
+    Ok(())
+}
+
+// 解除账户或 IP 的登录锁定，邮箱按小写匹配
+async fn unlock_login(db_client: &DBClient, throttle_key: &str) -> Result<(), String> {
+    let cleared = db_client
+        .clear_login_throttle(None, &throttle_key.to_lowercase())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if cleared == 0 {
+        println!("No login throttle found for {}.", throttle_key);
+    } else {
+        println!("✅ Unlocked {}.", throttle_key);
+    }
+
+    Ok(())
+}
+
+// 使用应用的解析规则重新校验公钥
+// 无法解析时返回原因，PEM 或指纹与规范结果不一致时返回规范的 PEM 和指纹
+fn check_public_key(user_key: &UserKey) -> Result<Option<(String, String)>, String> {
+    let public_key = keys::parse_public_key(&user_key.public_key).map_err(|e| e.to_string())?;
+    let pem = keys::to_pem(&public_key).map_err(|e| e.to_string())?;
+    let fingerprint = keys::fingerprint(&public_key).map_err(|e| e.to_string())?;
+
+    if pem == user_key.public_key && fingerprint == user_key.fingerprint {
+        return Ok(None);
+    }
+
+    Ok(Some((pem, fingerprint)))
+}
+
+// 列出无法解析、或 PEM 与指纹需要修正的设备公钥
+async fn report_public_keys(db_client: &DBClient) -> Result<(), String> {
+    let user_keys = db_client
+        .get_all_active_user_keys()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut reported = 0;
+
+    for user_key in &user_keys {
+        match check_public_key(user_key) {
+            Ok(None) => continue,
+            Ok(Some((_, fingerprint))) => println!(
+                "  key_id={} user_id={} needs normalization (fingerprint {} -> {})",
+                user_key.id, user_key.user_id, user_key.fingerprint, fingerprint
+            ),
+            Err(e) => println!(
+                "  key_id={} user_id={} is unparseable: {}",
+                user_key.id, user_key.user_id, e
+            ),
+        }
+
+        reported += 1;
+    }
+
+    if reported == 0 {
+        println!("✅ All {} active public key(s) are valid.", user_keys.len());
+    } else {
+        println!("⚠️ {} active public key(s) need attention.", reported);
+    }
+
+    Ok(())
+}
+
+// 将设备公钥统一为 SPKI PEM 并重新计算指纹，无法解析的公钥被吊销，不再用于包装新文件的密钥
+async fn migrate_public_keys(db_client: &DBClient) -> Result<(), String> {
+    let user_keys = db_client
+        .get_all_active_user_keys()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut migrated = 0;
+    let mut revoked = 0;
+    let mut failed = 0;
+
+    for user_key in user_keys {
+        match check_public_key(&user_key) {
+            Ok(None) => {}
+            Ok(Some((pem, fingerprint))) => {
+                // 同一用户已有同一把有效公钥时违反唯一索引，保留原样并在报告中继续显示
+                if let Err(e) = db_client
+                    .update_user_key_fingerprint(user_key.id, pem, fingerprint)
+                    .await
+                {
+                    println!("  skipped key_id={}: {}", user_key.id, e);
+                    failed += 1;
+                    continue;
+                }
+
+                migrated += 1;
+            }
+            Err(e) => {
+                db_client
+                    .revoke_user_key(user_key.user_id, user_key.id)
+                    .await
+                    .map_err(|e| e.to_string())?;
+
+                println!("  revoked key_id={}: {}", user_key.id, e);
+                revoked += 1;
+            }
+        }
+    }
+
+    println!(
+        "✅ Normalized {} public key(s), revoked {} unparseable key(s), skipped {}.",
+        migrated, revoked, failed
+    );
+
+    Ok(())
+}
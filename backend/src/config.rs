@@ -1,12 +1,66 @@
+use crate::utils::cipher::CipherSuite;
+
 // 导入 Debug 和 Clone trait，使得 Config 结构体能够打印调试信息，并允许克隆其实例
 #[derive(Debug, Clone)]
 pub struct Config {
     // 数据库连接的 URL
     pub database_url: String,
-    // JWT 密钥，用于加密和验证 JWT 令牌
-    pub jwt_secret: String,
-    // JWT 的最大有效期，单位是秒
+    // JWT 签名密钥目录，目录中的每个 <kid>.pem 文件对应一个密钥，未设置时启动时生成临时密钥
+    pub jwt_keys_dir: Option<String>,
+    // 当前用于签名的 JWT 密钥 ID
+    pub jwt_active_kid: Option<String>,
+    // JWT 访问令牌的最大有效期，单位是分钟
     pub jwt_maxage: i64,
+    // 刷新令牌的最大有效期，单位是分钟
+    pub refresh_token_maxage: i64,
+    // 前端地址，用于生成邮件中的链接
+    pub app_url: String,
+    // 发件人地址
+    pub mail_from: String,
+    // SMTP 服务器地址，未设置时邮件写入 mail_drop_dir 目录（开发和测试环境）
+    pub smtp_host: Option<String>,
+    // SMTP 服务器端口
+    pub smtp_port: u16,
+    // SMTP 用户名和密码，均为可选项
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    // 未配置 SMTP 时邮件文件的保存目录
+    pub mail_drop_dir: String,
+    // 同一账户在统计窗口内允许的最大连续登录失败次数，达到后锁定账户
+    pub login_max_failures: i32,
+    // 同一 IP 在统计窗口内允许的最大连续登录失败次数，达到后锁定该 IP
+    pub login_ip_max_failures: i32,
+    // 锁定时长（分钟），同时也是失败次数的统计窗口
+    pub login_lockout_minutes: i64,
+    // 指数退避的基础时长（秒）
+    pub login_backoff_seconds: i64,
+    // OIDC 身份提供方的 issuer URL，未设置时不启用单点登录
+    pub oidc_issuer_url: Option<String>,
+    // 在身份提供方注册的客户端 ID 和客户端密钥
+    pub oidc_client_id: Option<String>,
+    pub oidc_client_secret: Option<String>,
+    // 身份提供方的回调地址，指向本服务的 /auth/oidc/callback
+    pub oidc_redirect_url: Option<String>,
+    // 登录时依次尝试的认证后端，例如 ["ldap", "local"]
+    pub auth_providers: Vec<String>,
+    // LDAP 服务器地址（ldap:// 或 ldaps://），启用 ldap 认证后端时必须设置
+    pub ldap_url: Option<String>,
+    // 是否在 ldap:// 连接上使用 StartTLS
+    pub ldap_starttls: bool,
+    // 用于搜索用户 DN 的服务账户，未设置时匿名搜索
+    pub ldap_bind_dn: Option<String>,
+    pub ldap_bind_password: Option<String>,
+    // 搜索用户的基准 DN
+    pub ldap_base_dn: Option<String>,
+    // 搜索用户的过滤器，{email} 会被替换为转义后的登录邮箱
+    pub ldap_user_filter: String,
+    // 用户名和邮箱对应的 LDAP 属性
+    pub ldap_name_attr: String,
+    pub ldap_email_attr: String,
+    // 是否允许通过邮件中的一次性链接免密码登录
+    pub magic_link_enabled: bool,
+    // 新上传文件使用的加密算法：aes-256-gcm 或 chacha20-poly1305
+    pub file_cipher_suite: CipherSuite,
     // 服务器的端口号
     pub port: u16,
 }
@@ -19,18 +73,98 @@ impl Config {
         // 从环境变量中获取 DATABASE_URL，如果没有设置该环境变量，程序会报错并退出
         let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
 
-        // 从环境变量中获取 JWT_SECRET_KEY，如果没有设置该环境变量，程序会报错并退出
-        let jwt_secret = std::env::var("JWT_SECRET_KEY").expect("JWT_SECRET_KEY must be set");
+        // 从环境变量中获取 JWT_KEYS_DIR 和 JWT_ACTIVE_KID，均为可选项
+        let jwt_keys_dir = std::env::var("JWT_KEYS_DIR").ok();
+        let jwt_active_kid = std::env::var("JWT_ACTIVE_KID").ok();
 
         // 从环境变量中获取 JWT_MAXAGE，若没有设置该环境变量，程序会报错并退出
         let jwt_maxage = std::env::var("JWT_MAXAGE").expect("JWT_MAXAGE must be set");
 
-        // 返回一个 Config 实例，解析 JWT_MAXAGE 并将其转换为 i64 类型，端口号默认为 8000
+        // 从环境变量中获取 REFRESH_TOKEN_MAXAGE，若没有设置该环境变量，程序会报错并退出
+        let refresh_token_maxage = std::env::var("REFRESH_TOKEN_MAXAGE").expect("REFRESH_TOKEN_MAXAGE must be set");
+
+        // 从环境变量中获取邮件相关配置，均为可选项
+        let app_url = std::env::var("APP_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+        let mail_from = std::env::var("MAIL_FROM").unwrap_or_else(|_| "SecureShare <no-reply@localhost>".to_string());
+        let smtp_host = std::env::var("SMTP_HOST").ok();
+        let smtp_port = std::env::var("SMTP_PORT").unwrap_or_else(|_| "587".to_string());
+        let smtp_username = std::env::var("SMTP_USERNAME").ok();
+        let smtp_password = std::env::var("SMTP_PASSWORD").ok();
+        let mail_drop_dir = std::env::var("MAIL_DROP_DIR").unwrap_or_else(|_| "mail".to_string());
+
+        // 从环境变量中获取登录限流配置，均有默认值
+        let login_max_failures = std::env::var("LOGIN_MAX_FAILURES").unwrap_or_else(|_| "5".to_string());
+        let login_ip_max_failures = std::env::var("LOGIN_IP_MAX_FAILURES").unwrap_or_else(|_| "20".to_string());
+        let login_lockout_minutes = std::env::var("LOGIN_LOCKOUT_MINUTES").unwrap_or_else(|_| "15".to_string());
+        let login_backoff_seconds = std::env::var("LOGIN_BACKOFF_SECONDS").unwrap_or_else(|_| "1".to_string());
+
+        // 从环境变量中获取 OIDC 单点登录配置，均为可选项
+        let oidc_issuer_url = std::env::var("OIDC_ISSUER_URL").ok();
+        let oidc_client_id = std::env::var("OIDC_CLIENT_ID").ok();
+        let oidc_client_secret = std::env::var("OIDC_CLIENT_SECRET").ok();
+        let oidc_redirect_url = std::env::var("OIDC_REDIRECT_URL").ok();
+
+        // 从环境变量中获取认证后端配置，默认只使用本地密码
+        let auth_providers = std::env::var("AUTH_PROVIDERS").unwrap_or_else(|_| "local".to_string());
+
+        // 从环境变量中获取 LDAP 配置，只在启用 ldap 认证后端时使用
+        let ldap_url = std::env::var("LDAP_URL").ok();
+        let ldap_starttls = std::env::var("LDAP_STARTTLS").unwrap_or_else(|_| "false".to_string());
+        let ldap_bind_dn = std::env::var("LDAP_BIND_DN").ok();
+        let ldap_bind_password = std::env::var("LDAP_BIND_PASSWORD").ok();
+        let ldap_base_dn = std::env::var("LDAP_BASE_DN").ok();
+        let ldap_user_filter = std::env::var("LDAP_USER_FILTER").unwrap_or_else(|_| "(mail={email})".to_string());
+        let ldap_name_attr = std::env::var("LDAP_NAME_ATTR").unwrap_or_else(|_| "cn".to_string());
+        let ldap_email_attr = std::env::var("LDAP_EMAIL_ATTR").unwrap_or_else(|_| "mail".to_string());
+
+        // 从环境变量中获取免密码登录开关，默认关闭
+        let magic_link_enabled = std::env::var("MAGIC_LINK_ENABLED").unwrap_or_else(|_| "false".to_string());
+
+        // 从环境变量中获取文件加密算法，默认使用 AES-256-GCM
+        let file_cipher_suite = std::env::var("FILE_CIPHER_SUITE").unwrap_or_else(|_| "aes-256-gcm".to_string());
+
+        // 返回一个 Config 实例，解析 JWT_MAXAGE 和 REFRESH_TOKEN_MAXAGE 并将其转换为 i64 类型，端口号默认为 8000
         Config {
             database_url,
-            jwt_secret,
+            jwt_keys_dir,
+            jwt_active_kid,
             // 将 JWT_MAXAGE 环境变量值解析为 i64 类型，并处理解析失败的情况
             jwt_maxage: jwt_maxage.parse::<i64>().unwrap(),
+            // 将 REFRESH_TOKEN_MAXAGE 环境变量值解析为 i64 类型
+            refresh_token_maxage: refresh_token_maxage.parse::<i64>().unwrap(),
+            app_url,
+            mail_from,
+            smtp_host,
+            // 将 SMTP_PORT 环境变量值解析为 u16 类型
+            smtp_port: smtp_port.parse::<u16>().unwrap(),
+            smtp_username,
+            smtp_password,
+            mail_drop_dir,
+            // 将登录限流配置解析为整数类型
+            login_max_failures: login_max_failures.parse::<i32>().unwrap(),
+            login_ip_max_failures: login_ip_max_failures.parse::<i32>().unwrap(),
+            login_lockout_minutes: login_lockout_minutes.parse::<i64>().unwrap(),
+            login_backoff_seconds: login_backoff_seconds.parse::<i64>().unwrap(),
+            oidc_issuer_url,
+            oidc_client_id,
+            oidc_client_secret,
+            oidc_redirect_url,
+            // 按逗号拆分认证后端列表，忽略空白项
+            auth_providers: auth_providers
+                .split(',')
+                .map(|provider| provider.trim().to_lowercase())
+                .filter(|provider| !provider.is_empty())
+                .collect(),
+            ldap_url,
+            ldap_starttls: ldap_starttls.parse::<bool>().unwrap(),
+            ldap_bind_dn,
+            ldap_bind_password,
+            ldap_base_dn,
+            ldap_user_filter,
+            ldap_name_attr,
+            ldap_email_attr,
+            magic_link_enabled: magic_link_enabled.parse::<bool>().unwrap(),
+            file_cipher_suite: file_cipher_suite.parse::<CipherSuite>().unwrap(),
             // 默认端口设置为 8000
             port: 8000,
         }
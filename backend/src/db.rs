@@ -4,7 +4,11 @@ use sqlx::{Pool, Postgres};  // 引入 `sqlx` 库，用于与 PostgreSQL 数据
 use uuid::Uuid;              // 引入 `uuid` 库，用于生成和处理唯一标识符。
 
 // 引入当前模块中的模型（例如文件、用户、共享链接等），用于操作数据库返回的实体。
-use crate::models::{File, ReceiveFileDetails, SendFileDetails, SharedLink, User};
+use crate::utils::cipher::{CipherSuite, KEY_WRAP_VERSION, STREAM_FORMAT_VERSION};
+use crate::models::{
+    EmailTokenPurpose, File, FileKey, KeyRotationOutcome, LoginThrottle, OidcAuthRequest, PendingFileKey, PersonalAccessToken, ReceiveFileDetails,
+    SendFileDetails, Session, SharedLink, User, UserKey, UserTotp,
+};
 
 /// 数据库客户端结构体
 /// 用于封装与 PostgreSQL 数据库的连接池。
@@ -26,6 +30,40 @@ impl DBClient {
     }
 }
 
+/// 转义 LIKE 模式中的特殊字符
+///
+/// # 参数
+/// - `input`: 用户输入的原始字符串。
+///
+/// # 返回
+/// 返回将 `\`、`%` 和 `_` 前加上转义符 `\` 后的字符串。
+fn escape_like(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// 根据页码和每页条目数计算分页偏移量
+///
+/// # 参数
+/// - `page`: 分页页码，从 1 开始。
+/// - `limit`: 每页条目数。
+///
+/// # 返回
+/// 返回 `(page - 1) * limit`，结果超出 `i64` 范围时返回错误。
+fn page_offset(page: usize, limit: usize) -> Result<i64, sqlx::Error> {
+    i64::try_from(page.saturating_sub(1))
+        .ok()
+        .zip(i64::try_from(limit).ok())
+        .and_then(|(page, limit)| page.checked_mul(limit))
+        .ok_or_else(|| sqlx::Error::Protocol("page offset out of range".to_string()))
+}
+
 /// 定义一个用户相关的扩展接口（异步特征）
 /// 该特征包含多个与用户和文件管理相关的异步操作。
 #[async_trait]
@@ -78,6 +116,9 @@ pub trait UserExt {
 
     /// 更新用户的密码
     ///
+    /// 同时递增令牌版本号并吊销该用户的全部会话和个人访问令牌，使之前签发的令牌全部失效。
+    /// 新密码由用户自己设置，之后视为有本地密码。
+    ///
     /// # 参数
     /// - `user_id`: 用户唯一标识符。
     /// - `password`: 新密码。
@@ -90,55 +131,285 @@ pub trait UserExt {
         password: String,
     ) -> Result<User, sqlx::Error>;
 
-    /// 保存用户的公钥信息
+    /// 使用户之前签发的全部令牌失效（退出所有设备）
+    ///
+    /// 递增令牌版本号并吊销该用户的全部会话和个人访问令牌。
     ///
     /// # 参数
     /// - `user_id`: 用户唯一标识符。
-    /// - `public_key`: 用户的公钥。
     ///
     /// # 返回
-    /// 返回操作结果（成功或错误）。
-    async fn save_user_key(&self, user_id: Uuid, public_key: String) -> Result<(), sqlx::Error>;
+    /// 返回更新后的 `User` 或操作错误。
+    async fn invalidate_user_tokens(&self, user_id: Uuid) -> Result<User, sqlx::Error>;
+
+    /// 判断用户是否设置过自己知道的本地密码
+    ///
+    /// 通过单点登录或免密码登录链接创建、接管的账户使用无人知晓的随机密码。
+    ///
+    /// # 参数
+    /// - `user_id`: 用户唯一标识符。
+    ///
+    /// # 返回
+    /// 有本地密码时返回 `true`，否则返回 `false`。
+    async fn has_local_password(&self, user_id: Uuid) -> Result<bool, sqlx::Error>;
+
+    /// 为用户添加一把设备公钥
+    ///
+    /// # 参数
+    /// - `user_id`: 用户唯一标识符。
+    /// - `label`: 设备名称。
+    /// - `algorithm`: 包装文件密钥使用的算法。
+    /// - `public_key`: 用户的公钥（已校验的 SPKI PEM）。
+    /// - `fingerprint`: 公钥的 SHA-256 指纹。
+    ///
+    /// # 返回
+    /// 返回新添加的 `UserKey`，该用户已有同一把有效公钥时返回 `None`。
+    async fn save_user_key(
+        &self,
+        user_id: Uuid,
+        label: String,
+        algorithm: &str,
+        public_key: String,
+        fingerprint: String,
+    ) -> Result<Option<UserKey>, sqlx::Error>;
+
+    /// 获取用户的全部设备公钥，包括已吊销的公钥
+    ///
+    /// # 参数
+    /// - `user_id`: 用户唯一标识符。
+    ///
+    /// # 返回
+    /// 返回按添加时间排序的公钥列表或查询错误。
+    async fn get_user_keys(&self, user_id: Uuid) -> Result<Vec<UserKey>, sqlx::Error>;
+
+    /// 获取用户未吊销的设备公钥，上传和转发文件时为每一把公钥包装文件密钥
+    ///
+    /// # 参数
+    /// - `user_id`: 用户唯一标识符。
+    ///
+    /// # 返回
+    /// 返回按添加时间排序的公钥列表或查询错误。
+    async fn get_active_user_keys(&self, user_id: Uuid) -> Result<Vec<UserKey>, sqlx::Error>;
+
+    /// 吊销用户的设备公钥，吊销后不再为其包装新的文件密钥，已包装的密钥也不再返回
+    ///
+    /// # 参数
+    /// - `user_id`: 用户唯一标识符。
+    /// - `key_id`: 公钥 ID。
+    ///
+    /// # 返回
+    /// 返回是否找到并吊销了该公钥。
+    async fn revoke_user_key(&self, user_id: Uuid, key_id: Uuid) -> Result<bool, sqlx::Error>;
+
+    /// 获取所有用户未吊销的设备公钥，供维护命令重新校验公钥和指纹
+    ///
+    /// # 返回
+    /// 返回按添加时间排序的公钥列表或查询错误。
+    async fn get_all_active_user_keys(&self) -> Result<Vec<UserKey>, sqlx::Error>;
+
+    /// 更新设备公钥的 PEM 和指纹，用于将旧公钥统一为 SPKI PEM 并修正指纹
+    ///
+    /// # 参数
+    /// - `key_id`: 公钥 ID。
+    /// - `public_key`: SPKI PEM 格式的公钥。
+    /// - `fingerprint`: 公钥的 SHA-256 指纹。
+    ///
+    /// # 返回
+    /// 返回操作结果。
+    async fn update_user_key_fingerprint(
+        &self,
+        key_id: Uuid,
+        public_key: String,
+        fingerprint: String,
+    ) -> Result<(), sqlx::Error>;
+
+    /// 创建公钥轮换挑战，同一把公钥尚未使用的旧挑战全部作废
+    ///
+    /// # 参数
+    /// - `user_id`: 用户唯一标识符。
+    /// - `user_key_id`: 待轮换的设备公钥 ID。
+    /// - `challenge_hash`: 挑战明文的 SHA-256 摘要。
+    /// - `expires_at`: 挑战的过期时间。
+    ///
+    /// # 返回
+    /// 操作成功或数据库错误。
+    async fn create_key_rotation_challenge(
+        &self,
+        user_id: Uuid,
+        user_key_id: Uuid,
+        challenge_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error>;
+
+    /// 获取使用某把设备公钥包装、且对应文件还有未过期共享链接的文件密钥
+    ///
+    /// # 参数
+    /// - `user_key_id`: 设备公钥 ID。
+    ///
+    /// # 返回
+    /// 返回按文件 ID 排序的文件密钥列表或查询错误。
+    async fn get_pending_file_keys(
+        &self,
+        user_key_id: Uuid,
+    ) -> Result<Vec<PendingFileKey>, sqlx::Error>;
+
+    /// 轮换设备公钥
+    ///
+    /// 在同一事务中使用挑战、添加新公钥、将仍需使用的文件密钥替换为客户端使用新公钥重新包装的版本，
+    /// 并吊销旧公钥、记录替代它的新公钥。任何一步失败时整个轮换不生效。
+    ///
+    /// # 参数
+    /// - `user_id`: 用户唯一标识符。
+    /// - `old_key_id`: 待轮换的设备公钥 ID。
+    /// - `challenge_hash`: 用户解开的挑战明文的 SHA-256 摘要。
+    /// - `label`: 新公钥的设备名称。
+    /// - `algorithm`: 包装文件密钥使用的算法。
+    /// - `public_key`: 新公钥（已校验的 SPKI PEM）。
+    /// - `fingerprint`: 新公钥的 SHA-256 指纹。
+    /// - `file_keys`: 文件 ID 和使用新公钥重新包装的文件密钥（当前包装格式，见 `cipher::key_wrap_label`），必须与 `get_pending_file_keys` 的结果一一对应。
+    ///
+    /// # 返回
+    /// 返回轮换结果或数据库错误。
+    #[allow(clippy::too_many_arguments)]
+    async fn rotate_user_key(
+        &self,
+        user_id: Uuid,
+        old_key_id: Uuid,
+        challenge_hash: &str,
+        label: String,
+        algorithm: &str,
+        public_key: String,
+        fingerprint: String,
+        file_keys: Vec<(Uuid, Vec<u8>)>,
+    ) -> Result<KeyRotationOutcome, sqlx::Error>;
 
-    /// 根据邮箱搜索用户
+    /// 根据邮箱前缀搜索已验证邮箱且至少有一把有效公钥的用户
+    ///
+    /// 查询条件中的 `%`、`_` 和 `\` 会被转义，只按字面前缀匹配。
     ///
     /// # 参数
-    /// - `user_id`: 当前用户 ID（用于限制搜索范围）。
-    /// - `query`: 搜索关键词（邮箱部分匹配）。
+    /// - `user_id`: 当前用户 ID（搜索结果中排除自己）。
+    /// - `query`: 搜索关键词（邮箱前缀）。
+    /// - `limit`: 返回结果的最大数量。
     ///
     /// # 返回
     /// 返回符合条件的用户列表或查询错误。
-    async fn search_by_email(&self, user_id: Uuid, query: String)
-        -> Result<Vec<User>, sqlx::Error>;
+    async fn search_by_email(
+        &self,
+        user_id: Uuid,
+        query: String,
+        limit: i64,
+    ) -> Result<Vec<User>, sqlx::Error>;
 
-    /// 保存加密文件
+    /// 创建流式上传的文件记录
+    ///
+    /// 文件内容随后通过 `save_file_segment` 逐段保存，`complete_file_upload` 之前文件不可见。
     ///
     /// # 参数
+    /// - `file_id`: 文件 ID，同时绑定在每个分段的附加认证数据中。
     /// - `user_id`: 上传者 ID。
     /// - `file_name`: 文件名。
+    /// - `iv`: 分段随机数的前缀。
+    /// - `cipher_suite`: 文件内容使用的加密算法。
+    /// - `segment_size`: 每个分段的明文大小（字节）。
+    ///
+    /// # 返回
+    /// 返回操作结果（成功或错误）。
+    #[allow(clippy::too_many_arguments)]
+    async fn create_file(
+        &self,
+        file_id: Uuid,
+        user_id: Uuid,
+        file_name: String,
+        iv: Vec<u8>,
+        cipher_suite: CipherSuite,
+        segment_size: i32,
+    ) -> Result<(), sqlx::Error>;
+
+    /// 保存一个加密后的文件分段
+    ///
+    /// # 参数
+    /// - `file_id`: 文件 ID。
+    /// - `seq`: 分段序号，从 0 开始。
+    /// - `data`: 加密后的分段内容。
+    ///
+    /// # 返回
+    /// 返回操作结果（成功或错误）。
+    async fn save_file_segment(
+        &self,
+        file_id: Uuid,
+        seq: i32,
+        data: Vec<u8>,
+    ) -> Result<(), sqlx::Error>;
+
+    /// 完成流式上传，在同一事务中写入文件大小、分段数量和包装后的文件密钥并创建共享链接
+    ///
+    /// # 参数
+    /// - `file_id`: 文件 ID。
     /// - `file_size`: 文件大小（字节）。
+    /// - `segment_count`: 分段数量。
+    /// - `file_keys`: 设备公钥 ID 和使用该公钥包装的文件密钥（当前包装格式，见 `cipher::key_wrap_label`），包括接收者和发送者的副本。
     /// - `recipient_user_id`: 接收者 ID。
-    /// - `password`: 文件密码。
+    /// - `password`: 共享链接密码的 Argon2 哈希。
     /// - `expiration_date`: 文件到期时间。
-    /// - `encrypted_aes_key`: 加密后的 AES 密钥。
-    /// - `encrypted_file`: 加密后的文件内容。
-    /// - `iv`: 初始化向量。
     ///
     /// # 返回
     /// 返回操作结果（成功或错误）。
-    async fn save_encrypted_file(
+    #[allow(clippy::too_many_arguments)]
+    async fn complete_file_upload(
         &self,
-        user_id: Uuid,
-        file_name: String,
+        file_id: Uuid,
         file_size: i64,
+        segment_count: i32,
+        file_keys: Vec<(Uuid, Vec<u8>)>,
         recipient_user_id: Uuid,
         password: String,
         expiration_date: DateTime<Utc>,
-        encrypted_aes_key: Vec<u8>,
-        encrypted_file: Vec<u8>,
-        iv: Vec<u8>,
     ) -> Result<(), sqlx::Error>;
 
+    /// 将已上传的文件转发给新的接收者，在同一事务中保存为其包装的文件密钥并创建共享链接
+    ///
+    /// # 参数
+    /// - `file_id`: 文件 ID。
+    /// - `recipient_user_id`: 新接收者 ID。
+    /// - `file_keys`: 新接收者的设备公钥 ID 和使用该公钥包装的文件密钥（当前包装格式，见 `cipher::key_wrap_label`），已持有该文件密钥的公钥保留原密钥。
+    /// - `password`: 共享链接密码的 Argon2 哈希。
+    /// - `expiration_date`: 共享链接到期时间。
+    ///
+    /// # 返回
+    /// 返回操作结果（成功或错误）。
+    async fn share_file(
+        &self,
+        file_id: Uuid,
+        recipient_user_id: Uuid,
+        file_keys: Vec<(Uuid, Vec<u8>)>,
+        password: String,
+        expiration_date: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error>;
+
+    /// 获取为某个用户的有效设备公钥包装的文件密钥
+    ///
+    /// # 参数
+    /// - `file_id`: 文件 ID。
+    /// - `user_id`: 用户 ID。
+    ///
+    /// # 返回
+    /// 返回每把设备公钥对应的文件密钥或查询错误，该用户没有密钥时返回空列表。
+    async fn get_file_keys(
+        &self,
+        file_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<FileKey>, sqlx::Error>;
+
+    /// 删除文件及其分段和共享链接，用于清理失败的上传
+    ///
+    /// # 参数
+    /// - `file_id`: 文件 ID。
+    ///
+    /// # 返回
+    /// 返回操作结果（成功或错误）。
+    async fn delete_file(&self, file_id: Uuid) -> Result<(), sqlx::Error>;
+
     /// 获取共享链接信息
     ///
     /// # 参数
@@ -153,6 +424,26 @@ pub trait UserExt {
         user_id: Uuid,
     ) -> Result<Option<SharedLink>, sqlx::Error>;
 
+    /// 更新共享链接的密码
+    ///
+    /// # 参数
+    /// - `shared_id`: 共享链接 ID。
+    /// - `password`: 新的密码哈希。
+    ///
+    /// # 返回
+    /// 返回操作结果（成功或错误）。
+    async fn update_shared_password(
+        &self,
+        shared_id: Uuid,
+        password: String,
+    ) -> Result<(), sqlx::Error>;
+
+    /// 获取仍以明文存储密码的共享链接
+    ///
+    /// # 返回
+    /// 返回密码没有 `$argon2` 前缀的共享链接列表或查询错误。
+    async fn get_legacy_shared_links(&self) -> Result<Vec<SharedLink>, sqlx::Error>;
+
     /// 获取文件信息
     ///
     /// # 参数
@@ -165,6 +456,20 @@ pub trait UserExt {
         file_id: Uuid,
     ) -> Result<Option<File>, sqlx::Error>;
 
+    /// 获取流式格式文件的一个加密分段
+    ///
+    /// # 参数
+    /// - `file_id`: 文件 ID。
+    /// - `seq`: 分段序号。
+    ///
+    /// # 返回
+    /// 返回分段内容或查询错误，分段不存在时返回 `None`。
+    async fn get_file_segment(
+        &self,
+        file_id: Uuid,
+        seq: i32,
+    ) -> Result<Option<Vec<u8>>, sqlx::Error>;
+
     /// 获取用户发送的文件列表
     ///
     /// # 参数
@@ -177,7 +482,7 @@ pub trait UserExt {
     async fn get_sent_files(
         &self,
         user_id: Uuid,
-        page: u32,
+        page: usize,
         limit: usize
     ) -> Result<(Vec<SendFileDetails>, i64), sqlx::Error>;
 
@@ -193,7 +498,7 @@ pub trait UserExt {
     async fn get_receive_files(
         &self,
         user_id: Uuid,
-        page: u32,
+        page: usize,
         limit: usize
     ) -> Result<(Vec<ReceiveFileDetails>, i64), sqlx::Error>;
 
@@ -204,399 +509,2285 @@ pub trait UserExt {
     async fn delete_expired_files(
         &self
     ) -> Result<(), sqlx::Error>;
-}
 
+    /// 创建会话（保存一个新的刷新令牌）
+    ///
+    /// # 参数
+    /// - `user_id`: 用户 ID。
+    /// - `family_id`: 会话族 ID，首次登录时新生成，轮换时沿用。
+    /// - `refresh_token_hash`: 刷新令牌的 SHA-256 摘要。
+    /// - `expires_at`: 刷新令牌的过期时间。
+    ///
+    /// # 返回
+    /// 返回保存成功的 `Session` 或操作错误。
+    async fn create_session(
+        &self,
+        user_id: Uuid,
+        family_id: Uuid,
+        refresh_token_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Session, sqlx::Error>;
 
-#[async_trait]
-impl UserExt for DBClient {
-    async fn get_user(
+    /// 根据刷新令牌摘要获取会话
+    ///
+    /// # 参数
+    /// - `refresh_token_hash`: 刷新令牌的 SHA-256 摘要。
+    ///
+    /// # 返回
+    /// 返回会话信息或查询错误。
+    async fn get_session_by_token_hash(
         &self,
-        user_id: Option<Uuid>,
-        name: Option<&str>,
-        email: Option<&str>,
-    ) -> Result<Option<User>, sqlx::Error> {
-        let mut user: Option<User> = None;
+        refresh_token_hash: &str,
+    ) -> Result<Option<Session>, sqlx::Error>;
 
-        if let Some(user_id) = user_id {
-            user = sqlx::query_as!(
-                User,
-                r#"SELECT id, name, email, password, public_key, created_at, updated_at FROM users WHERE id = $1"#,
-                user_id
-            ).fetch_optional(&self.pool).await?;
-        } else if let Some(name) = name {
-            user = sqlx::query_as!(
-                User,
-                r#"SELECT id, name, email, password, public_key, created_at, updated_at FROM users WHERE name = $1"#,
-                name
-            ).fetch_optional(&self.pool).await?;
-        } else if let Some(email) = email {
-            user = sqlx::query_as!(
-                User,
-                r#"SELECT id, name, email, password, public_key, created_at, updated_at FROM users WHERE email = $1"#,
-                email
-            ).fetch_optional(&self.pool).await?;
-        }
+    /// 将刷新令牌标记为已轮换
+    ///
+    /// 只有尚未使用的令牌才会被标记，用于在并发请求中保证令牌只能被轮换一次。
+    ///
+    /// # 参数
+    /// - `session_id`: 会话记录 ID。
+    ///
+    /// # 返回
+    /// 本次成功标记时返回 `true`，令牌已被使用过时返回 `false`。
+    async fn mark_session_used(&self, session_id: Uuid) -> Result<bool, sqlx::Error>;
 
-        Ok(user)
-    }
+    /// 吊销整个会话族
+    ///
+    /// # 参数
+    /// - `family_id`: 会话族 ID。
+    ///
+    /// # 返回
+    /// 返回操作结果（成功或错误）。
+    async fn revoke_session_family(&self, family_id: Uuid) -> Result<(), sqlx::Error>;
 
-    async fn save_user<T: Into<String> + Send>(
-        &self,
-        name: T,
-        email: T,
-        password: T,
-    ) -> Result<User, sqlx::Error> {
-        let user = sqlx::query_as!(
-            User,
-            r#"
-            INSERT INTO users (name, email, password) 
-            VALUES ($1, $2, $3) 
-            RETURNING id, name, email, password, public_key, created_at, updated_at
-            "#,
-            name.into(),
-            email.into(),
-            password.into()
-        )
-        .fetch_one(&self.pool)
-        .await?;
-        Ok(user)
-    }
+    /// 判断会话族是否仍然有效（未被吊销且未过期）
+    ///
+    /// # 参数
+    /// - `family_id`: 会话族 ID。
+    ///
+    /// # 返回
+    /// 返回会话是否有效或查询错误。
+    async fn is_session_active(&self, family_id: Uuid) -> Result<bool, sqlx::Error>;
 
-    async fn update_user_name<T: Into<String> + Send>(
+    /// 创建个人访问令牌
+    ///
+    /// # 参数
+    /// - `user_id`: 用户 ID。
+    /// - `name`: 令牌名称。
+    /// - `token_hash`: 令牌的 SHA-256 摘要。
+    /// - `token_prefix`: 令牌的展示前缀。
+    /// - `scopes`: 令牌的权限范围。
+    /// - `expires_at`: 令牌的过期时间。
+    ///
+    /// # 返回
+    /// 返回保存成功的 `PersonalAccessToken` 或操作错误。
+    async fn create_personal_access_token(
         &self,
         user_id: Uuid,
-        new_name: T,
-    ) -> Result<User, sqlx::Error> {
-        let user = sqlx::query_as!(
-            User,
-            r#"
-            UPDATE users
-            SET name = $1, updated_at = Now()
-            WHERE id = $2
-            RETURNING id, name, email, password, public_key, created_at, updated_at
-            "#,
-            new_name.into(),
-            user_id
-        )
-        .fetch_one(&self.pool)
-        .await?;
+        name: String,
+        token_hash: String,
+        token_prefix: String,
+        scopes: Vec<String>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<PersonalAccessToken, sqlx::Error>;
 
-        Ok(user)
-    }
+    /// 获取用户的全部个人访问令牌
+    ///
+    /// # 参数
+    /// - `user_id`: 用户 ID。
+    ///
+    /// # 返回
+    /// 返回按创建时间倒序排列的令牌列表或查询错误。
+    async fn get_personal_access_tokens(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<PersonalAccessToken>, sqlx::Error>;
+
+    /// 吊销用户的个人访问令牌
+    ///
+    /// # 参数
+    /// - `user_id`: 用户 ID。
+    /// - `token_id`: 令牌 ID。
+    ///
+    /// # 返回
+    /// 令牌存在且属于该用户时返回 `true`。
+    async fn revoke_personal_access_token(
+        &self,
+        user_id: Uuid,
+        token_id: Uuid,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// 使用个人访问令牌
+    ///
+    /// 只返回未吊销且未过期的令牌，并记录最近一次使用时间。
+    ///
+    /// # 参数
+    /// - `token_hash`: 令牌的 SHA-256 摘要。
+    ///
+    /// # 返回
+    /// 返回有效的令牌或查询错误。
+    async fn use_personal_access_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<PersonalAccessToken>, sqlx::Error>;
+
+    /// 获取用户的 TOTP 两步验证配置
+    ///
+    /// # 参数
+    /// - `user_id`: 用户 ID。
+    ///
+    /// # 返回
+    /// 返回 `UserTotp` 或 `None`（用户从未生成过密钥）。
+    async fn get_user_totp(&self, user_id: Uuid) -> Result<Option<UserTotp>, sqlx::Error>;
+
+    /// 保存待确认的 TOTP 密钥
+    ///
+    /// 已启用两步验证的用户不会被覆盖，未确认的旧密钥会被替换。
+    ///
+    /// # 参数
+    /// - `user_id`: 用户 ID。
+    /// - `secret`: Base32 编码的密钥。
+    ///
+    /// # 返回
+    /// 保存成功时返回 `true`，用户已启用两步验证时返回 `false`。
+    async fn save_user_totp_secret(&self, user_id: Uuid, secret: String) -> Result<bool, sqlx::Error>;
+
+    /// 启用两步验证并替换恢复码
+    ///
+    /// # 参数
+    /// - `user_id`: 用户 ID。
+    /// - `step`: 确认时验证通过的时间步。
+    /// - `recovery_code_hashes`: 恢复码的摘要列表。
+    ///
+    /// # 返回
+    /// 操作成功或数据库错误。
+    async fn enable_user_totp(
+        &self,
+        user_id: Uuid,
+        step: i64,
+        recovery_code_hashes: Vec<String>,
+    ) -> Result<(), sqlx::Error>;
+
+    /// 记录验证通过的时间步
+    ///
+    /// # 参数
+    /// - `user_id`: 用户 ID。
+    /// - `step`: 验证通过的时间步。
+    ///
+    /// # 返回
+    /// 时间步比已记录的更新时返回 `true`，否则说明验证码已被使用。
+    async fn update_totp_last_used_step(&self, user_id: Uuid, step: i64) -> Result<bool, sqlx::Error>;
+
+    /// 使用恢复码
+    ///
+    /// # 参数
+    /// - `user_id`: 用户 ID。
+    /// - `code_hash`: 恢复码的摘要。
+    ///
+    /// # 返回
+    /// 恢复码存在且未使用时返回 `true`，同时标记为已使用。
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str) -> Result<bool, sqlx::Error>;
+
+    /// 关闭两步验证，删除密钥和全部恢复码
+    ///
+    /// # 参数
+    /// - `user_id`: 用户 ID。
+    ///
+    /// # 返回
+    /// 操作成功或数据库错误。
+    async fn disable_user_totp(&self, user_id: Uuid) -> Result<(), sqlx::Error>;
+
+    /// 创建邮件令牌，同一用途下尚未使用的旧令牌全部作废
+    ///
+    /// # 参数
+    /// - `user_id`: 用户 ID。
+    /// - `purpose`: 令牌用途。
+    /// - `token_hash`: 令牌的 SHA-256 摘要。
+    /// - `expires_at`: 令牌的过期时间。
+    ///
+    /// # 返回
+    /// 操作成功或数据库错误。
+    async fn create_email_token(
+        &self,
+        user_id: Uuid,
+        purpose: EmailTokenPurpose,
+        token_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error>;
+
+    /// 查询邮件令牌所属的用户，不将令牌标记为已使用
+    ///
+    /// 用于在使用令牌之前做其他校验，校验不通过时令牌仍然有效。
+    ///
+    /// # 参数
+    /// - `token_hash`: 令牌的 SHA-256 摘要。
+    /// - `purpose`: 令牌用途。
+    ///
+    /// # 返回
+    /// 返回令牌所属的用户 ID，过期或已使用的令牌返回 `None`。
+    async fn peek_email_token(
+        &self,
+        token_hash: &str,
+        purpose: EmailTokenPurpose,
+    ) -> Result<Option<Uuid>, sqlx::Error>;
+
+    /// 使用邮件令牌
+    ///
+    /// 令牌只能使用一次，过期或已使用的令牌返回 `None`。
+    ///
+    /// # 参数
+    /// - `token_hash`: 令牌的 SHA-256 摘要。
+    /// - `purpose`: 令牌用途。
+    ///
+    /// # 返回
+    /// 返回令牌所属的用户 ID 或 `None`。
+    async fn consume_email_token(
+        &self,
+        token_hash: &str,
+        purpose: EmailTokenPurpose,
+    ) -> Result<Option<Uuid>, sqlx::Error>;
+
+    /// 将用户邮箱标记为已验证
+    ///
+    /// # 参数
+    /// - `user_id`: 用户 ID。
+    ///
+    /// # 返回
+    /// 操作成功或数据库错误。
+    async fn verify_user_email(&self, user_id: Uuid) -> Result<(), sqlx::Error>;
+
+    /// 由邮箱所有者接管邮箱尚未验证的账户
+    ///
+    /// 邮箱未验证的账户可能由他人抢先注册。在同一事务中将邮箱标记为已验证、设置新密码、
+    /// 递增令牌版本号，并吊销全部会话、个人访问令牌和设备公钥，删除两步验证密钥和恢复码。
+    ///
+    /// # 参数
+    /// - `user_id`: 用户 ID。
+    /// - `password`: 新密码的哈希。
+    /// - `has_local_password`: 新密码是否由用户自己设置，随机密码为 `false`。
+    ///
+    /// # 返回
+    /// 返回接管后的 `User`，邮箱已经验证过时不做任何修改并返回 `None`。
+    async fn claim_unverified_user(
+        &self,
+        user_id: Uuid,
+        password: String,
+        has_local_password: bool,
+    ) -> Result<Option<User>, sqlx::Error>;
+
+    /// 获取登录限流记录
+    ///
+    /// # 参数
+    /// - `scope`: 限流维度（account 或 ip）。
+    /// - `throttle_key`: 限流键。
+    ///
+    /// # 返回
+    /// 返回 `LoginThrottle` 或 `None`（从未失败过）。
+    async fn get_login_throttle(
+        &self,
+        scope: &str,
+        throttle_key: &str,
+    ) -> Result<Option<LoginThrottle>, sqlx::Error>;
+
+    /// 记录一次登录失败
+    ///
+    /// 距离上次失败超过统计窗口时重新计数。
+    ///
+    /// # 参数
+    /// - `scope`: 限流维度（account 或 ip）。
+    /// - `throttle_key`: 限流键。
+    /// - `window_minutes`: 统计窗口（分钟）。
+    ///
+    /// # 返回
+    /// 返回窗口内的连续失败次数。
+    async fn record_login_failure(
+        &self,
+        scope: &str,
+        throttle_key: &str,
+        window_minutes: i32,
+    ) -> Result<i32, sqlx::Error>;
+
+    /// 锁定登录
+    ///
+    /// # 参数
+    /// - `scope`: 限流维度（account 或 ip）。
+    /// - `throttle_key`: 限流键。
+    /// - `locked_until`: 在此时间之前拒绝登录。
+    ///
+    /// # 返回
+    /// 操作成功或数据库错误。
+    async fn lock_login(
+        &self,
+        scope: &str,
+        throttle_key: &str,
+        locked_until: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error>;
+
+    /// 清除登录限流记录（登录成功或管理员解锁时调用）
+    ///
+    /// # 参数
+    /// - `scope`: 限流维度，为空时清除该键在所有维度下的记录。
+    /// - `throttle_key`: 限流键。
+    ///
+    /// # 返回
+    /// 返回清除的记录数。
+    async fn clear_login_throttle(
+        &self,
+        scope: Option<&str>,
+        throttle_key: &str,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// 获取当前处于锁定状态的登录限流记录
+    ///
+    /// # 返回
+    /// 返回按解锁时间排序的记录列表或查询错误。
+    async fn get_locked_login_throttles(&self) -> Result<Vec<LoginThrottle>, sqlx::Error>;
+
+    /// 保存 OIDC 授权请求，同时清理已过期的请求
+    ///
+    /// # 参数
+    /// - `state_hash`: state 参数的 SHA-256 摘要。
+    /// - `nonce`: ID Token 中必须携带的 nonce。
+    /// - `pkce_verifier`: PKCE 校验码。
+    /// - `expires_at`: 过期时间。
+    ///
+    /// # 返回
+    /// 操作成功或数据库错误。
+    async fn create_oidc_auth_request(
+        &self,
+        state_hash: String,
+        nonce: String,
+        pkce_verifier: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error>;
+
+    /// 取出并删除 OIDC 授权请求
+    ///
+    /// # 参数
+    /// - `state_hash`: state 参数的 SHA-256 摘要。
+    ///
+    /// # 返回
+    /// 返回未过期的授权请求或 `None`。
+    async fn consume_oidc_auth_request(
+        &self,
+        state_hash: &str,
+    ) -> Result<Option<OidcAuthRequest>, sqlx::Error>;
+
+    /// 根据外部身份查询用户
+    ///
+    /// # 参数
+    /// - `issuer`: 身份提供方标识。
+    /// - `subject`: 用户在身份提供方中的主体 ID。
+    ///
+    /// # 返回
+    /// 返回关联的 `User` 或 `None`。
+    async fn get_user_by_identity(
+        &self,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<Option<User>, sqlx::Error>;
+
+    /// 为用户关联外部身份
+    ///
+    /// # 参数
+    /// - `user_id`: 用户 ID。
+    /// - `issuer`: 身份提供方标识。
+    /// - `subject`: 用户在身份提供方中的主体 ID。
+    ///
+    /// # 返回
+    /// 操作成功或数据库错误。
+    async fn link_user_identity(
+        &self,
+        user_id: Uuid,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<(), sqlx::Error>;
+
+    /// 判断用户是否关联了指定身份提供方的外部身份
+    ///
+    /// # 参数
+    /// - `user_id`: 用户 ID。
+    /// - `issuer`: 身份提供方标识。
+    ///
+    /// # 返回
+    /// 已关联返回 `true`，否则返回 `false`。
+    async fn has_user_identity(&self, user_id: Uuid, issuer: &str) -> Result<bool, sqlx::Error>;
+
+    /// 使用外部身份提供方中的资料更新用户名和邮箱
+    ///
+    /// 邮箱发生变化时视为已由身份提供方验证。
+    ///
+    /// # 参数
+    /// - `user_id`: 用户 ID。
+    /// - `name`: 新用户名。
+    /// - `email`: 新邮箱。
+    ///
+    /// # 返回
+    /// 返回更新后的 `User` 或操作错误。
+    async fn update_user_profile(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        email: &str,
+    ) -> Result<User, sqlx::Error>;
+}
+
+
+#[async_trait]
+impl UserExt for DBClient {
+    async fn get_user(
+        &self,
+        user_id: Option<Uuid>,
+        name: Option<&str>,
+        email: Option<&str>,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let mut user: Option<User> = None;
+
+        if let Some(user_id) = user_id {
+            user = sqlx::query_as!(
+                User,
+                r#"SELECT id, name, email, password, token_version, email_verified_at, created_at, updated_at FROM users WHERE id = $1"#,
+                user_id
+            ).fetch_optional(&self.pool).await?;
+        } else if let Some(name) = name {
+            user = sqlx::query_as!(
+                User,
+                r#"SELECT id, name, email, password, token_version, email_verified_at, created_at, updated_at FROM users WHERE name = $1"#,
+                name
+            ).fetch_optional(&self.pool).await?;
+        } else if let Some(email) = email {
+            user = sqlx::query_as!(
+                User,
+                r#"SELECT id, name, email, password, token_version, email_verified_at, created_at, updated_at FROM users WHERE email = $1"#,
+                email
+            ).fetch_optional(&self.pool).await?;
+        }
+
+        Ok(user)
+    }
+
+    async fn save_user<T: Into<String> + Send>(
+        &self,
+        name: T,
+        email: T,
+        password: T,
+    ) -> Result<User, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            INSERT INTO users (name, email, password) 
+            VALUES ($1, $2, $3) 
+            RETURNING id, name, email, password, token_version, email_verified_at, created_at, updated_at
+            "#,
+            name.into(),
+            email.into(),
+            password.into()
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(user)
+    }
+
+    async fn update_user_name<T: Into<String> + Send>(
+        &self,
+        user_id: Uuid,
+        new_name: T,
+    ) -> Result<User, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET name = $1, updated_at = Now()
+            WHERE id = $2
+            RETURNING id, name, email, password, token_version, email_verified_at, created_at, updated_at
+            "#,
+            new_name.into(),
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
 
     async fn update_user_password(
         &self,
         user_id: Uuid,
         new_password: String,
     ) -> Result<User, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
         let user = sqlx::query_as!(
             User,
             r#"
-            UPDATE users
-            SET password = $1, updated_at = Now()
-            WHERE id = $2
-            RETURNING id, name, email, password, public_key, created_at, updated_at
+            UPDATE users
+            SET password = $1, has_local_password = TRUE, token_version = token_version + 1, updated_at = Now()
+            WHERE id = $2
+            RETURNING id, name, email, password, token_version, email_verified_at, created_at, updated_at
+            "#,
+            new_password,
+            user_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET revoked_at = NOW()
+            WHERE user_id = $1
+            AND revoked_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Personal access tokens do not carry the token version, so revoke them explicitly
+        sqlx::query!(
+            r#"
+            UPDATE personal_access_tokens
+            SET revoked_at = NOW()
+            WHERE user_id = $1
+            AND revoked_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn invalidate_user_tokens(&self, user_id: Uuid) -> Result<User, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET token_version = token_version + 1, updated_at = Now()
+            WHERE id = $1
+            RETURNING id, name, email, password, token_version, email_verified_at, created_at, updated_at
+            "#,
+            user_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET revoked_at = NOW()
+            WHERE user_id = $1
+            AND revoked_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Personal access tokens do not carry the token version, so revoke them explicitly
+        sqlx::query!(
+            r#"
+            UPDATE personal_access_tokens
+            SET revoked_at = NOW()
+            WHERE user_id = $1
+            AND revoked_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn has_local_password(&self, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        let has_local_password = sqlx::query_scalar!(
+            r#"
+            SELECT has_local_password
+            FROM users
+            WHERE id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(has_local_password)
+    }
+
+    async fn save_user_key(
+        &self,
+        user_id: Uuid,
+        label: String,
+        algorithm: &str,
+        public_key: String,
+        fingerprint: String,
+    ) -> Result<Option<UserKey>, sqlx::Error> {
+        let user_key = sqlx::query_as!(
+            UserKey,
+            r#"
+            INSERT INTO user_keys (user_id, label, algorithm, public_key, fingerprint)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (user_id, fingerprint) WHERE revoked_at IS NULL DO NOTHING
+            RETURNING id, user_id, label, algorithm, public_key, fingerprint, created_at, revoked_at, replaced_by
+            "#,
+            user_id,
+            label,
+            algorithm,
+            public_key,
+            fingerprint
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user_key)
+    }
+
+    async fn get_user_keys(&self, user_id: Uuid) -> Result<Vec<UserKey>, sqlx::Error> {
+        let user_keys = sqlx::query_as!(
+            UserKey,
+            r#"
+            SELECT id, user_id, label, algorithm, public_key, fingerprint, created_at, revoked_at, replaced_by
+            FROM user_keys
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(user_keys)
+    }
+
+    async fn get_active_user_keys(&self, user_id: Uuid) -> Result<Vec<UserKey>, sqlx::Error> {
+        let user_keys = sqlx::query_as!(
+            UserKey,
+            r#"
+            SELECT id, user_id, label, algorithm, public_key, fingerprint, created_at, revoked_at, replaced_by
+            FROM user_keys
+            WHERE user_id = $1
+            AND revoked_at IS NULL
+            ORDER BY created_at
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(user_keys)
+    }
+
+    async fn revoke_user_key(&self, user_id: Uuid, key_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_keys
+            SET revoked_at = COALESCE(revoked_at, NOW())
+            WHERE id = $1
+            AND user_id = $2
+            "#,
+            key_id,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn get_all_active_user_keys(&self) -> Result<Vec<UserKey>, sqlx::Error> {
+        let user_keys = sqlx::query_as!(
+            UserKey,
+            r#"
+            SELECT id, user_id, label, algorithm, public_key, fingerprint, created_at, revoked_at, replaced_by
+            FROM user_keys
+            WHERE revoked_at IS NULL
+            ORDER BY created_at
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(user_keys)
+    }
+
+    async fn update_user_key_fingerprint(
+        &self,
+        key_id: Uuid,
+        public_key: String,
+        fingerprint: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE user_keys
+            SET public_key = $1, fingerprint = $2
+            WHERE id = $3
+            "#,
+            public_key,
+            fingerprint,
+            key_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn create_key_rotation_challenge(
+        &self,
+        user_id: Uuid,
+        user_key_id: Uuid,
+        challenge_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // Only the latest challenge for a key can be answered
+        sqlx::query!(
+            r#"
+            UPDATE key_rotation_challenges
+            SET used_at = NOW()
+            WHERE user_key_id = $1
+            AND used_at IS NULL
+            "#,
+            user_key_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO key_rotation_challenges (user_id, user_key_id, challenge_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            user_id,
+            user_key_id,
+            challenge_hash,
+            expires_at
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get_pending_file_keys(
+        &self,
+        user_key_id: Uuid,
+    ) -> Result<Vec<PendingFileKey>, sqlx::Error> {
+        // A key is still needed while the holder has an unexpired link to the file,
+        // either as its recipient or as the sender of the file
+        let file_keys = sqlx::query_as!(
+            PendingFileKey,
+            r#"
+            SELECT fk.file_id, fk.encrypted_aes_key, fk.key_wrap_version
+            FROM file_keys fk
+            JOIN files f ON f.id = fk.file_id
+            WHERE fk.user_key_id = $1
+            AND EXISTS (
+                SELECT 1 FROM shared_links sl
+                WHERE sl.file_id = fk.file_id
+                AND sl.expiration_date > NOW()
+                AND (sl.recipient_user_id = fk.user_id OR f.user_id = fk.user_id)
+            )
+            ORDER BY fk.file_id
+            "#,
+            user_key_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(file_keys)
+    }
+
+    async fn rotate_user_key(
+        &self,
+        user_id: Uuid,
+        old_key_id: Uuid,
+        challenge_hash: &str,
+        label: String,
+        algorithm: &str,
+        public_key: String,
+        fingerprint: String,
+        file_keys: Vec<(Uuid, Vec<u8>)>,
+    ) -> Result<KeyRotationOutcome, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // Lock the old key so concurrent rotations or revocations cannot interleave
+        let old_key = sqlx::query_scalar!(
+            r#"
+            SELECT id
+            FROM user_keys
+            WHERE id = $1
+            AND user_id = $2
+            AND revoked_at IS NULL
+            FOR UPDATE
+            "#,
+            old_key_id,
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if old_key.is_none() {
+            return Ok(KeyRotationOutcome::InvalidChallenge);
+        }
+
+        let challenge = sqlx::query_scalar!(
+            r#"
+            UPDATE key_rotation_challenges
+            SET used_at = NOW()
+            WHERE challenge_hash = $1
+            AND user_id = $2
+            AND user_key_id = $3
+            AND used_at IS NULL
+            AND expires_at > NOW()
+            RETURNING id
+            "#,
+            challenge_hash,
+            user_id,
+            old_key_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if challenge.is_none() {
+            return Ok(KeyRotationOutcome::InvalidChallenge);
+        }
+
+        let new_key = sqlx::query_as!(
+            UserKey,
+            r#"
+            INSERT INTO user_keys (user_id, label, algorithm, public_key, fingerprint)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (user_id, fingerprint) WHERE revoked_at IS NULL DO NOTHING
+            RETURNING id, user_id, label, algorithm, public_key, fingerprint, created_at, revoked_at, replaced_by
+            "#,
+            user_id,
+            label,
+            algorithm,
+            public_key,
+            fingerprint
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(new_key) = new_key else {
+            return Ok(KeyRotationOutcome::DuplicateKey);
+        };
+
+        // The rewrapped keys must cover exactly the keys that are still pending,
+        // otherwise a share created or expired since the challenge would be lost or resurrected
+        let pending_file_ids = sqlx::query_scalar!(
+            r#"
+            SELECT fk.file_id
+            FROM file_keys fk
+            JOIN files f ON f.id = fk.file_id
+            WHERE fk.user_key_id = $1
+            AND EXISTS (
+                SELECT 1 FROM shared_links sl
+                WHERE sl.file_id = fk.file_id
+                AND sl.expiration_date > NOW()
+                AND (sl.recipient_user_id = fk.user_id OR f.user_id = fk.user_id)
+            )
+            ORDER BY fk.file_id
+            FOR UPDATE OF fk
+            "#,
+            old_key_id
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let mut submitted_file_ids: Vec<Uuid> = file_keys.iter().map(|(file_id, _)| *file_id).collect();
+        submitted_file_ids.sort();
+        submitted_file_ids.dedup();
+
+        if submitted_file_ids.len() != file_keys.len() || submitted_file_ids != pending_file_ids {
+            return Ok(KeyRotationOutcome::StaleFileKeys);
+        }
+
+        for (file_id, encrypted_aes_key) in file_keys {
+            sqlx::query!(
+                r#"
+                UPDATE file_keys
+                SET user_key_id = $3, encrypted_aes_key = $4, key_wrap_version = $5, created_at = NOW()
+                WHERE file_id = $1
+                AND user_key_id = $2
+                "#,
+                file_id,
+                old_key_id,
+                new_key.id,
+                encrypted_aes_key,
+                KEY_WRAP_VERSION
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        // The old key stays in the history as revoked, pointing at its replacement
+        sqlx::query!(
+            r#"
+            UPDATE user_keys
+            SET revoked_at = NOW(), replaced_by = $2
+            WHERE id = $1
+            "#,
+            old_key_id,
+            new_key.id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(KeyRotationOutcome::Rotated(new_key))
+    }
+
+    async fn search_by_email(
+        &self,
+        user_id: Uuid,
+        query: String,
+        limit: i64,
+    ) -> Result<Vec<User>, sqlx::Error> {
+        // 转义 LIKE 通配符，只做前缀匹配
+        let pattern = format!("{}%", escape_like(&query));
+
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            SELECT id, name, email, password, token_version, email_verified_at, created_at, updated_at
+            FROM users
+            WHERE email LIKE $1 ESCAPE '\'
+            AND EXISTS (
+                SELECT 1 FROM user_keys uk
+                WHERE uk.user_id = users.id AND uk.revoked_at IS NULL
+            )
+            AND email_verified_at IS NOT NULL
+            AND id != $2
+            ORDER BY email
+            LIMIT $3
+            "#,
+            pattern,
+            user_id,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn create_file(
+        &self,
+        file_id: Uuid,
+        user_id: Uuid,
+        file_name: String,
+        iv: Vec<u8>,
+        cipher_suite: CipherSuite,
+        segment_size: i32,
+    ) -> Result<(), sqlx::Error> {
+        // The content lives in file_segments, so encrypted_file stays empty
+        sqlx::query!(
+            r#"
+            INSERT INTO files (id, user_id, file_name, file_size, encrypted_file, iv, cipher_suite, format_version, segment_size, created_at)
+            VALUES ($1, $2, $3, 0, '', $4, $5, $6, $7, NOW())
+            "#,
+            file_id,
+            user_id,
+            file_name,
+            iv,
+            cipher_suite.as_str(),
+            STREAM_FORMAT_VERSION,
+            segment_size
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn save_file_segment(
+        &self,
+        file_id: Uuid,
+        seq: i32,
+        data: Vec<u8>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO file_segments (file_id, seq, data)
+            VALUES ($1, $2, $3)
+            "#,
+            file_id,
+            seq,
+            data
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn complete_file_upload(
+        &self,
+        file_id: Uuid,
+        file_size: i64,
+        segment_count: i32,
+        file_keys: Vec<(Uuid, Vec<u8>)>,
+        recipient_user_id: Uuid,
+        password: String,
+        expiration_date: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE files
+            SET file_size = $2, segment_count = $3
+            WHERE id = $1
+            "#,
+            file_id,
+            file_size,
+            segment_count
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // The sender may also be the recipient, in which case both copies are the same key
+        for (user_key_id, encrypted_aes_key) in file_keys {
+            sqlx::query!(
+                r#"
+                INSERT INTO file_keys (file_id, user_id, user_key_id, encrypted_aes_key, key_wrap_version)
+                SELECT $1, user_id, id, $3, $4
+                FROM user_keys
+                WHERE id = $2
+                ON CONFLICT (file_id, user_key_id) DO NOTHING
+                "#,
+                file_id,
+                user_key_id,
+                encrypted_aes_key,
+                KEY_WRAP_VERSION
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        // Insert into the shared_links table using the file_id
+        sqlx::query!(
+            r#"
+            INSERT INTO shared_links (file_id, recipient_user_id, password, expiration_date, created_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            "#,
+            file_id,
+            recipient_user_id,
+            password,
+            expiration_date
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn share_file(
+        &self,
+        file_id: Uuid,
+        recipient_user_id: Uuid,
+        file_keys: Vec<(Uuid, Vec<u8>)>,
+        password: String,
+        expiration_date: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // A device key that already holds this file keeps the existing wrapped key,
+        // so a bad client-side wrap cannot lock the recipient out of their earlier shares
+        for (user_key_id, encrypted_aes_key) in file_keys {
+            sqlx::query!(
+                r#"
+                INSERT INTO file_keys (file_id, user_id, user_key_id, encrypted_aes_key, key_wrap_version)
+                SELECT $1, user_id, id, $4, $5
+                FROM user_keys
+                WHERE id = $2 AND user_id = $3
+                ON CONFLICT (file_id, user_key_id) DO NOTHING
+                "#,
+                file_id,
+                user_key_id,
+                recipient_user_id,
+                encrypted_aes_key,
+                KEY_WRAP_VERSION
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO shared_links (file_id, recipient_user_id, password, expiration_date, created_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            "#,
+            file_id,
+            recipient_user_id,
+            password,
+            expiration_date
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get_file_keys(
+        &self,
+        file_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<FileKey>, sqlx::Error> {
+        // Keys wrapped for revoked device keys are no longer handed out
+        let file_keys = sqlx::query_as!(
+            FileKey,
+            r#"
+            SELECT fk.user_key_id, uk.fingerprint, fk.encrypted_aes_key, fk.key_wrap_version
+            FROM file_keys fk
+            JOIN user_keys uk ON uk.id = fk.user_key_id
+            WHERE fk.file_id = $1 AND fk.user_id = $2
+            AND uk.revoked_at IS NULL
+            ORDER BY uk.created_at
+            "#,
+            file_id,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(file_keys)
+    }
+
+    async fn delete_file(&self, file_id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            DELETE FROM files
+            WHERE id = $1
+            "#,
+            file_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_shared(
+        &self,
+        shared_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<SharedLink>, sqlx::Error> {
+        let shared_link = sqlx::query_as!(
+            SharedLink,
+            r#"
+            SELECT id, file_id, recipient_user_id, password, expiration_date, created_at
+            FROM shared_links
+            WHERE id = $1
+            AND recipient_user_id = $2
+            AND expiration_date > NOW()
+            "#,
+            shared_id,
+            user_id,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(shared_link)
+    }
+
+    async fn update_shared_password(
+        &self,
+        shared_id: Uuid,
+        password: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE shared_links
+            SET password = $1
+            WHERE id = $2
+            "#,
+            password,
+            shared_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_legacy_shared_links(&self) -> Result<Vec<SharedLink>, sqlx::Error> {
+        let shared_links = sqlx::query_as!(
+            SharedLink,
+            r#"
+            SELECT id, file_id, recipient_user_id, password, expiration_date, created_at
+            FROM shared_links
+            WHERE password NOT LIKE '$argon2%'
+            ORDER BY created_at
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(shared_links)
+    }
+
+    async fn get_file(
+        &self,
+        file_id: Uuid,
+    ) -> Result<Option<File>, sqlx::Error> {
+        let file = sqlx::query_as!(
+            File,
+            r#"
+            SELECT id, user_id, file_name, file_size, encrypted_file, iv, cipher_suite, format_version, segment_size, segment_count, created_at
+            FROM files
+            WHERE id = $1
+            "#,
+            file_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(file)
+    }
+
+    async fn get_file_segment(
+        &self,
+        file_id: Uuid,
+        seq: i32,
+    ) -> Result<Option<Vec<u8>>, sqlx::Error> {
+        let data = sqlx::query_scalar!(
+            r#"
+            SELECT data
+            FROM file_segments
+            WHERE file_id = $1 AND seq = $2
+            "#,
+            file_id,
+            seq
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(data)
+    }
+
+    async fn get_sent_files(
+        &self,
+        user_id: Uuid,
+        page: usize,
+        limit: usize
+    ) -> Result<(Vec<SendFileDetails>, i64), sqlx::Error> {
+        let offset = page_offset(page, limit)?;
+
+        let files = sqlx::query_as!(
+            SendFileDetails,
+            r#"
+                SELECT
+                    f.id AS file_id,
+                    f.file_name,
+                    u.email AS recipient_email,
+                    f.format_version < 2 AS "encryption_deprecated!",
+                    EXISTS (
+                        SELECT 1 FROM file_keys fk
+                        JOIN user_keys uk ON uk.id = fk.user_key_id
+                        WHERE fk.file_id = f.id AND fk.user_id = f.user_id
+                        AND uk.revoked_at IS NULL
+                    ) AS "sender_copy!",
+                    sl.expiration_date,
+                    sl.created_at
+                FROM 
+                    shared_links sl
+                JOIN 
+                    files f ON sl.file_id = f.id
+                JOIN 
+                    users u ON sl.recipient_user_id = u.id
+                WHERE 
+                    f.user_id = $1
+                ORDER BY 
+                    sl.created_at DESC 
+                LIMIT $2 
+                OFFSET $3
+            "#,
+            user_id,
+            limit as i64,
+            offset,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let count_row = sqlx::query_scalar!(
+            r#"
+                SELECT COUNT(*)
+                FROM shared_links sl
+                JOIN files f ON sl.file_id = f.id
+                WHERE f.user_id = $1
+            "#,
+            user_id,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let total_count = count_row.unwrap_or(0);
+
+        Ok((files, total_count))
+    }
+
+    async fn get_receive_files(
+        &self,
+        user_id: Uuid,
+        page: usize,
+        limit: usize
+    ) -> Result<(Vec<ReceiveFileDetails>, i64), sqlx::Error> {
+        let offset = page_offset(page, limit)?;
+
+        let files = sqlx::query_as!(
+            ReceiveFileDetails,
+            r#"
+                SELECT
+                    sl.id AS file_id,
+                    f.file_name,
+                    u.email AS sender_email,
+                    f.format_version < 2 AS "encryption_deprecated!",
+                    sl.expiration_date,
+                    sl.created_at
+                FROM 
+                    shared_links sl
+                JOIN 
+                    files f ON sl.file_id = f.id
+                JOIN 
+                    users u ON f.user_id = u.id
+                WHERE 
+                    sl.recipient_user_id = $1
+                ORDER BY 
+                    sl.created_at DESC 
+                LIMIT $2 
+                OFFSET $3
+            "#,
+            user_id,
+            limit as i64,
+            offset,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let count_row = sqlx::query_scalar!(
+            r#"
+                SELECT COUNT(*)
+                FROM shared_links sl
+                JOIN files f ON sl.file_id = f.id
+                WHERE sl.recipient_user_id = $1
+            "#,
+            user_id,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let total_count = count_row.unwrap_or(0);
+
+        Ok((files, total_count))
+    }
+
+    async fn delete_expired_files(
+        &self
+    ) -> Result<(), sqlx::Error> {
+
+        // Delete uploads that were interrupted before completion
+        sqlx::query!(
+            r#"
+            DELETE FROM files
+            WHERE format_version = $1
+            AND segment_count IS NULL
+            AND created_at < NOW() - INTERVAL '1 day'
+            "#,
+            STREAM_FORMAT_VERSION
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let expired_shared_links: Vec<Uuid> = sqlx::query_scalar!(
+            r#"
+            SELECT sl.id
+            FROM shared_links sl
+            WHERE sl.expiration_date < NOW()
+            "#,
+        ).
+        fetch_all(&self.pool)
+        .await?;
+
+        if expired_shared_links.is_empty() {
+            println!("No expired files or shared links to delete.");
+            return Ok(());
+        }
+
+        let expired_file_ids: Vec<Uuid> = sqlx::query_scalar!(
+            r#"
+            SELECT f.id
+            FROM files f
+            WHERE f.id IN (
+                SELECT sl.file_id
+                FROM shared_links sl
+                WHERE sl.expiration_date < NOW()
+            )
+            AND NOT EXISTS (
+                SELECT 1
+                FROM shared_links sl
+                WHERE sl.file_id = f.id
+                AND sl.expiration_date >= NOW()
+            )
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM shared_links
+            WHERE id = ANY($1)
+            "#,
+            &expired_shared_links[..] // Pass the list of expired shared link IDs
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Delete the expired files
+        sqlx::query!(
+            r#"
+            DELETE FROM files
+            WHERE id = ANY($1)
+            "#,
+            &expired_file_ids[..] // Pass the list of expired file IDs
+        )
+        .execute(&self.pool)
+        .await?;
+
+        println!("Successfully deleted expired files and their shared links.");
+
+        Ok(())
+
+    }
+
+    async fn create_session(
+        &self,
+        user_id: Uuid,
+        family_id: Uuid,
+        refresh_token_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Session, sqlx::Error> {
+        let session = sqlx::query_as!(
+            Session,
+            r#"
+            INSERT INTO sessions (user_id, family_id, refresh_token_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, user_id, family_id, refresh_token_hash, expires_at, used_at, revoked_at, created_at
+            "#,
+            user_id,
+            family_id,
+            refresh_token_hash,
+            expires_at
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(session)
+    }
+
+    async fn get_session_by_token_hash(
+        &self,
+        refresh_token_hash: &str,
+    ) -> Result<Option<Session>, sqlx::Error> {
+        let session = sqlx::query_as!(
+            Session,
+            r#"
+            SELECT id, user_id, family_id, refresh_token_hash, expires_at, used_at, revoked_at, created_at
+            FROM sessions
+            WHERE refresh_token_hash = $1
+            "#,
+            refresh_token_hash
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(session)
+    }
+
+    async fn mark_session_used(&self, session_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET used_at = NOW()
+            WHERE id = $1
+            AND used_at IS NULL
+            "#,
+            session_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn revoke_session_family(&self, family_id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET revoked_at = NOW()
+            WHERE family_id = $1
+            AND revoked_at IS NULL
+            "#,
+            family_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn is_session_active(&self, family_id: Uuid) -> Result<bool, sqlx::Error> {
+        let active = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM sessions
+                WHERE family_id = $1
+                AND revoked_at IS NULL
+                AND expires_at > NOW()
+            )
+            "#,
+            family_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(active.unwrap_or(false))
+    }
+
+    async fn create_personal_access_token(
+        &self,
+        user_id: Uuid,
+        name: String,
+        token_hash: String,
+        token_prefix: String,
+        scopes: Vec<String>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<PersonalAccessToken, sqlx::Error> {
+        let token = sqlx::query_as!(
+            PersonalAccessToken,
+            r#"
+            INSERT INTO personal_access_tokens (user_id, name, token_hash, token_prefix, scopes, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, user_id, name, token_hash, token_prefix, scopes, expires_at, last_used_at, revoked_at, created_at
+            "#,
+            user_id,
+            name,
+            token_hash,
+            token_prefix,
+            &scopes[..],
+            expires_at
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    async fn get_personal_access_tokens(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<PersonalAccessToken>, sqlx::Error> {
+        let tokens = sqlx::query_as!(
+            PersonalAccessToken,
+            r#"
+            SELECT id, user_id, name, token_hash, token_prefix, scopes, expires_at, last_used_at, revoked_at, created_at
+            FROM personal_access_tokens
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(tokens)
+    }
+
+    async fn revoke_personal_access_token(
+        &self,
+        user_id: Uuid,
+        token_id: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE personal_access_tokens
+            SET revoked_at = COALESCE(revoked_at, NOW())
+            WHERE id = $1
+            AND user_id = $2
+            "#,
+            token_id,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn use_personal_access_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<PersonalAccessToken>, sqlx::Error> {
+        let token = sqlx::query_as!(
+            PersonalAccessToken,
+            r#"
+            UPDATE personal_access_tokens
+            SET last_used_at = NOW()
+            WHERE token_hash = $1
+            AND revoked_at IS NULL
+            AND expires_at > NOW()
+            RETURNING id, user_id, name, token_hash, token_prefix, scopes, expires_at, last_used_at, revoked_at, created_at
+            "#,
+            token_hash
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    async fn get_user_totp(&self, user_id: Uuid) -> Result<Option<UserTotp>, sqlx::Error> {
+        let totp = sqlx::query_as!(
+            UserTotp,
+            r#"
+            SELECT user_id, secret, enabled_at, last_used_step, created_at
+            FROM user_totp
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(totp)
+    }
+
+    async fn save_user_totp_secret(&self, user_id: Uuid, secret: String) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_totp (user_id, secret)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE
+            SET secret = EXCLUDED.secret, last_used_step = NULL, created_at = NOW()
+            WHERE user_totp.enabled_at IS NULL
+            "#,
+            user_id,
+            secret
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn enable_user_totp(
+        &self,
+        user_id: Uuid,
+        step: i64,
+        recovery_code_hashes: Vec<String>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE user_totp
+            SET enabled_at = NOW(), last_used_step = $2
+            WHERE user_id = $1
+            "#,
+            user_id,
+            step
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // 旧的恢复码全部作废
+        sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO recovery_codes (user_id, code_hash)
+            SELECT $1, code_hash FROM UNNEST($2::VARCHAR[]) AS code_hash
+            "#,
+            user_id,
+            &recovery_code_hashes[..]
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn update_totp_last_used_step(&self, user_id: Uuid, step: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_totp
+            SET last_used_step = $2
+            WHERE user_id = $1
+            AND enabled_at IS NOT NULL
+            AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+            user_id,
+            step
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE recovery_codes
+            SET used_at = NOW()
+            WHERE user_id = $1
+            AND code_hash = $2
+            AND used_at IS NULL
+            "#,
+            user_id,
+            code_hash
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn disable_user_totp(&self, user_id: Uuid) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM user_totp
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn create_email_token(
+        &self,
+        user_id: Uuid,
+        purpose: EmailTokenPurpose,
+        token_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // 只保留最新发送的链接
+        sqlx::query!(
+            r#"
+            UPDATE email_tokens
+            SET used_at = NOW()
+            WHERE user_id = $1
+            AND purpose = $2
+            AND used_at IS NULL
+            "#,
+            user_id,
+            purpose.as_str()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO email_tokens (user_id, purpose, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            user_id,
+            purpose.as_str(),
+            token_hash,
+            expires_at
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn peek_email_token(
+        &self,
+        token_hash: &str,
+        purpose: EmailTokenPurpose,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        let user_id = sqlx::query_scalar!(
+            r#"
+            SELECT user_id
+            FROM email_tokens
+            WHERE token_hash = $1
+            AND purpose = $2
+            AND used_at IS NULL
+            AND expires_at > NOW()
+            "#,
+            token_hash,
+            purpose.as_str()
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user_id)
+    }
+
+    async fn consume_email_token(
+        &self,
+        token_hash: &str,
+        purpose: EmailTokenPurpose,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        let user_id = sqlx::query_scalar!(
+            r#"
+            UPDATE email_tokens
+            SET used_at = NOW()
+            WHERE token_hash = $1
+            AND purpose = $2
+            AND used_at IS NULL
+            AND expires_at > NOW()
+            RETURNING user_id
+            "#,
+            token_hash,
+            purpose.as_str()
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user_id)
+    }
+
+    async fn verify_user_email(&self, user_id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET email_verified_at = COALESCE(email_verified_at, NOW())
+            WHERE id = $1
+            "#,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn claim_unverified_user(
+        &self,
+        user_id: Uuid,
+        password: String,
+        has_local_password: bool,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // Only an unverified account can be claimed; the row lock also serializes concurrent claims
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET password = $2, has_local_password = $3, token_version = token_version + 1,
+                email_verified_at = NOW(), updated_at = Now()
+            WHERE id = $1
+            AND email_verified_at IS NULL
+            RETURNING id, name, email, password, token_version, email_verified_at, created_at, updated_at
+            "#,
+            user_id,
+            password,
+            has_local_password
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(user) = user else {
+            return Ok(None);
+        };
+
+        sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET revoked_at = NOW()
+            WHERE user_id = $1
+            AND revoked_at IS NULL
             "#,
-            new_password,
             user_id
         )
-        .fetch_one(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
-        Ok(user)
-    }
-
-    async fn save_user_key(&self, user_id: Uuid, public_key: String) -> Result<(), sqlx::Error> {
-        sqlx::query_as!(
-            User,
+        sqlx::query!(
             r#"
-            UPDATE users
-            SET public_key = $1, updated_at = Now()
-            WHERE id = $2
-            RETURNING id, name, email, password, public_key, created_at, updated_at
+            UPDATE personal_access_tokens
+            SET revoked_at = NOW()
+            WHERE user_id = $1
+            AND revoked_at IS NULL
             "#,
-            public_key,
             user_id
         )
-        .fetch_one(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
-        Ok(())
-    }
-    async fn search_by_email(
-        &self,
-        user_id: Uuid,
-        query: String,
-    ) -> Result<Vec<User>, sqlx::Error> {
-        let user = sqlx::query_as!(
-            User,
+        // Keys registered before the claim must not receive file keys shared with the real owner
+        sqlx::query!(
             r#"
-            SELECT id, name, email, password, public_key, created_at, updated_at
-            FROM users
-            WHERE email LIKE $1
-            AND public_key IS NOT NULL
-            AND id != $2
+            UPDATE user_keys
+            SET revoked_at = NOW()
+            WHERE user_id = $1
+            AND revoked_at IS NULL
             "#,
-            query,
             user_id
         )
-        .fetch_all(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
-        Ok(user)
-    }
-    async fn save_encrypted_file(
-        &self,
-        user_id: Uuid,
-        file_name: String,
-        file_size: i64,
-        recipient_user_ud: Uuid,
-        password: String,
-        expiration_date: DateTime<Utc>,
-        encrypted_aes_key: Vec<u8>,
-        encrypted_file: Vec<u8>,
-        iv: Vec<u8>,
-    ) -> Result<(), sqlx::Error> {
-        // Insert into the files table and get the file_id
-        let file_id: Uuid = sqlx::query_scalar!(
+        sqlx::query!(
             r#"
-            INSERT INTO files (user_id, file_name, file_size, encrypted_aes_key, encrypted_file, iv, created_at)
-            VALUES ($1, $2, $3, $4, $5, $6, NOW())
-            RETURNING id
+            DELETE FROM recovery_codes
+            WHERE user_id = $1
             "#,
-            user_id,
-            file_name,
-            file_size,
-            encrypted_aes_key,
-            encrypted_file,
-            iv
+            user_id
         )
-        .fetch_one(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
-        // Insert into the shared_links table using the returned file_id
         sqlx::query!(
             r#"
-            INSERT INTO shared_links (file_id, recipient_user_id, password, expiration_date, created_at)
-            VALUES ($1, $2, $3, $4, NOW())
+            DELETE FROM user_totp
+            WHERE user_id = $1
             "#,
-            file_id,
-            recipient_user_ud,
-            password,
-            expiration_date
+            user_id
         )
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
-        Ok(())
+        tx.commit().await?;
+
+        Ok(Some(user))
     }
 
-    async fn get_shared(
+    async fn get_login_throttle(
         &self,
-        shared_id: Uuid,
-        user_id: Uuid,
-    ) -> Result<Option<SharedLink>, sqlx::Error> {
-        let shared_link = sqlx::query_as!(
-            SharedLink,
+        scope: &str,
+        throttle_key: &str,
+    ) -> Result<Option<LoginThrottle>, sqlx::Error> {
+        let throttle = sqlx::query_as!(
+            LoginThrottle,
             r#"
-            SELECT id, file_id, recipient_user_id, password, expiration_date, created_at
-            FROM shared_links
-            WHERE id = $1
-            AND recipient_user_id = $2
-            AND expiration_date > NOW()
+            SELECT scope, throttle_key, failure_count, last_failure_at, locked_until
+            FROM login_throttles
+            WHERE scope = $1
+            AND throttle_key = $2
             "#,
-            shared_id,
-            user_id,
+            scope,
+            throttle_key
         )
         .fetch_optional(&self.pool)
         .await?;
 
-        Ok(shared_link)
+        Ok(throttle)
     }
 
-    async fn get_file(
+    async fn record_login_failure(
         &self,
-        file_id: Uuid,
-    ) -> Result<Option<File>, sqlx::Error> {
-        let file = sqlx::query_as!(
-            File,
+        scope: &str,
+        throttle_key: &str,
+        window_minutes: i32,
+    ) -> Result<i32, sqlx::Error> {
+        let failure_count = sqlx::query_scalar!(
             r#"
-            SELECT id, user_id, file_name, file_size, encrypted_aes_key, encrypted_file, iv, created_at
-            FROM files
-            WHERE id = $1
+            INSERT INTO login_throttles (scope, throttle_key, failure_count, last_failure_at)
+            VALUES ($1, $2, 1, NOW())
+            ON CONFLICT (scope, throttle_key) DO UPDATE
+            SET failure_count = CASE
+                    WHEN login_throttles.last_failure_at < NOW() - make_interval(mins => $3) THEN 1
+                    ELSE login_throttles.failure_count + 1
+                END,
+                last_failure_at = NOW()
+            RETURNING failure_count
             "#,
-            file_id
+            scope,
+            throttle_key,
+            window_minutes
         )
-        .fetch_optional(&self.pool)
+        .fetch_one(&self.pool)
         .await?;
 
-        Ok(file)
+        Ok(failure_count)
     }
-    async fn get_sent_files(
-        &self,
-        user_id: Uuid,
-        page: u32,
-        limit: usize
-    ) -> Result<(Vec<SendFileDetails>, i64), sqlx::Error> {
-        let offset = (page - 1) * limit as u32;
 
-        let files = sqlx::query_as!(
-            SendFileDetails,
+    async fn lock_login(
+        &self,
+        scope: &str,
+        throttle_key: &str,
+        locked_until: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
             r#"
-                SELECT
-                    f.id AS file_id,
-                    f.file_name,
-                    u.email AS recipient_email,
-                    sl.expiration_date,
-                    sl.created_at
-                FROM 
-                    shared_links sl
-                JOIN 
-                    files f ON sl.file_id = f.id
-                JOIN 
-                    users u ON sl.recipient_user_id = u.id
-                WHERE 
-                    f.user_id = $1
-                ORDER BY 
-                    sl.created_at DESC 
-                LIMIT $2 
-                OFFSET $3
+            UPDATE login_throttles
+            SET locked_until = $3
+            WHERE scope = $1
+            AND throttle_key = $2
             "#,
-            user_id,
-            limit as i64,
-            offset as i64,
+            scope,
+            throttle_key,
+            locked_until
         )
-        .fetch_all(&self.pool)
+        .execute(&self.pool)
         .await?;
 
-        let count_row = sqlx::query_scalar!(
+        Ok(())
+    }
+
+    async fn clear_login_throttle(
+        &self,
+        scope: Option<&str>,
+        throttle_key: &str,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
             r#"
-                SELECT COUNT(*)
-                FROM shared_links sl
-                JOIN files f ON sl.file_id = f.id
-                WHERE f.user_id = $1
+            DELETE FROM login_throttles
+            WHERE throttle_key = $2
+            AND ($1::VARCHAR IS NULL OR scope = $1)
             "#,
-            user_id,
+            scope,
+            throttle_key
         )
-        .fetch_one(&self.pool)
+        .execute(&self.pool)
         .await?;
 
-        let total_count = count_row.unwrap_or(0);
+        Ok(result.rows_affected())
+    }
 
-        Ok((files, total_count))
+    async fn get_locked_login_throttles(&self) -> Result<Vec<LoginThrottle>, sqlx::Error> {
+        let throttles = sqlx::query_as!(
+            LoginThrottle,
+            r#"
+            SELECT scope, throttle_key, failure_count, last_failure_at, locked_until
+            FROM login_throttles
+            WHERE locked_until > NOW()
+            ORDER BY locked_until
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(throttles)
     }
 
-    async fn get_receive_files(
+    async fn create_oidc_auth_request(
         &self,
-        user_id: Uuid,
-        page: u32,
-        limit: usize
-    ) -> Result<(Vec<ReceiveFileDetails>, i64), sqlx::Error> {
-        let offset = (page - 1) * limit as u32;
-
-        let files = sqlx::query_as!(
-            ReceiveFileDetails,
+        state_hash: String,
+        nonce: String,
+        pkce_verifier: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
             r#"
-                SELECT
-                    sl.id AS file_id,
-                    f.file_name,
-                    u.email AS sender_email,
-                    sl.expiration_date,
-                    sl.created_at
-                FROM 
-                    shared_links sl
-                JOIN 
-                    files f ON sl.file_id = f.id
-                JOIN 
-                    users u ON f.user_id = u.id
-                WHERE 
-                    sl.recipient_user_id = $1
-                ORDER BY 
-                    sl.created_at DESC 
-                LIMIT $2 
-                OFFSET $3
-            "#,
-            user_id,
-            limit as i64,
-            offset as i64,
+            DELETE FROM oidc_auth_requests
+            WHERE expires_at <= NOW()
+            "#
         )
-        .fetch_all(&self.pool)
+        .execute(&self.pool)
         .await?;
 
-        let count_row = sqlx::query_scalar!(
+        sqlx::query!(
             r#"
-                SELECT COUNT(*)
-                FROM shared_links sl
-                JOIN files f ON sl.file_id = f.id
-                WHERE sl.recipient_user_id = $1
+            INSERT INTO oidc_auth_requests (state_hash, nonce, pkce_verifier, expires_at)
+            VALUES ($1, $2, $3, $4)
             "#,
-            user_id,
+            state_hash,
+            nonce,
+            pkce_verifier,
+            expires_at
         )
-        .fetch_one(&self.pool)
+        .execute(&self.pool)
         .await?;
 
-        let total_count = count_row.unwrap_or(0);
-
-        Ok((files, total_count))
+        Ok(())
     }
 
-    async fn delete_expired_files(
-        &self
-    ) -> Result<(), sqlx::Error> {
-        
-        let expired_shared_links: Vec<Uuid> = sqlx::query_scalar!(
+    async fn consume_oidc_auth_request(
+        &self,
+        state_hash: &str,
+    ) -> Result<Option<OidcAuthRequest>, sqlx::Error> {
+        let request = sqlx::query_as!(
+            OidcAuthRequest,
             r#"
-            SELECT sl.id
-            FROM shared_links sl
-            WHERE sl.expiration_date < NOW()
+            DELETE FROM oidc_auth_requests
+            WHERE state_hash = $1
+            RETURNING state_hash, nonce, pkce_verifier, expires_at, created_at
             "#,
-        ).
-        fetch_all(&self.pool)
+            state_hash
+        )
+        .fetch_optional(&self.pool)
         .await?;
 
-        if expired_shared_links.is_empty() {
-            println!("No expired files or shared links to delete.");
-            return Ok(());
-        }
+        Ok(request.filter(|request| request.expires_at > Utc::now()))
+    }
 
-        let expired_file_ids: Vec<Uuid> = sqlx::query_scalar!(
+    async fn get_user_by_identity(
+        &self,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
             r#"
-            SELECT f.id
-            FROM files f
-            WHERE f.id IN (
-                SELECT sl.file_id
-                FROM shared_links sl
-                WHERE sl.expiration_date < NOW()
-            )
+            SELECT u.id, u.name, u.email, u.password, u.token_version, u.email_verified_at, u.created_at, u.updated_at
+            FROM users u
+            INNER JOIN user_identities i ON i.user_id = u.id
+            WHERE i.issuer = $1
+            AND i.subject = $2
             "#,
+            issuer,
+            subject
         )
-        .fetch_all(&self.pool)
+        .fetch_optional(&self.pool)
         .await?;
 
+        Ok(user)
+    }
+
+    async fn link_user_identity(
+        &self,
+        user_id: Uuid,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<(), sqlx::Error> {
         sqlx::query!(
             r#"
-            DELETE FROM shared_links
-            WHERE id = ANY($1)
+            INSERT INTO user_identities (user_id, issuer, subject)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (issuer, subject) DO NOTHING
             "#,
-            &expired_shared_links[..] // Pass the list of expired shared link IDs
+            user_id,
+            issuer,
+            subject
         )
         .execute(&self.pool)
         .await?;
 
-        // Delete the expired files
-        sqlx::query!(
+        Ok(())
+    }
+
+    async fn has_user_identity(&self, user_id: Uuid, issuer: &str) -> Result<bool, sqlx::Error> {
+        let exists = sqlx::query_scalar!(
             r#"
-            DELETE FROM files
-            WHERE id = ANY($1)
+            SELECT EXISTS (
+                SELECT 1 FROM user_identities WHERE user_id = $1 AND issuer = $2
+            ) AS "exists!"
             "#,
-            &expired_file_ids[..] // Pass the list of expired file IDs
+            user_id,
+            issuer
         )
-        .execute(&self.pool)
+        .fetch_one(&self.pool)
         .await?;
 
-        println!("Successfully deleted expired files and their shared links.");
+        Ok(exists)
+    }
 
-        Ok(())
+    async fn update_user_profile(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        email: &str,
+    ) -> Result<User, sqlx::Error> {
+        // SET 右侧引用的是更新前的值，邮箱变化时刷新验证时间
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET name = $1,
+                email = $2::VARCHAR,
+                email_verified_at = CASE WHEN email = $2::VARCHAR THEN email_verified_at ELSE NOW() END,
+                updated_at = Now()
+            WHERE id = $3
+            RETURNING id, name, email, password, token_version, email_verified_at, created_at, updated_at
+            "#,
+            name,
+            email,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_like_escapes_wildcards() {
+        assert_eq!(escape_like("100%"), "100\\%");
+        assert_eq!(escape_like("a_b"), "a\\_b");
+        assert_eq!(escape_like("%_%"), "\\%\\_\\%");
+    }
+
+    #[test]
+    fn escape_like_escapes_backslash() {
+        assert_eq!(escape_like("a\\b"), "a\\\\b");
+        assert_eq!(escape_like("\\%"), "\\\\\\%");
+    }
+
+    #[test]
+    fn escape_like_keeps_plain_text() {
+        assert_eq!(escape_like("alice@example.com"), "alice@example.com");
+        assert_eq!(escape_like(""), "");
+    }
+
+    #[test]
+    fn page_offset_starts_at_zero() {
+        assert_eq!(page_offset(1, 10).unwrap(), 0);
+        assert_eq!(page_offset(3, 10).unwrap(), 20);
+    }
 
+    #[test]
+    fn page_offset_rejects_overflow() {
+        assert!(page_offset(usize::MAX, 50).is_err());
     }
-}
\ No newline at end of file
+}
@@ -8,7 +8,7 @@ use serde::{Deserialize, Serialize};
 use validator::{Validate, ValidationError};
 
 // 导入其他模块中的数据结构
-use crate::models::{ReceiveFileDetails, SendFileDetails, User};
+use crate::models::{PersonalAccessToken, ReceiveFileDetails, SendFileDetails, User, UserKey};
 
 // 注册用户数据传输对象（DTO）结构体
 #[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]  // 派生了验证、调试、默认值、克隆、序列化和反序列化等功能
@@ -58,12 +58,12 @@ pub struct LoginUserDto {
 // 请求查询参数数据传输对象（DTO）结构体，用于分页等查询
 #[derive(Serialize, Deserialize, Validate)]  // 派生序列化、反序列化和验证功能
 pub struct RequestQueryDto {
-    // 页码，必须大于等于 1
-    #[validate(range(min = 1))]
+    // 页码，必须在 1 和 10000 之间
+    #[validate(range(min = 1, max = 10000, message = "Page must be between 1 and 10000"))]
     pub page: Option<usize>,  // 页码字段（可选）
 
     // 每页条目数，必须在 1 和 50 之间
-    #[validate(range(min = 1, max = 50))]
+    #[validate(range(min = 1, max = 50, message = "Limit must be between 1 and 50"))]
     pub limit: Option<usize>,  // 每页条目数字段（可选）
 }
 
@@ -73,11 +73,30 @@ pub struct FilterUserDto {
     pub id: String,                // 用户的 ID（字符串类型）
     pub name: String,              // 用户名
     pub email: String,             // 用户邮箱
-    pub public_key: Option<String>, // 用户的公钥，可能为空
+    pub email_verified: bool,      // 邮箱是否已验证
     pub created_at: DateTime<Utc>, // 用户创建时间
     pub updated_at: DateTime<Utc>, // 用户更新时间
 }
 
+impl FilterUserDto {
+    // 过滤单个用户的信息，去掉密码等敏感字段
+    pub fn filter_user(user: &User) -> Self {
+        FilterUserDto {
+            id: user.id.to_string(),
+            name: user.name.to_owned(),
+            email: user.email.to_owned(),
+            email_verified: user.email_verified_at.is_some(),
+            created_at: user.created_at.unwrap(),
+            updated_at: user.updated_at.unwrap(),
+        }
+    }
+
+    // 过滤多个用户的信息
+    pub fn filter_users(user: &[User]) -> Vec<FilterUserDto> {
+        user.iter().map(FilterUserDto::filter_user).collect()
+    }
+}
+
 // 用于描述用户数据的结构体
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserData {
@@ -97,6 +116,8 @@ pub struct UserSendFileDto {
     pub file_id: String, // 文件 ID
     pub file_name: String, // 文件名称
     pub recipient_email: String, // 接收者的邮箱
+    pub encryption_deprecated: bool, // 是否使用已弃用的加密格式（旧版 AES-256-CBC，没有完整性校验）
+    pub sender_copy: bool, // 发送者是否保留了文件密钥副本，可以通过 /files/download 下载和 /files/forward 转发
     pub expiration_date: DateTime<Utc>, // 文件过期时间
     pub created_at: DateTime<Utc>, // 文件创建时间
 }
@@ -108,6 +129,8 @@ impl UserSendFileDto {
             file_id: file_data.file_id.to_string(),
             file_name: file_data.file_name.to_owned(),
             recipient_email: file_data.recipient_email.to_owned(),
+            encryption_deprecated: file_data.encryption_deprecated,
+            sender_copy: file_data.sender_copy,
             expiration_date: file_data.expiration_date.unwrap(),
             created_at: file_data.created_at.unwrap(),
         }
@@ -125,6 +148,9 @@ pub struct UserSendFileListResponseDto {
     pub status: String, // 响应状态
     pub files: Vec<UserSendFileDto>, // 文件列表
     pub results: i64, // 返回结果的总数
+    pub page: usize, // 当前页码
+    pub limit: usize, // 每页条目数
+    pub total_pages: i64, // 总页数
 }
 
 // 用户接收文件的 DTO
@@ -133,6 +159,7 @@ pub struct UserReceiveFileDto {
     pub file_id: String, // 文件 ID
     pub file_name: String, // 文件名称
     pub sender_email: String, // 发送者邮箱
+    pub encryption_deprecated: bool, // 是否使用已弃用的加密格式（旧版 AES-256-CBC，没有完整性校验）
     pub expiration_date: DateTime<Utc>, // 文件过期时间
     pub created_at: DateTime<Utc>, // 文件创建时间
 }
@@ -144,6 +171,7 @@ impl UserReceiveFileDto {
             file_id: file_data.file_id.to_string(),
             file_name: file_data.file_name.to_owned(),
             sender_email: file_data.sender_email.to_owned(),
+            encryption_deprecated: file_data.encryption_deprecated,
             expiration_date: file_data.expiration_date.unwrap(),
             created_at: file_data.created_at.unwrap(),
         }
@@ -161,13 +189,69 @@ pub struct UserReceiveFileListResponseDto {
     pub status: String, // 响应状态
     pub files: Vec<UserReceiveFileDto>, // 文件列表
     pub results: i64, // 返回结果的总数
+    pub page: usize, // 当前页码
+    pub limit: usize, // 每页条目数
+    pub total_pages: i64, // 总页数
 }
 
 // 用户登录响应的 DTO
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserLoginResponseDto {
     pub status: String, // 登录状态
-    pub token: String,  // 用户的认证 token
+    pub token: String,  // 用户的认证 token（短期有效的访问令牌）
+    pub refresh_token: String, // 刷新令牌，用于换取新的访问令牌
+}
+
+// 开启两步验证的用户登录时，密码验证通过后返回的响应 DTO
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MfaRequiredResponseDto {
+    pub status: String, // 登录状态，固定为 mfa_required
+    pub mfa_token: String, // 短期有效的两步验证令牌，用于提交验证码
+}
+
+// 登录第二步提交两步验证码的 DTO
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct VerifyMfaDto {
+    #[validate(length(min = 1, message = "MFA token is required"))] // 两步验证令牌不能为空
+    pub mfa_token: String, // 登录第一步返回的两步验证令牌
+
+    pub code: Option<String>, // 验证器中的 6 位验证码或一次性恢复码
+}
+
+// 确认启用两步验证的 DTO
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct EnableMfaDto {
+    pub code: Option<String>, // 验证器中的 6 位验证码
+}
+
+// 关闭两步验证的 DTO，需要重新确认密码
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct DisableMfaDto {
+    #[validate(length(min = 1, message = "Password is required"))] // 密码不能为空
+    pub password: Option<String>, // 当前密码，有本地密码或密码由外部目录管理的账户必须提供
+    pub code: Option<String>, // 验证器中的当前验证码或恢复码，仅供没有可用密码的账户（如 OIDC、魔法链接创建的账户）代替密码
+}
+
+// 生成两步验证密钥的响应 DTO
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TotpSetupResponseDto {
+    pub status: String, // 响应状态
+    pub secret: String, // Base32 编码的密钥，供无法扫码时手动输入
+    pub otpauth_uri: String, // otpauth URI，可生成二维码供验证器扫描
+}
+
+// 恢复码的响应 DTO，恢复码只在此返回一次
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RecoveryCodesResponseDto {
+    pub status: String, // 响应状态
+    pub recovery_codes: Vec<String>, // 一次性恢复码
+}
+
+// 刷新令牌请求的 DTO
+// 浏览器客户端通过 Cookie 传递刷新令牌，其他客户端可以在请求体中传递
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RefreshTokenDto {
+    pub refresh_token: Option<String>, // 刷新令牌
 }
 
 // 通用响应 DTO
@@ -207,11 +291,226 @@ pub struct UserPasswordUpdateDto {
     pub old_password: String, // 旧密码
 }
 
+// 提交邮件中令牌（邮箱验证链接）的 DTO
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct EmailTokenDto {
+    #[validate(length(min = 1, message = "Token is required"))] // 令牌不能为空
+    pub token: String, // 邮件链接中的令牌
+}
+
+// 只包含邮箱的 DTO，用于忘记密码和重新发送验证邮件
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct EmailDto {
+    #[validate(
+        length(min = 1, message = "Email is required"),
+        email(message = "Email is invalid")
+    )]
+    pub email: String, // 用户邮箱
+}
+
+// 通过邮件令牌重置密码的 DTO
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ResetPasswordDto {
+    #[validate(length(min = 1, message = "Token is required"))] // 令牌不能为空
+    pub token: String, // 重置密码邮件中的令牌
+
+    #[validate(
+        length(min = 1, message = "New password is required."), // 校验新密码不能为空
+        length(min = 6, message = "new password must be at least 6 characters") // 新密码至少 6 位
+    )]
+    pub new_password: String, // 新密码
+
+    #[validate(
+        length(min = 1, message = "New password confirm is required."), // 校验确认新密码不能为空
+        must_match(other = "new_password", message="new passwords do not match") // 确认密码和新密码必须匹配
+    )]
+    pub new_password_confirm: String, // 确认新密码
+}
+
+// 身份提供方重定向回调时携带的查询参数
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct OidcCallbackQueryDto {
+    pub code: Option<String>,              // 授权码
+    pub state: Option<String>,             // 登录时生成的 state 参数
+    pub error: Option<String>,             // 身份提供方返回的错误码
+    pub error_description: Option<String>, // 错误描述
+}
+
+// 上传用户设备公钥的 DTO
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PublicKeyDto {
+    #[validate(length(min = 1, message = "Public key is required"))] // 校验公钥不能为空
+    pub public_key: String, // PEM 格式的 RSA 公钥（SPKI 或 PKCS#1）
+
+    #[validate(length(min = 1, max = 100, message = "Label must be between 1 and 100 characters"))] // 校验设备名称
+    pub label: Option<String>, // 设备名称，例如 "Laptop"，未提供时使用默认名称
+}
+
+// 用户设备公钥信息的 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserKeyDto {
+    pub id: String, // 公钥 ID
+    pub user_id: String, // 公钥所属用户 ID，客户端包装文件密钥时用于构造 OAEP 标签
+    pub label: String, // 设备名称
+    pub algorithm: String, // 包装文件密钥使用的算法
+    pub fingerprint: String, // 公钥的 SHA-256 指纹
+    pub public_key: String, // SPKI PEM 格式的公钥
+    pub revoked_at: Option<DateTime<Utc>>, // 吊销时间
+    pub replaced_by: Option<String>, // 轮换后替代该公钥的新公钥 ID
+    pub created_at: DateTime<Utc>, // 添加时间
+}
+
+impl UserKeyDto {
+    // 过滤单个公钥的信息
+    pub fn filter_key(user_key: &UserKey) -> Self {
+        UserKeyDto {
+            id: user_key.id.to_string(),
+            user_id: user_key.user_id.to_string(),
+            label: user_key.label.to_owned(),
+            algorithm: user_key.algorithm.to_owned(),
+            fingerprint: user_key.fingerprint.to_owned(),
+            public_key: user_key.public_key.to_owned(),
+            revoked_at: user_key.revoked_at,
+            replaced_by: user_key.replaced_by.map(|id| id.to_string()),
+            created_at: user_key.created_at.unwrap(),
+        }
+    }
+
+    // 过滤多个公钥的信息
+    pub fn filter_keys(user_keys: &[UserKey]) -> Vec<UserKeyDto> {
+        user_keys.iter().map(UserKeyDto::filter_key).collect()
+    }
+}
+
+// 上传公钥成功后的响应 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicKeyResponseDto {
+    pub status: String,      // 响应状态
+    pub fingerprint: String, // 公钥的 SHA-256 指纹，供用户线下核对
+    pub data: UserKeyDto,    // 新添加的设备公钥信息
+}
+
+// 设备公钥列表的响应 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserKeyListResponseDto {
+    pub status: String, // 响应状态
+    pub keys: Vec<UserKeyDto>, // 公钥列表
+}
+
+// 使用某把设备公钥包装的文件密钥
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct FileKeyDto {
+    pub file_id: String, // 文件 ID
+    pub encrypted_aes_key: String, // 包装后的文件密钥（Base64 编码）
+
+    // 响应中为原有包装使用的 OAEP 标签，旧版包装没有标签时省略；提交时忽略该字段
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_wrap_label: Option<String>,
+}
+
+// 开始轮换公钥的响应 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyRotationChallengeResponseDto {
+    pub status: String, // 响应状态
+    pub challenge: String, // 使用旧公钥加密的挑战（Base64 编码），用旧私钥解开后提交
+    pub expires_at: DateTime<Utc>, // 挑战的过期时间
+    pub file_keys: Vec<FileKeyDto>, // 使用旧公钥包装、仍需重新包装的文件密钥
+}
+
+// 完成公钥轮换的 DTO
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RotateUserKeyDto {
+    #[validate(length(min = 1, message = "Challenge is required"))] // 校验挑战不能为空
+    pub challenge: String, // 用旧私钥解开的挑战明文
+
+    #[validate(length(min = 1, message = "Public key is required"))] // 校验公钥不能为空
+    pub public_key: String, // PEM 格式的新 RSA 公钥（SPKI 或 PKCS#1）
+
+    #[validate(length(min = 1, max = 100, message = "Label must be between 1 and 100 characters"))] // 校验设备名称
+    pub label: Option<String>, // 新公钥的设备名称，未提供时沿用旧公钥的名称
+
+    // 使用新公钥重新包装的文件密钥，必须覆盖开始轮换时返回的全部文件
+    // 包装时使用 OAEP 标签 SecureShare/key/v2/<文件 ID>/<当前用户 ID>
+    pub file_keys: Vec<FileKeyDto>,
+}
+
+// 查询接收者设备公钥的参数，客户端自行包装文件密钥时使用
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RecipientKeysQueryDto {
+    #[validate(email(message = "Invalid email format"))] // 校验邮箱格式是否合法
+    pub email: String, // 接收者的邮箱
+}
+
+// 创建个人访问令牌的 DTO
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CreatePersonalAccessTokenDto {
+    #[validate(length(min = 1, max = 100, message = "Name must be between 1 and 100 characters"))] // 校验令牌名称
+    pub name: String, // 令牌名称
+
+    #[validate(length(min = 1, message = "At least one scope is required"))] // 至少需要一个权限范围
+    pub scopes: Vec<String>, // 权限范围，例如 files:upload、files:read、users:search
+
+    #[validate(range(min = 1, max = 365, message = "Expiration must be between 1 and 365 days"))] // 有效期 1 到 365 天
+    pub expires_in_days: i64, // 有效期（天）
+}
+
+// 个人访问令牌信息的 DTO，不包含令牌明文和摘要
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PersonalAccessTokenDto {
+    pub id: String, // 令牌 ID
+    pub name: String, // 令牌名称
+    pub token_prefix: String, // 令牌的展示前缀
+    pub scopes: Vec<String>, // 权限范围
+    pub expires_at: DateTime<Utc>, // 过期时间
+    pub last_used_at: Option<DateTime<Utc>>, // 最近一次使用时间
+    pub revoked_at: Option<DateTime<Utc>>, // 吊销时间
+    pub created_at: DateTime<Utc>, // 创建时间
+}
+
+impl PersonalAccessTokenDto {
+    // 过滤单个令牌的信息
+    pub fn filter_token(token: &PersonalAccessToken) -> Self {
+        PersonalAccessTokenDto {
+            id: token.id.to_string(),
+            name: token.name.to_owned(),
+            token_prefix: token.token_prefix.to_owned(),
+            scopes: token.scopes.to_owned(),
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at,
+            revoked_at: token.revoked_at,
+            created_at: token.created_at.unwrap(),
+        }
+    }
+
+    // 过滤多个令牌的信息
+    pub fn filter_tokens(tokens: &[PersonalAccessToken]) -> Vec<PersonalAccessTokenDto> {
+        tokens.iter().map(PersonalAccessTokenDto::filter_token).collect()
+    }
+}
+
+// 创建个人访问令牌的响应 DTO，令牌明文只在此返回一次
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PersonalAccessTokenCreatedResponseDto {
+    pub status: String, // 响应状态
+    pub token: String, // 令牌明文
+    pub data: PersonalAccessTokenDto, // 令牌信息
+}
+
+// 个人访问令牌列表的响应 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PersonalAccessTokenListResponseDto {
+    pub status: String, // 响应状态
+    pub tokens: Vec<PersonalAccessTokenDto>, // 令牌列表
+}
+
 // 通过电子邮件查询用户的 DTO
 #[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
 pub struct SearchQueryByEmailDTO {
-    #[validate(length(min = 1, message = "Query is required"))] // 校验查询条件不能为空
-    pub query: String, // 查询条件（电子邮件）
+    #[validate(
+        length(min = 1, message = "Query is required"), // 校验查询条件不能为空
+        length(min = 3, message = "Query must be at least 3 characters") // 至少输入 3 个字符，避免枚举全部用户
+    )]
+    pub query: String, // 查询条件（电子邮件前缀）
 }
 
 // 用于过滤用户邮箱的 DTO
@@ -296,4 +595,58 @@ pub struct RetrieveFileDto {
         length(min = 6, message = "Password must be at least 6 characters") // 密码至少 6 位
     )]
     pub password: String, // 密码
+
+    // 接收者的 RSA 私钥（PEM 格式，可选）
+    // 提供时服务端直接返回解密后的文件，否则返回密文以及加密后的 AES 密钥和 IV
+    pub private_key: Option<String>,
+
+    // 返回密文时使用哪把设备公钥包装的文件密钥（公钥指纹），接收者只有一把公钥时可省略
+    pub key_fingerprint: Option<String>,
+}
+
+// 发送者下载自己发送的文件的 DTO
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct DownloadSentFileDto {
+    #[validate(length(min = 1, message = "File id is required"))] // 校验文件 ID 必须存在
+    pub file_id: String, // 文件 ID，即已发送文件列表中的 file_id
+
+    // 发送者的 RSA 私钥（PEM 格式，可选）
+    // 提供时服务端直接返回解密后的文件，否则返回密文以及使用发送者公钥加密的 AES 密钥和 IV
+    pub private_key: Option<String>,
+
+    // 返回密文时使用哪把设备公钥包装的文件密钥（公钥指纹），发送者只有一把公钥时可省略
+    pub key_fingerprint: Option<String>,
+}
+
+// 转发文件的 DTO
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ForwardFileDto {
+    #[validate(length(min = 1, message = "File id is required"))] // 校验文件 ID 必须存在
+    pub file_id: String, // 文件 ID，即已发送文件列表中的 file_id
+
+    #[validate(email(message = "Invalid email format"))] // 校验邮箱格式是否合法
+    pub recipient_email: String, // 新接收者的邮箱
+
+    #[validate(
+        length(min = 1, message = "New password is required."), // 校验新密码不能为空
+        length(min = 6, message = "New password must be at least 6 characters") // 新密码至少 6 位
+    )]
+    pub password: String, // 新共享链接的密码
+
+    #[validate(custom = "validate_expiration_date")] // 自定义的过期日期验证
+    pub expiration_date: String, // 新共享链接的过期日期
+
+    // 发送者的 RSA 私钥（PEM 格式），由服务端解开发送者副本后为新接收者重新包装文件密钥
+    pub private_key: Option<String>,
+
+    // 客户端自行为新接收者每把有效设备公钥包装好的文件密钥，与 private_key 二选一
+    // 包装时使用 OAEP 标签 SecureShare/key/v2/<文件 ID>/<新接收者用户 ID>
+    pub encrypted_aes_keys: Option<Vec<WrappedFileKeyDto>>,
+}
+
+// 客户端为某把设备公钥包装的文件密钥
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct WrappedFileKeyDto {
+    pub key_id: String, // 设备公钥 ID，见查询接收者公钥接口
+    pub encrypted_aes_key: String, // 使用该公钥包装的文件密钥（Base64 编码）
 }
\ No newline at end of file
@@ -34,12 +34,37 @@ pub enum ErrorMessage {
     EmailExist, // 邮箱已存在
     UserNoLongerExist, // 用户已不存在
     TokenNotProvided, // 未提供令牌
+    InvalidPublicKey, // 公钥无法解析
+    PublicKeyTooSmall(usize), // 公钥长度不足
+    InvalidPrivateKey, // 私钥无法解析
+    RefreshTokenNotProvided, // 未提供刷新令牌
+    RefreshTokenReused, // 刷新令牌被重复使用
+    SessionRevoked, // 会话已被吊销
+    InsufficientScope, // 令牌权限不足
+    MfaCodeRequired, // 缺少两步验证码
+    InvalidMfaCode, // 两步验证码错误
+    MfaAlreadyEnabled, // 两步验证已启用
+    MfaNotEnabled, // 两步验证未启用
+    MfaNotSetUp, // 尚未生成两步验证密钥
+    MfaConfirmationRequired, // 关闭两步验证时缺少密码
+    IncorrectPassword, // 重新确认的密码错误
+    TooManyMfaAttempts, // 两步验证码尝试次数过多
+    InvalidEmailToken, // 邮件链接无效或已过期
+    RecipientNotVerified, // 接收者邮箱尚未验证
+    EmailNotVerified, // 当前用户邮箱尚未验证
+    LoginLocked(i64), // 登录失败次数过多，暂时锁定
+    OidcNotConfigured, // 未配置单点登录
+    InvalidOidcState, // 单点登录会话无效或已过期
+    OidcLoginFailed, // 单点登录失败
+    AuthProviderUnavailable, // 认证后端不可用
+    PasswordManagedByDirectory, // 密码由外部目录管理
+    MagicLinkDisabled, // 未启用免密码登录
 }
 
-// 为 ErrorMessage 实现 ToString trait，允许将 ErrorMessage 转换为字符串
-impl ToString for ErrorMessage {
-    fn to_string(&self) -> String {
-        self.to_str().to_owned()
+// 为 ErrorMessage 实现 Display trait，允许将 ErrorMessage 转换为字符串
+impl fmt::Display for ErrorMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str())
     }
 }
 
@@ -56,6 +81,31 @@ impl ErrorMessage {
             ErrorMessage::ExceededMaxPasswordLength(max_length) => format!("Password must not be more than {} characters", max_length), // 密码超出最大长度
             ErrorMessage::InvalidToken => "Authentication token is invalid or expired".to_string(), // 无效或过期的令牌
             ErrorMessage::TokenNotProvided => "You are not logged in, please provide a token".to_string(), // 未提供令牌
+            ErrorMessage::InvalidPublicKey => "Public key must be an RSA key in SPKI or PKCS#1 PEM format".to_string(), // 公钥无法解析
+            ErrorMessage::PublicKeyTooSmall(min_bits) => format!("Public key must be at least {} bits", min_bits), // 公钥长度不足
+            ErrorMessage::InvalidPrivateKey => "Private key must be an RSA key in PKCS#8 or PKCS#1 PEM format".to_string(), // 私钥无法解析
+            ErrorMessage::RefreshTokenNotProvided => "Refresh token is missing, please log in again".to_string(), // 未提供刷新令牌
+            ErrorMessage::RefreshTokenReused => "Refresh token has already been used, all sessions from this login have been revoked".to_string(), // 刷新令牌被重复使用
+            ErrorMessage::SessionRevoked => "Session has been revoked, please log in again".to_string(), // 会话已被吊销
+            ErrorMessage::InsufficientScope => "This access token is not allowed to perform this action".to_string(), // 令牌权限不足
+            ErrorMessage::MfaCodeRequired => "Two-factor authentication code is required".to_string(), // 缺少两步验证码
+            ErrorMessage::InvalidMfaCode => "Invalid two-factor authentication code".to_string(), // 两步验证码错误
+            ErrorMessage::MfaAlreadyEnabled => "Two-factor authentication is already enabled".to_string(), // 两步验证已启用
+            ErrorMessage::MfaNotEnabled => "Two-factor authentication is not enabled".to_string(), // 两步验证未启用
+            ErrorMessage::MfaNotSetUp => "Two-factor authentication has not been set up, please request a new secret".to_string(), // 尚未生成两步验证密钥
+            ErrorMessage::MfaConfirmationRequired => "Password is required to disable two-factor authentication".to_string(), // 关闭两步验证时缺少密码
+            ErrorMessage::IncorrectPassword => "Password is incorrect".to_string(), // 重新确认的密码错误
+            ErrorMessage::TooManyMfaAttempts => "Too many verification attempts, please try again later".to_string(), // 两步验证码尝试次数过多
+            ErrorMessage::InvalidEmailToken => "This link is invalid or has expired".to_string(), // 邮件链接无效或已过期
+            ErrorMessage::RecipientNotVerified => "Recipient has not verified their email address".to_string(), // 接收者邮箱尚未验证
+            ErrorMessage::EmailNotVerified => "Please verify your email address first".to_string(), // 当前用户邮箱尚未验证
+            ErrorMessage::LoginLocked(retry_after) => format!("Too many failed login attempts, please try again in {} seconds", retry_after), // 登录被暂时锁定
+            ErrorMessage::OidcNotConfigured => "Single sign-on is not configured".to_string(), // 未配置单点登录
+            ErrorMessage::InvalidOidcState => "Single sign-on session is invalid or has expired, please try again".to_string(), // 单点登录会话无效或已过期
+            ErrorMessage::OidcLoginFailed => "Single sign-on failed, please try again".to_string(), // 单点登录失败
+            ErrorMessage::AuthProviderUnavailable => "Authentication service is unavailable, please try again later".to_string(), // 认证后端不可用
+            ErrorMessage::PasswordManagedByDirectory => "Password is managed by the directory, please change it there".to_string(), // 密码由外部目录管理
+            ErrorMessage::MagicLinkDisabled => "Magic link login is not enabled".to_string(), // 未启用免密码登录
         }
     }
 }
@@ -109,6 +159,22 @@ impl HttpError {
         }
     }
 
+    // 创建一个 403（禁止访问）状态的 HttpError
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        HttpError {
+            message: message.into(),  // 设置错误消息
+            status: StatusCode::FORBIDDEN,  // 设置 HTTP 状态码为 403
+        }
+    }
+
+    // 创建一个 429（请求过多）状态的 HttpError
+    pub fn too_many_requests(message: impl Into<String>) -> Self {
+        HttpError {
+            message: message.into(),  // 设置错误消息
+            status: StatusCode::TOO_MANY_REQUESTS,  // 设置 HTTP 状态码为 429
+        }
+    }
+
     // 将 HttpError 转换为 HTTP 响应
     pub fn into_http_response(self) -> Response {
         // 创建一个 JSON 格式的错误响应
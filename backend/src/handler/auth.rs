@@ -0,0 +1,958 @@
+// 引入标准库中的 Arc 和 SocketAddr，用于共享应用状态和获取客户端地址
+use std::{net::SocketAddr, sync::Arc};
+
+// 引入 axum 的路由、响应和扩展类型
+use axum::{
+    extract::ConnectInfo,
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::post,
+    Extension, Json, Router,
+};
+// 引入 axum-extra 的 Cookie 类型
+use axum_extra::extract::cookie::{Cookie, CookieJar};
+// 引入 chrono 库，用于计算刷新令牌的过期时间
+use chrono::{Duration, Utc};
+// 引入 uuid 库，用于生成会话族 ID
+use uuid::Uuid;
+// 引入 validator 库，用于请求数据校验
+use validator::Validate;
+
+use crate::{
+    auth_provider::{random_password_hash, AuthOutcome},
+    db::UserExt,
+    dtos::{
+        EmailDto, EmailTokenDto, LoginUserDto, MfaRequiredResponseDto, RefreshTokenDto,
+        RegisterUserDto, ResetPasswordDto, Response, UserLoginResponseDto, VerifyMfaDto,
+    },
+    error::{ErrorMessage, HttpError},
+    mailer::Email,
+    middleware::extract_access_token,
+    models::{EmailTokenPurpose, User, UserTotp},
+    utils::{
+        login_throttle::{self, ThrottleScope},
+        password, token, totp,
+    },
+    AppState,
+};
+
+// 刷新令牌 Cookie 的名称和路径，只在认证接口下发送
+const REFRESH_TOKEN_COOKIE: &str = "refresh_token";
+const REFRESH_TOKEN_COOKIE_PATH: &str = "/auth";
+
+// 两步验证令牌的有效期（分钟）
+const MFA_TOKEN_MAXAGE: i64 = 5;
+
+// 邮箱验证链接和重置密码链接的有效期（分钟）
+const VERIFY_EMAIL_TOKEN_MAXAGE: i64 = 24 * 60;
+const RESET_PASSWORD_TOKEN_MAXAGE: i64 = 30;
+
+// 免密码登录链接的有效期（分钟）
+const MAGIC_LINK_TOKEN_MAXAGE: i64 = 15;
+
+/// 创建认证相关的路由
+///
+/// # 返回
+/// 返回包含注册、邮箱验证、登录、免密码登录、两步验证、刷新令牌、登出和重置密码接口的 `Router`。
+pub fn auth_handler() -> Router {
+    Router::new()
+        .route("/register", post(register))
+        .route("/verify-email", post(verify_email))
+        .route("/resend-verification", post(resend_verification))
+        .route("/login", post(login))
+        .route("/login/mfa", post(verify_mfa))
+        .route("/magic-link", post(request_magic_link))
+        .route("/magic-link/verify", post(verify_magic_link))
+        .route("/refresh", post(refresh))
+        .route("/logout", post(logout))
+        .route("/forgot-password", post(forgot_password))
+        .route("/reset-password", post(reset_password))
+}
+
+// 用户注册：校验参数、哈希密码并保存用户，然后发送邮箱验证邮件
+pub async fn register(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<RegisterUserDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let hash_password = password::hash(&body.password)
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let result = app_state
+        .db_client
+        .save_user(&body.name, &body.email, &hash_password)
+        .await;
+
+    match result {
+        Ok(user) => {
+            send_verification_email(&app_state, &user).await?;
+
+            Ok((
+                StatusCode::CREATED,
+                Json(Response {
+                    status: "success",
+                    message: "Registration successful! Please check your email to verify your account".to_string(),
+                }),
+            ))
+        }
+        // 邮箱唯一约束冲突时返回 409
+        Err(sqlx::Error::Database(db_err)) => {
+            if db_err.is_unique_violation() {
+                Err(HttpError::unique_constraint_violation(
+                    ErrorMessage::EmailExist.to_string(),
+                ))
+            } else {
+                Err(HttpError::server_error(db_err.to_string()))
+            }
+        }
+        Err(e) => Err(HttpError::server_error(e.to_string())),
+    }
+}
+
+// 验证邮箱：使用邮件中的令牌将用户邮箱标记为已验证
+pub async fn verify_email(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<EmailTokenDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let user_id = app_state
+        .db_client
+        .consume_email_token(&token::hash_token(&body.token), EmailTokenPurpose::VerifyEmail)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or_else(|| HttpError::bad_request(ErrorMessage::InvalidEmailToken.to_string()))?;
+
+    app_state
+        .db_client
+        .verify_user_email(user_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    Ok(Json(Response {
+        status: "success",
+        message: "Email verified successfully".to_string(),
+    }))
+}
+
+// 重新发送邮箱验证邮件：无论邮箱是否存在都返回相同的响应，避免暴露注册用户
+pub async fn resend_verification(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<EmailDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    // 查询账户、创建令牌和发送邮件都在后台完成，账户是否存在不影响响应时间
+    let email = body.email;
+    tokio::spawn(async move {
+        if let Err(e) = resend_verification_email(&app_state, &email).await {
+            eprintln!("Failed to send verification email to {}: {}", email, e);
+        }
+    });
+
+    Ok(Json(Response {
+        status: "success",
+        message: "If the account exists and is not verified yet, a verification email has been sent".to_string(),
+    }))
+}
+
+// 账户存在且邮箱尚未验证时重新发送验证邮件
+async fn resend_verification_email(app_state: &AppState, email: &str) -> Result<(), HttpError> {
+    let user = app_state
+        .db_client
+        .get_user(None, None, Some(email))
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    if let Some(user) = user.filter(|user| user.email_verified_at.is_none()) {
+        send_verification_email(app_state, &user).await?;
+    }
+
+    Ok(())
+}
+
+// 用户登录：检查登录限流后校验邮箱和密码，创建会话并签发访问令牌和刷新令牌
+pub async fn login(
+    ConnectInfo(client_addr): ConnectInfo<SocketAddr>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<LoginUserDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    // 分别按账户和 IP 限流，锁定期间不再校验密码
+    let account_key = body.email.to_lowercase();
+    let ip_key = client_addr.ip().to_string();
+    let throttle_keys = [
+        (ThrottleScope::Account, account_key.as_str()),
+        (ThrottleScope::Ip, ip_key.as_str()),
+    ];
+
+    ensure_login_allowed(&app_state, &throttle_keys).await?;
+
+    // 由配置的认证后端（本地密码、LDAP 等）校验邮箱和密码
+    let outcome = app_state
+        .auth_provider
+        .authenticate(&app_state.db_client, &body.email, &body.password)
+        .await?;
+
+    let user = match outcome {
+        AuthOutcome::Authenticated(user) => user,
+        AuthOutcome::Rejected | AuthOutcome::Unknown => {
+            record_login_failure(&app_state, &throttle_keys).await?;
+
+            return Err(HttpError::bad_request(
+                ErrorMessage::WrongCredentials.to_string(),
+            ));
+        }
+    };
+
+    // 登录成功后清除该账户的失败记录，IP 的失败记录随统计窗口自然过期
+    app_state
+        .db_client
+        .clear_login_throttle(Some(ThrottleScope::Account.as_str()), &account_key)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    complete_login(&app_state, &user).await
+}
+
+// 第一步认证通过后完成登录
+// 已启用两步验证时不签发令牌，只返回短期有效的两步验证令牌
+async fn complete_login(
+    app_state: &AppState,
+    user: &User,
+) -> Result<axum::response::Response, HttpError> {
+    let totp = app_state
+        .db_client
+        .get_user_totp(user.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    if totp.is_some_and(|totp| totp.enabled_at.is_some()) {
+        let mfa_token = token::create_mfa_token(
+            &user.id.to_string(),
+            user.token_version,
+            &app_state.jwt_keys,
+            MFA_TOKEN_MAXAGE,
+        )
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+        let response = Json(MfaRequiredResponseDto {
+            status: "mfa_required".to_string(),
+            mfa_token,
+        });
+
+        return Ok(response.into_response());
+    }
+
+    let (access_token, refresh_token) = issue_tokens(app_state, user, None).await?;
+
+    Ok(token_response(app_state, access_token, refresh_token))
+}
+
+// 请求免密码登录链接：无论邮箱是否存在都返回相同的响应，避免暴露注册用户
+pub async fn request_magic_link(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<EmailDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    ensure_magic_link_enabled(&app_state)?;
+
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    // 查询账户、创建令牌和发送邮件都在后台完成，账户是否存在不影响响应时间
+    let email = body.email;
+    tokio::spawn(async move {
+        if let Err(e) = send_magic_link(&app_state, &email).await {
+            eprintln!("Failed to send login link to {}: {}", email, e);
+        }
+    });
+
+    Ok(Json(Response {
+        status: "success",
+        message: "If the account exists, a login link has been sent".to_string(),
+    }))
+}
+
+// 账户存在时创建免密码登录令牌并发送登录链接
+async fn send_magic_link(app_state: &AppState, email: &str) -> Result<(), HttpError> {
+    let user = app_state
+        .db_client
+        .get_user(None, None, Some(email))
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    if let Some(user) = user {
+        if let Some(link) = create_email_link(
+            app_state,
+            &user,
+            EmailTokenPurpose::MagicLogin,
+            "magic-link",
+            MAGIC_LINK_TOKEN_MAXAGE,
+        )
+        .await?
+        {
+            send_in_background(
+                app_state,
+                Email {
+                    to: user.email.clone(),
+                    subject: "Your SecureShare login link".to_string(),
+                    body: format!(
+                        "Hi {},\n\nUse the link below to log in to SecureShare. It expires in {} minutes and can only be used once.\n\n{}\n\nIf you did not request this link, you can ignore this email.\n",
+                        user.name, MAGIC_LINK_TOKEN_MAXAGE, link
+                    ),
+                },
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// 使用免密码登录链接登录：令牌只能使用一次，之后与密码登录相同（包括两步验证）
+pub async fn verify_magic_link(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<EmailTokenDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    ensure_magic_link_enabled(&app_state)?;
+
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let user_id = app_state
+        .db_client
+        .consume_email_token(&token::hash_token(&body.token), EmailTokenPurpose::MagicLogin)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or_else(|| HttpError::bad_request(ErrorMessage::InvalidEmailToken.to_string()))?;
+
+    // 能打开邮件中的链接即证明拥有该邮箱。邮箱未验证的账户可能由他人抢先注册，
+    // 由邮箱所有者接管：密码重置为随机值，并吊销抢注者留下的会话、令牌、设备公钥和两步验证
+    app_state
+        .db_client
+        .claim_unverified_user(user_id, random_password_hash()?, false)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let user = app_state
+        .db_client
+        .get_user(Some(user_id), None, None)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNoLongerExist.to_string()))?;
+
+    complete_login(&app_state, &user).await
+}
+
+// 未启用免密码登录时返回 404
+fn ensure_magic_link_enabled(app_state: &AppState) -> Result<(), HttpError> {
+    if app_state.env.magic_link_enabled {
+        Ok(())
+    } else {
+        Err(HttpError::new(
+            ErrorMessage::MagicLinkDisabled.to_string(),
+            StatusCode::NOT_FOUND,
+        ))
+    }
+}
+
+// 登录第二步：校验两步验证令牌和验证码（或恢复码），通过后签发令牌
+pub async fn verify_mfa(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<VerifyMfaDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let claims = token::decode_mfa_token(&body.mfa_token, &app_state.jwt_keys)?;
+
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| HttpError::unauthorized(ErrorMessage::InvalidToken.to_string()))?;
+
+    let code = body
+        .code
+        .filter(|code| !code.trim().is_empty())
+        .ok_or_else(|| HttpError::bad_request(ErrorMessage::MfaCodeRequired.to_string()))?;
+
+    if !app_state.mfa_limiter.check(&user_id.to_string()) {
+        return Err(HttpError::too_many_requests(
+            ErrorMessage::TooManyMfaAttempts.to_string(),
+        ));
+    }
+
+    let user = app_state
+        .db_client
+        .get_user(Some(user_id), None, None)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNoLongerExist.to_string()))?;
+
+    // 第一步之后修改过密码或退出过所有设备，两步验证令牌随之失效
+    if claims.ver != user.token_version {
+        return Err(HttpError::unauthorized(
+            ErrorMessage::InvalidToken.to_string(),
+        ));
+    }
+
+    let totp = app_state
+        .db_client
+        .get_user_totp(user.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .filter(|totp| totp.enabled_at.is_some())
+        .ok_or_else(|| HttpError::bad_request(ErrorMessage::MfaNotEnabled.to_string()))?;
+
+    verify_second_factor(&app_state, &totp, &code).await?;
+
+    let (access_token, refresh_token) = issue_tokens(&app_state, &user, None).await?;
+
+    Ok(token_response(&app_state, access_token, refresh_token))
+}
+
+// 检查账户和 IP 是否处于锁定状态，锁定时返回 429 和剩余秒数
+pub async fn ensure_login_allowed(
+    app_state: &AppState,
+    throttle_keys: &[(ThrottleScope, &str)],
+) -> Result<(), HttpError> {
+    for (scope, throttle_key) in throttle_keys {
+        let throttle = app_state
+            .db_client
+            .get_login_throttle(scope.as_str(), throttle_key)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+        let now = Utc::now();
+        if let Some(locked_until) = throttle
+            .and_then(|throttle| throttle.locked_until)
+            .filter(|locked_until| *locked_until > now)
+        {
+            let retry_after = (locked_until - now).num_seconds().max(1);
+
+            return Err(HttpError::too_many_requests(
+                ErrorMessage::LoginLocked(retry_after).to_string(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// 记录一次登录失败，并按失败次数指数退避或锁定
+pub async fn record_login_failure(
+    app_state: &AppState,
+    throttle_keys: &[(ThrottleScope, &str)],
+) -> Result<(), HttpError> {
+    for (scope, throttle_key) in throttle_keys {
+        let failure_count = app_state
+            .db_client
+            .record_login_failure(
+                scope.as_str(),
+                throttle_key,
+                app_state.env.login_lockout_minutes as i32,
+            )
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+        if let Some(duration) = login_throttle::lock_duration(*scope, failure_count, &app_state.env) {
+            app_state
+                .db_client
+                .lock_login(scope.as_str(), throttle_key, Utc::now() + duration)
+                .await
+                .map_err(|e| HttpError::server_error(e.to_string()))?;
+        }
+    }
+
+    Ok(())
+}
+
+// 刷新令牌：每次使用都会轮换刷新令牌，已轮换的令牌再次出现时吊销整个会话
+pub async fn refresh(
+    Extension(app_state): Extension<Arc<AppState>>,
+    cookie_jar: CookieJar,
+    body: Option<Json<RefreshTokenDto>>,
+) -> Result<impl IntoResponse, HttpError> {
+    let refresh_token = extract_refresh_token(&cookie_jar, body).ok_or_else(|| {
+        HttpError::unauthorized(ErrorMessage::RefreshTokenNotProvided.to_string())
+    })?;
+
+    let session = app_state
+        .db_client
+        .get_session_by_token_hash(&token::hash_token(&refresh_token))
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::InvalidToken.to_string()))?;
+
+    if session.revoked_at.is_some() {
+        return Err(HttpError::unauthorized(
+            ErrorMessage::SessionRevoked.to_string(),
+        ));
+    }
+
+    // 已轮换的刷新令牌被再次使用，说明令牌可能已泄露，吊销整个会话族
+    let marked = app_state
+        .db_client
+        .mark_session_used(session.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    if !marked {
+        app_state
+            .db_client
+            .revoke_session_family(session.family_id)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+        return Err(HttpError::unauthorized(
+            ErrorMessage::RefreshTokenReused.to_string(),
+        ));
+    }
+
+    if session.expires_at <= Utc::now() {
+        return Err(HttpError::unauthorized(
+            ErrorMessage::InvalidToken.to_string(),
+        ));
+    }
+
+    // 重新读取用户，使新的访问令牌携带最新的令牌版本号
+    let user = app_state
+        .db_client
+        .get_user(Some(session.user_id), None, None)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNoLongerExist.to_string()))?;
+
+    let (access_token, refresh_token) =
+        issue_tokens(&app_state, &user, Some(session.family_id)).await?;
+
+    Ok(token_response(&app_state, access_token, refresh_token))
+}
+
+// 用户登出：吊销服务端会话并清除令牌 Cookie
+pub async fn logout(
+    Extension(app_state): Extension<Arc<AppState>>,
+    cookie_jar: CookieJar,
+    headers: HeaderMap,
+    body: Option<Json<RefreshTokenDto>>,
+) -> Result<impl IntoResponse, HttpError> {
+    // 优先根据刷新令牌找到会话族，其次根据访问令牌中的 sid
+    let mut family_id = None;
+
+    if let Some(refresh_token) = extract_refresh_token(&cookie_jar, body) {
+        family_id = app_state
+            .db_client
+            .get_session_by_token_hash(&token::hash_token(&refresh_token))
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?
+            .map(|session| session.family_id);
+    }
+
+    if family_id.is_none() {
+        family_id = extract_access_token(&cookie_jar, &headers)
+            .and_then(|access_token| {
+                token::decode_token(access_token, &app_state.jwt_keys).ok()
+            })
+            .and_then(|claims| Uuid::parse_str(&claims.sid).ok());
+    }
+
+    if let Some(family_id) = family_id {
+        app_state
+            .db_client
+            .revoke_session_family(family_id)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+    }
+
+    let access_cookie = Cookie::build(("token", ""))
+        .path("/")
+        .max_age(time::Duration::minutes(-1))
+        .http_only(true)
+        .build();
+
+    let refresh_cookie = Cookie::build((REFRESH_TOKEN_COOKIE, ""))
+        .path(REFRESH_TOKEN_COOKIE_PATH)
+        .max_age(time::Duration::minutes(-1))
+        .http_only(true)
+        .build();
+
+    let mut headers = HeaderMap::new();
+    headers.append(header::SET_COOKIE, access_cookie.to_string().parse().unwrap());
+    headers.append(header::SET_COOKIE, refresh_cookie.to_string().parse().unwrap());
+
+    let response = Json(Response {
+        status: "success",
+        message: "Logout successful".to_string(),
+    });
+
+    let mut response = response.into_response();
+    response.headers_mut().extend(headers);
+
+    Ok(response)
+}
+
+// 忘记密码：发送重置密码邮件，无论邮箱是否存在都返回相同的响应
+pub async fn forgot_password(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<EmailDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    // 查询账户、创建令牌和发送邮件都在后台完成，账户是否存在不影响响应时间
+    let email = body.email;
+    tokio::spawn(async move {
+        if let Err(e) = send_password_reset(&app_state, &email).await {
+            eprintln!("Failed to send password reset email to {}: {}", email, e);
+        }
+    });
+
+    Ok(Json(Response {
+        status: "success",
+        message: "If the account exists, a password reset email has been sent".to_string(),
+    }))
+}
+
+// 账户存在且密码不由外部目录管理时创建重置密码令牌并发送重置链接
+async fn send_password_reset(app_state: &AppState, email: &str) -> Result<(), HttpError> {
+    let user = app_state
+        .db_client
+        .get_user(None, None, Some(email))
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let Some(user) = user else {
+        return Ok(());
+    };
+
+    // 密码由 LDAP 等外部目录管理的账户不发送重置邮件
+    if app_state
+        .auth_provider
+        .manages_password(&app_state.db_client, user.id)
+        .await?
+    {
+        return Ok(());
+    }
+
+    if let Some(link) = create_email_link(
+        app_state,
+        &user,
+        EmailTokenPurpose::ResetPassword,
+        "reset-password",
+        RESET_PASSWORD_TOKEN_MAXAGE,
+    )
+    .await?
+    {
+        send_in_background(
+            app_state,
+            Email {
+                to: user.email.clone(),
+                subject: "Reset your SecureShare password".to_string(),
+                body: format!(
+                    "Hi {},\n\nUse the link below to reset your password. It expires in {} minutes and can only be used once.\n\n{}\n\nIf you did not request a password reset, you can ignore this email.\n",
+                    user.name, RESET_PASSWORD_TOKEN_MAXAGE, link
+                ),
+            },
+        );
+    }
+
+    Ok(())
+}
+
+// 重置密码：使用邮件中的一次性令牌设置新密码，并使所有已登录设备失效
+pub async fn reset_password(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<ResetPasswordDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let hash_password = password::hash(&body.new_password)
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let token_hash = token::hash_token(&body.token);
+
+    let user_id = app_state
+        .db_client
+        .peek_email_token(&token_hash, EmailTokenPurpose::ResetPassword)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or_else(|| HttpError::bad_request(ErrorMessage::InvalidEmailToken.to_string()))?;
+
+    // 密码由 LDAP 等外部目录管理的账户不重置本地密码，此时不使用令牌
+    if app_state
+        .auth_provider
+        .manages_password(&app_state.db_client, user_id)
+        .await?
+    {
+        return Err(HttpError::forbidden(
+            ErrorMessage::PasswordManagedByDirectory.to_string(),
+        ));
+    }
+
+    // 校验通过后才使用令牌，并发请求中只有一个能使用成功
+    let user_id = app_state
+        .db_client
+        .consume_email_token(&token_hash, EmailTokenPurpose::ResetPassword)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or_else(|| HttpError::bad_request(ErrorMessage::InvalidEmailToken.to_string()))?;
+
+    // 能收到重置密码邮件说明用户拥有该邮箱。邮箱未验证的账户可能由他人抢先注册，
+    // 接管时还要吊销抢注者留下的个人访问令牌、设备公钥和两步验证
+    let claimed = app_state
+        .db_client
+        .claim_unverified_user(user_id, hash_password.clone(), true)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    // 修改密码会递增令牌版本号并吊销全部会话和个人访问令牌
+    if claimed.is_none() {
+        app_state
+            .db_client
+            .update_user_password(user_id, hash_password)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+    }
+
+    // 重置密码后解除该账户的登录锁定
+    if let Some(user) = app_state
+        .db_client
+        .get_user(Some(user_id), None, None)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+    {
+        app_state
+            .db_client
+            .clear_login_throttle(Some(ThrottleScope::Account.as_str()), &user.email.to_lowercase())
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+    }
+
+    Ok(Json(Response {
+        status: "success",
+        message: "Password has been reset, please log in with your new password".to_string(),
+    }))
+}
+
+/// 为用户签发访问令牌和刷新令牌
+///
+/// 刷新令牌的摘要保存到会话表中，访问令牌中携带会话族 ID。
+///
+/// # 参数
+/// - `app_state`: 应用全局状态。
+/// - `user`: 登录的用户。
+/// - `family_id`: 会话族 ID，为空时创建新的会话族（即一次新的登录）。
+///
+/// # 返回
+/// 返回 `(访问令牌, 刷新令牌)` 或 500 错误。
+pub async fn issue_tokens(
+    app_state: &AppState,
+    user: &User,
+    family_id: Option<Uuid>,
+) -> Result<(String, String), HttpError> {
+    let family_id = family_id.unwrap_or_else(Uuid::new_v4);
+
+    let refresh_token = token::generate_random_token();
+    let expires_at = Utc::now() + Duration::minutes(app_state.env.refresh_token_maxage);
+
+    app_state
+        .db_client
+        .create_session(
+            user.id,
+            family_id,
+            token::hash_token(&refresh_token),
+            expires_at,
+        )
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let access_token = token::create_token(
+        &user.id.to_string(),
+        &family_id.to_string(),
+        user.token_version,
+        &app_state.jwt_keys,
+        app_state.env.jwt_maxage,
+    )
+    .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    Ok((access_token, refresh_token))
+}
+
+/// 构造写入访问令牌和刷新令牌的 HttpOnly Cookie 响应头
+///
+/// # 参数
+/// - `app_state`: 应用全局状态。
+/// - `access_token`: 访问令牌。
+/// - `refresh_token`: 刷新令牌。
+///
+/// # 返回
+/// 返回包含两个 `Set-Cookie` 的 `HeaderMap`。
+pub fn token_cookies(app_state: &AppState, access_token: &str, refresh_token: &str) -> HeaderMap {
+    // Cookie 的有效期与对应令牌保持一致
+    let access_cookie = Cookie::build(("token", access_token.to_string()))
+        .path("/")
+        .max_age(time::Duration::minutes(app_state.env.jwt_maxage))
+        .http_only(true)
+        .build();
+
+    let refresh_cookie = Cookie::build((REFRESH_TOKEN_COOKIE, refresh_token.to_string()))
+        .path(REFRESH_TOKEN_COOKIE_PATH)
+        .max_age(time::Duration::minutes(app_state.env.refresh_token_maxage))
+        .http_only(true)
+        .build();
+
+    let mut headers = HeaderMap::new();
+    headers.append(header::SET_COOKIE, access_cookie.to_string().parse().unwrap());
+    headers.append(header::SET_COOKIE, refresh_cookie.to_string().parse().unwrap());
+
+    headers
+}
+
+/// 构造包含令牌的登录响应，并写入 HttpOnly Cookie
+///
+/// # 参数
+/// - `app_state`: 应用全局状态。
+/// - `access_token`: 访问令牌。
+/// - `refresh_token`: 刷新令牌。
+///
+/// # 返回
+/// 返回 JSON 格式的 `UserLoginResponseDto`，并通过 `Set-Cookie` 写入两个令牌。
+pub fn token_response(
+    app_state: &AppState,
+    access_token: String,
+    refresh_token: String,
+) -> axum::response::Response {
+    let headers = token_cookies(app_state, &access_token, &refresh_token);
+
+    let response = Json(UserLoginResponseDto {
+        status: "success".to_string(),
+        token: access_token,
+        refresh_token,
+    });
+
+    let mut response = response.into_response();
+    response.headers_mut().extend(headers);
+
+    response
+}
+
+// 生成邮箱验证链接并在后台发送验证邮件
+async fn send_verification_email(app_state: &AppState, user: &User) -> Result<(), HttpError> {
+    let link = create_email_link(
+        app_state,
+        user,
+        EmailTokenPurpose::VerifyEmail,
+        "verify-email",
+        VERIFY_EMAIL_TOKEN_MAXAGE,
+    )
+    .await?;
+
+    if let Some(link) = link {
+        send_in_background(
+            app_state,
+            Email {
+                to: user.email.clone(),
+                subject: "Verify your SecureShare email address".to_string(),
+                body: format!(
+                    "Hi {},\n\nPlease confirm your email address by opening the link below. It expires in {} hours.\n\n{}\n\nYou will not be able to receive files until your email address is verified.\n",
+                    user.name,
+                    VERIFY_EMAIL_TOKEN_MAXAGE / 60,
+                    link
+                ),
+            },
+        );
+    }
+
+    Ok(())
+}
+
+// 创建邮件令牌并拼接前端链接，超过该邮箱同类邮件的发送频率限制时返回 None
+// 按用途分别限流，频繁请求登录链接不会影响验证邮件和重置密码邮件
+async fn create_email_link(
+    app_state: &AppState,
+    user: &User,
+    purpose: EmailTokenPurpose,
+    path: &str,
+    expires_in_minutes: i64,
+) -> Result<Option<String>, HttpError> {
+    let limiter_key = format!("{}:{}", purpose.as_str(), user.email.to_lowercase());
+    if !app_state.mail_limiter.check(&limiter_key) {
+        return Ok(None);
+    }
+
+    let email_token = token::generate_random_token();
+    let expires_at = Utc::now() + Duration::minutes(expires_in_minutes);
+
+    app_state
+        .db_client
+        .create_email_token(user.id, purpose, token::hash_token(&email_token), expires_at)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    Ok(Some(format!(
+        "{}/{}?token={}",
+        app_state.env.app_url.trim_end_matches('/'),
+        path,
+        email_token
+    )))
+}
+
+// 在后台发送邮件，响应时间不受邮件服务器影响，发送失败只记录日志
+fn send_in_background(app_state: &AppState, email: Email) {
+    let mailer = app_state.mailer.clone();
+
+    tokio::spawn(async move {
+        let to = email.to.clone();
+        if let Err(e) = mailer.send(email).await {
+            eprintln!("Failed to send email to {}: {}", to, e);
+        }
+    });
+}
+
+// 校验 TOTP 验证码或一次性恢复码，通过后记录使用情况，同一验证码或恢复码不能重复使用
+pub async fn verify_second_factor(
+    app_state: &AppState,
+    totp: &UserTotp,
+    code: &str,
+) -> Result<(), HttpError> {
+    let verified = match totp::verify_code(&totp.secret, code, totp.last_used_step) {
+        // 按时间步做条件更新，并发请求中同一验证码只有一个能通过
+        Some(step) => app_state
+            .db_client
+            .update_totp_last_used_step(totp.user_id, step)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?,
+        None => {
+            let code_hash = token::hash_token(&totp::normalize_recovery_code(code));
+            app_state
+                .db_client
+                .use_recovery_code(totp.user_id, &code_hash)
+                .await
+                .map_err(|e| HttpError::server_error(e.to_string()))?
+        }
+    };
+
+    if !verified {
+        return Err(HttpError::unauthorized(
+            ErrorMessage::InvalidMfaCode.to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+// 从 Cookie 或请求体中读取刷新令牌
+fn extract_refresh_token(
+    cookie_jar: &CookieJar,
+    body: Option<Json<RefreshTokenDto>>,
+) -> Option<String> {
+    cookie_jar
+        .get(REFRESH_TOKEN_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .filter(|token| !token.is_empty())
+        .or_else(|| body.and_then(|Json(body)| body.refresh_token))
+}
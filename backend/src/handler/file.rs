@@ -0,0 +1,885 @@
+// 引入标准库中的 Arc，用于共享应用状态
+use std::sync::Arc;
+
+// 引入 axum 的路由、请求体和扩展类型
+use axum::{
+    body::Body,
+    extract::{multipart::Field, DefaultBodyLimit, Multipart},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::IntoResponse,
+    routing::post,
+    Extension, Json, Router,
+};
+// 引入 base64 库，用于在响应头中传递二进制的密钥和 IV
+use base64::{engine::general_purpose::STANDARD, Engine};
+// 引入 chrono 库，用于解析文件过期时间
+use chrono::{DateTime, Utc};
+// 引入 futures 库，用于将分段读取包装为响应流
+use futures::{stream, TryStreamExt};
+// 引入 rsa 库的公钥和私钥类型
+use rsa::{traits::PublicKeyParts, RsaPrivateKey, RsaPublicKey};
+// 引入 validator 库，用于请求数据校验
+use validator::Validate;
+
+use crate::{
+    db::UserExt,
+    dtos::{DownloadSentFileDto, FileUploadDtos, ForwardFileDto, Response, RetrieveFileDto},
+    error::{ErrorMessage, HttpError},
+    middleware::JWTAuthMiddleware,
+    models::{File, FileKey, User},
+    utils::{
+        cipher::{
+            self, CipherSuite, AEAD_TAG_LEN, KEY_WRAP_VERSION, STREAM_FORMAT_VERSION,
+            STREAM_SEGMENT_SIZE,
+        },
+        decrypt::{decrypt_file, unwrap_key, StreamDecryptor},
+        encrypt::{wrap_key, StreamEncryptor},
+        keys, password,
+    },
+    AppState,
+};
+
+// 允许上传的最大文件大小（字节），上传和下载都按分段流式处理，内存占用与文件大小无关
+// 以 u64 声明，32 位平台的 usize 无法表示该值
+const MAX_FILE_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+
+// 返回密文时，用于携带加密后的 AES 密钥和 IV 的响应头（Base64 编码）
+pub const ENCRYPTED_AES_KEY_HEADER: &str = "x-encrypted-aes-key";
+pub const ENCRYPTION_IV_HEADER: &str = "x-encryption-iv";
+// 返回密文时，用于携带加密算法、密文格式版本和附加认证数据（Base64 编码）的响应头
+pub const CIPHER_SUITE_HEADER: &str = "x-cipher-suite";
+pub const FORMAT_VERSION_HEADER: &str = "x-format-version";
+pub const ENCRYPTION_AAD_HEADER: &str = "x-encryption-aad";
+// 返回流式格式的密文时，用于携带每个分段明文大小的响应头
+pub const SEGMENT_SIZE_HEADER: &str = "x-segment-size";
+// 返回密文时，用于携带包装文件密钥所用设备公钥指纹的响应头
+pub const KEY_FINGERPRINT_HEADER: &str = "x-key-fingerprint";
+// 返回密文时，用于携带包装文件密钥所用 OAEP 标签的响应头，旧版包装格式没有标签时不返回
+pub const KEY_WRAP_LABEL_HEADER: &str = "x-key-wrap-label";
+
+/// 创建文件相关的路由
+///
+/// 所有接口都需要经过认证中间件，在 `routes.rs` 中统一挂载。
+///
+/// # 返回
+/// 返回包含文件上传和文件获取接口的 `Router`。
+pub fn file_handle() -> Router {
+    Router::new()
+        .route(
+            "/upload",
+            // 预留表单字段的空间，避免文件大小恰好达到上限时被拒绝
+            // 32 位平台上超出 usize 范围时取 usize::MAX，文件大小仍在上传时按 MAX_FILE_SIZE 校验
+            post(upload_file).layer(DefaultBodyLimit::max(
+                usize::try_from(MAX_FILE_SIZE + 64 * 1024).unwrap_or(usize::MAX),
+            )),
+        )
+        .route("/retrieve", post(retrieve_file))
+        .route("/download", post(download_sent_file))
+        .route("/forward", post(forward_file))
+}
+
+// 上传文件：边接收边使用接收者的公钥分段加密并保存，完成后创建共享链接
+// 文件内容不会整体缓存在内存中，因此表单中的 recipient_email、password 和 expiration_date 必须位于 file 之前
+pub async fn upload_file(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, HttpError> {
+    let mut form_data = FileUploadDtos::default();
+    let mut uploaded = false;
+
+    // 逐个读取表单字段
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| HttpError::bad_request(e.to_string()))?
+    {
+        let name = field.name().unwrap_or_default().to_string();
+
+        match name.as_str() {
+            "file" => {
+                if uploaded {
+                    return Err(HttpError::bad_request(
+                        "Only one file can be uploaded at a time".to_string(),
+                    ));
+                }
+
+                form_data.validate()
+                    .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+                let file_name = field.file_name().unwrap_or("unknown_file").to_string();
+                let target = prepare_share(
+                    &app_state,
+                    &form_data.recipient_email,
+                    &form_data.password,
+                    &form_data.expiration_date,
+                )
+                .await?;
+                let file_id = uuid::Uuid::new_v4();
+
+                // 上传失败时删除已保存的分段，避免留下不完整的文件
+                if let Err(e) =
+                    store_file(&app_state, &user.user, file_id, file_name, field, target).await
+                {
+                    if let Err(err) = app_state.db_client.delete_file(file_id).await {
+                        eprintln!("Failed to clean up incomplete upload {}: {}", file_id, err);
+                    }
+                    return Err(e);
+                }
+
+                uploaded = true;
+            }
+            "recipient_email" => {
+                form_data.recipient_email = field
+                    .text()
+                    .await
+                    .map_err(|e| HttpError::bad_request(e.to_string()))?;
+            }
+            "password" => {
+                form_data.password = field
+                    .text()
+                    .await
+                    .map_err(|e| HttpError::bad_request(e.to_string()))?;
+            }
+            "expiration_date" => {
+                form_data.expiration_date = field
+                    .text()
+                    .await
+                    .map_err(|e| HttpError::bad_request(e.to_string()))?;
+            }
+            _ => {}
+        }
+    }
+
+    if !uploaded {
+        return Err(HttpError::bad_request("File is required".to_string()));
+    }
+
+    let response = Response {
+        message: "File uploaded and encrypted successfully".to_string(),
+        status: "success",
+    };
+
+    Ok(Json(response))
+}
+
+// 校验通过的共享信息，上传时在读取文件内容之前准备好
+struct ShareTarget {
+    recipient_id: uuid::Uuid,        // 接收者 ID
+    recipient_keys: Vec<(uuid::Uuid, RsaPublicKey)>, // 接收者每把有效设备公钥的 ID 和公钥
+    hash_password: String,           // 共享链接密码的 Argon2 哈希
+    expiration_date: DateTime<Utc>,  // 共享链接的过期时间
+}
+
+// 查询接收者并要求其已验证邮箱且至少有一把有效公钥，同时计算共享链接的密码哈希和过期时间
+async fn prepare_share(
+    app_state: &AppState,
+    recipient_email: &str,
+    password: &str,
+    expiration_date: &str,
+) -> Result<ShareTarget, HttpError> {
+    let recipient_result = app_state
+        .db_client
+        .get_user(None, None, Some(recipient_email))
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let recipient_user = recipient_result.ok_or(HttpError::bad_request(
+        "Recipient user not found".to_string(),
+    ))?;
+
+    if recipient_user.email_verified_at.is_none() {
+        return Err(HttpError::bad_request(
+            ErrorMessage::RecipientNotVerified.to_string(),
+        ));
+    }
+
+    let recipient_keys = get_user_keys(app_state, recipient_user.id).await?;
+
+    if recipient_keys.is_empty() {
+        return Err(HttpError::bad_request(
+            "Recipient has not uploaded a public key".to_string(),
+        ));
+    }
+
+    // 共享链接密码只保存 Argon2 哈希
+    let hash_password = password::hash(password)
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let expiration_date = DateTime::parse_from_rfc3339(expiration_date)
+        .map_err(|e| HttpError::bad_request(e.to_string()))?
+        .with_timezone(&Utc);
+
+    Ok(ShareTarget {
+        recipient_id: recipient_user.id,
+        recipient_keys,
+        hash_password,
+        expiration_date,
+    })
+}
+
+// 查询用户的有效设备公钥并解析，返回公钥 ID 和公钥
+async fn get_user_keys(
+    app_state: &AppState,
+    user_id: uuid::Uuid,
+) -> Result<Vec<(uuid::Uuid, RsaPublicKey)>, HttpError> {
+    let user_keys = app_state
+        .db_client
+        .get_active_user_keys(user_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    user_keys
+        .iter()
+        .map(|user_key| {
+            keys::parse_public_key(&user_key.public_key)
+                .map(|public_key| (user_key.id, public_key))
+                .map_err(|e| HttpError::server_error(e.to_string()))
+        })
+        .collect()
+}
+
+// 边读取文件内容边分段加密并保存，内存中最多缓存一个分段
+// 文件密钥为接收者和发送者的每把有效设备公钥分别包装，发送者有公钥时可以之后下载和转发该文件
+async fn store_file(
+    app_state: &AppState,
+    sender: &User,
+    file_id: uuid::Uuid,
+    file_name: String,
+    mut field: Field<'_>,
+    target: ShareTarget,
+) -> Result<(), HttpError> {
+    let cipher_suite = app_state.env.file_cipher_suite;
+    let aad = cipher::stream_associated_data(cipher_suite, file_id);
+
+    let mut encryptor = StreamEncryptor::new(cipher_suite, aad)?;
+
+    let sender_keys = get_user_keys(app_state, sender.id).await?;
+
+    // 分段中不绑定接收者，接收者和发送者各自的文件密钥以 OAEP 标签绑定到持有者
+    let recipient_label = cipher::key_wrap_label(KEY_WRAP_VERSION, file_id, target.recipient_id);
+    let sender_label = cipher::key_wrap_label(KEY_WRAP_VERSION, file_id, sender.id);
+
+    let file_keys = target
+        .recipient_keys
+        .iter()
+        .map(|(key_id, public_key)| (key_id, public_key, &recipient_label))
+        .chain(sender_keys.iter().map(|(key_id, public_key)| (key_id, public_key, &sender_label)))
+        .map(|(key_id, public_key, label)| {
+            Ok((*key_id, encryptor.wrap_key(public_key, label.clone())?))
+        })
+        .collect::<Result<Vec<_>, HttpError>>()?;
+
+    app_state
+        .db_client
+        .create_file(
+            file_id,
+            sender.id,
+            file_name,
+            encryptor.nonce_prefix().to_vec(),
+            cipher_suite,
+            STREAM_SEGMENT_SIZE as i32,
+        )
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let mut file_size: u64 = 0;
+    let mut segment_count: i32 = 0;
+
+    while let Some(chunk) = field
+        .chunk()
+        .await
+        .map_err(|e| HttpError::bad_request(e.to_string()))?
+    {
+        file_size += chunk.len() as u64;
+
+        if file_size > MAX_FILE_SIZE {
+            return Err(HttpError::bad_request(format!(
+                "File must not be larger than {} bytes",
+                MAX_FILE_SIZE
+            )));
+        }
+
+        for segment in encryptor.update(&chunk)? {
+            app_state
+                .db_client
+                .save_file_segment(file_id, segment_count, segment)
+                .await
+                .map_err(|e| HttpError::server_error(e.to_string()))?;
+            segment_count += 1;
+        }
+    }
+
+    if file_size == 0 {
+        return Err(HttpError::bad_request("File is required".to_string()));
+    }
+
+    // 最后一段带有结束标记，防止密文被截断
+    let segment = encryptor.finish()?;
+
+    app_state
+        .db_client
+        .save_file_segment(file_id, segment_count, segment)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+    segment_count += 1;
+
+    app_state
+        .db_client
+        .complete_file_upload(
+            file_id,
+            file_size as i64,
+            segment_count,
+            file_keys,
+            target.recipient_id,
+            target.hash_password,
+            target.expiration_date,
+        )
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))
+}
+
+// 获取文件：校验共享链接和密码后返回文件，响应格式见 file_response
+pub async fn retrieve_file(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<RetrieveFileDto>,
+) -> Result<axum::response::Response, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let user_id = user.user.id;
+
+    let shared_id = uuid::Uuid::parse_str(&body.shared_id)
+        .map_err(|_| HttpError::bad_request("Invalid shared id".to_string()))?;
+
+    // 只查询属于当前用户且尚未过期的共享链接
+    let shared_result = app_state
+        .db_client
+        .get_shared(shared_id, user_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let shared_link = shared_result.ok_or(HttpError::new(
+        "The requested shared link does not exist or has expired",
+        StatusCode::NOT_FOUND,
+    ))?;
+
+    // 旧版共享链接的密码以明文存储，使用常量时间比较
+    let is_legacy = !password::is_hashed(&shared_link.password);
+
+    let password_matched = if is_legacy {
+        password::constant_time_eq(body.password.as_bytes(), shared_link.password.as_bytes())
+    } else {
+        password::compare(&body.password, &shared_link.password).unwrap_or(false)
+    };
+
+    if !password_matched {
+        return Err(HttpError::bad_request(
+            "Incorrect shared link password".to_string(),
+        ));
+    }
+
+    // 旧版明文密码在首次成功使用后重新以哈希形式保存，失败时不影响本次获取
+    if is_legacy {
+        match password::hash(&body.password) {
+            Ok(hash_password) => {
+                if let Err(e) = app_state
+                    .db_client
+                    .update_shared_password(shared_link.id, hash_password)
+                    .await
+                {
+                    eprintln!("Failed to rehash password of shared link {}: {}", shared_link.id, e);
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to rehash password of shared link {}: {}", shared_link.id, e);
+            }
+        }
+    }
+
+    let file_id = shared_link.file_id.ok_or(HttpError::new(
+        "The requested file does not exist",
+        StatusCode::NOT_FOUND,
+    ))?;
+
+    let file_result = app_state
+        .db_client
+        .get_file(file_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let file = file_result.ok_or(HttpError::new(
+        "The requested file does not exist",
+        StatusCode::NOT_FOUND,
+    ))?;
+
+    // 携带私钥时在服务端解密
+    let private_key = parse_private_key(body.private_key)?;
+
+    // 接收者的文件密钥在上传或转发时为其每把设备公钥分别包装
+    let file_keys = app_state
+        .db_client
+        .get_file_keys(file.id, user_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    if file_keys.is_empty() {
+        return Err(HttpError::new(
+            "The requested file does not exist",
+            StatusCode::NOT_FOUND,
+        ));
+    }
+
+    let file_key = select_file_key(file_keys, private_key.as_ref(), body.key_fingerprint)?;
+
+    file_response(app_state, file, file_key, user_id, private_key)
+}
+
+// 发送者下载自己发送的文件：使用上传时为发送者包装的文件密钥副本，无需共享链接密码
+pub async fn download_sent_file(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<DownloadSentFileDto>,
+) -> Result<axum::response::Response, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let user_id = user.user.id;
+
+    let file = get_sent_file(&app_state, &body.file_id, user_id).await?;
+
+    let private_key = parse_private_key(body.private_key)?;
+
+    let sender_copies = get_sender_copies(&app_state, file.id, user_id).await?;
+    let file_key = select_file_key(sender_copies, private_key.as_ref(), body.key_fingerprint)?;
+
+    file_response(app_state, file, file_key, user_id, private_key)
+}
+
+// 转发文件：为新接收者的每把有效设备公钥重新包装文件密钥并创建共享链接，无需重新上传文件内容
+// 携带发送者私钥时由服务端解开发送者副本后重新包装，否则使用客户端包装好的文件密钥
+pub async fn forward_file(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<ForwardFileDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let user_id = user.user.id;
+
+    let file = get_sent_file(&app_state, &body.file_id, user_id).await?;
+
+    // 整体加密的旧格式将密文绑定到原接收者，无法转发
+    if file.format_version != STREAM_FORMAT_VERSION {
+        return Err(HttpError::bad_request(
+            "This file uses a legacy encryption format and cannot be forwarded, please upload it again".to_string(),
+        ));
+    }
+
+    let target = prepare_share(
+        &app_state,
+        &body.recipient_email,
+        &body.password,
+        &body.expiration_date,
+    )
+    .await?;
+
+    let file_keys = match (body.private_key, body.encrypted_aes_keys) {
+        (Some(private_key), None) => {
+            let private_key = keys::parse_private_key(&private_key)
+                .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+            let sender_copies = get_sender_copies(&app_state, file.id, user_id).await?;
+            let sender_copy = select_file_key(sender_copies, Some(&private_key), None)?;
+            let aes_key = unwrap_key(
+                &sender_copy.encrypted_aes_key,
+                &private_key,
+                cipher::key_wrap_label(sender_copy.key_wrap_version, file.id, user_id),
+            )?;
+
+            let label = cipher::key_wrap_label(KEY_WRAP_VERSION, file.id, target.recipient_id);
+
+            target
+                .recipient_keys
+                .iter()
+                .map(|(key_id, public_key)| Ok((*key_id, wrap_key(&aes_key, public_key, label.clone())?)))
+                .collect::<Result<Vec<_>, HttpError>>()?
+        }
+        (None, Some(encrypted_aes_keys)) => {
+            // 必须为接收者的每把有效设备公钥各提供一份，不能多也不能少
+            if encrypted_aes_keys.len() != target.recipient_keys.len() {
+                return Err(HttpError::bad_request(
+                    "Encrypted AES keys must be provided for every active key of the recipient".to_string(),
+                ));
+            }
+
+            target
+                .recipient_keys
+                .iter()
+                .map(|(key_id, public_key)| {
+                    let wrapped = encrypted_aes_keys
+                        .iter()
+                        .find(|wrapped| wrapped.key_id == key_id.to_string())
+                        .ok_or(HttpError::bad_request(
+                            "Encrypted AES keys must be provided for every active key of the recipient".to_string(),
+                        ))?;
+
+                    let encrypted_aes_key = STANDARD
+                        .decode(&wrapped.encrypted_aes_key)
+                        .map_err(|_| HttpError::bad_request("Invalid encrypted AES key".to_string()))?;
+
+                    // RSA-OAEP 密文的长度与接收者公钥的模数长度相同
+                    if encrypted_aes_key.len() != public_key.size() {
+                        return Err(HttpError::bad_request(
+                            "Encrypted AES key does not match the recipient's public key".to_string(),
+                        ));
+                    }
+
+                    Ok((*key_id, encrypted_aes_key))
+                })
+                .collect::<Result<Vec<_>, HttpError>>()?
+        }
+        _ => {
+            return Err(HttpError::bad_request(
+                "Exactly one of private_key and encrypted_aes_keys is required".to_string(),
+            ))
+        }
+    };
+
+    app_state
+        .db_client
+        .share_file(
+            file.id,
+            target.recipient_id,
+            file_keys,
+            target.hash_password,
+            target.expiration_date,
+        )
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let response = Response {
+        message: "File forwarded successfully".to_string(),
+        status: "success",
+    };
+
+    Ok(Json(response))
+}
+
+// 查询当前用户发送的、已上传完成的文件
+async fn get_sent_file(
+    app_state: &AppState,
+    file_id: &str,
+    user_id: uuid::Uuid,
+) -> Result<File, HttpError> {
+    let file_id = uuid::Uuid::parse_str(file_id)
+        .map_err(|_| HttpError::bad_request("Invalid file id".to_string()))?;
+
+    let file_result = app_state
+        .db_client
+        .get_file(file_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    // 不属于当前用户的文件与不存在的文件返回相同的错误
+    file_result
+        .filter(|file| file.user_id == Some(user_id))
+        .filter(|file| file.format_version != STREAM_FORMAT_VERSION || file.segment_count.is_some())
+        .ok_or(HttpError::new(
+            "The requested file does not exist",
+            StatusCode::NOT_FOUND,
+        ))
+}
+
+// 获取为发送者包装的文件密钥副本，发送者上传时没有公钥或文件为旧格式时不存在
+async fn get_sender_copies(
+    app_state: &AppState,
+    file_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+) -> Result<Vec<FileKey>, HttpError> {
+    let sender_copies = app_state
+        .db_client
+        .get_file_keys(file_id, user_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    if sender_copies.is_empty() {
+        return Err(HttpError::new(
+            "No sender copy of the file key is available for this file",
+            StatusCode::NOT_FOUND,
+        ));
+    }
+
+    Ok(sender_copies)
+}
+
+// 解析请求中可选的 PEM 格式私钥
+fn parse_private_key(private_key: Option<String>) -> Result<Option<RsaPrivateKey>, HttpError> {
+    private_key
+        .map(|private_key| keys::parse_private_key(&private_key))
+        .transpose()
+        .map_err(|e| HttpError::bad_request(e.to_string()))
+}
+
+// 从为当前用户各把设备公钥包装的文件密钥中选出本次使用的一份
+// 携带私钥时按私钥对应的公钥指纹选择，否则按请求中的公钥指纹选择，只有一份时可以省略指纹
+fn select_file_key(
+    file_keys: Vec<FileKey>,
+    private_key: Option<&RsaPrivateKey>,
+    key_fingerprint: Option<String>,
+) -> Result<FileKey, HttpError> {
+    let key_fingerprint = match private_key {
+        Some(private_key) => Some(
+            keys::fingerprint(&RsaPublicKey::from(private_key))
+                .map_err(|e| HttpError::bad_request(e.to_string()))?,
+        ),
+        None => key_fingerprint.map(|fingerprint| fingerprint.to_lowercase()),
+    };
+
+    match key_fingerprint {
+        Some(key_fingerprint) => file_keys
+            .into_iter()
+            .find(|file_key| file_key.fingerprint == key_fingerprint)
+            .ok_or(HttpError::bad_request(
+                "The file key is not available for this key, please use another device key".to_string(),
+            )),
+        None if file_keys.len() == 1 => Ok(file_keys.into_iter().next().unwrap()),
+        None => Err(HttpError::bad_request(
+            "The file key is available for several of your keys, please specify key_fingerprint".to_string(),
+        )),
+    }
+}
+
+// 构造文件下载响应
+// 携带私钥时返回解密后的文件，否则返回密文，并通过响应头返回加密后的 AES 密钥、IV、加密算法和附加认证数据
+fn file_response(
+    app_state: Arc<AppState>,
+    file: File,
+    file_key: FileKey,
+    user_id: uuid::Uuid,
+    private_key: Option<RsaPrivateKey>,
+) -> Result<axum::response::Response, HttpError> {
+    let cipher_suite = file
+        .cipher_suite
+        .parse::<CipherSuite>()
+        .map_err(HttpError::server_error)?;
+
+    let is_stream = file.format_version == STREAM_FORMAT_VERSION;
+
+    // 流式格式的附加认证数据只绑定文件；整体加密的格式还绑定接收者，即当前用户
+    let aad = if is_stream {
+        cipher::stream_associated_data(cipher_suite, file.id)
+    } else {
+        cipher::associated_data(cipher_suite, file.id, user_id)
+    };
+
+    // 文件密钥的 OAEP 标签绑定文件和持有者，即当前用户
+    let key_wrap_label = cipher::key_wrap_label(file_key.key_wrap_version, file.id, user_id);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/octet-stream"),
+    );
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        content_disposition(&file.file_name),
+    );
+
+    // 返回密文时，由客户端自行解密
+    if private_key.is_none() {
+        headers.insert(
+            HeaderName::from_static(ENCRYPTED_AES_KEY_HEADER),
+            HeaderValue::from_str(&STANDARD.encode(&file_key.encrypted_aes_key))
+                .map_err(|e| HttpError::server_error(e.to_string()))?,
+        );
+        headers.insert(
+            HeaderName::from_static(KEY_FINGERPRINT_HEADER),
+            HeaderValue::from_str(&file_key.fingerprint)
+                .map_err(|e| HttpError::server_error(e.to_string()))?,
+        );
+        if let Some(key_wrap_label) = &key_wrap_label {
+            headers.insert(
+                HeaderName::from_static(KEY_WRAP_LABEL_HEADER),
+                HeaderValue::from_str(key_wrap_label)
+                    .map_err(|e| HttpError::server_error(e.to_string()))?,
+            );
+        }
+        headers.insert(
+            HeaderName::from_static(ENCRYPTION_IV_HEADER),
+            HeaderValue::from_str(&STANDARD.encode(&file.iv))
+                .map_err(|e| HttpError::server_error(e.to_string()))?,
+        );
+        headers.insert(
+            HeaderName::from_static(CIPHER_SUITE_HEADER),
+            HeaderValue::from_static(cipher_suite.as_str()),
+        );
+        headers.insert(
+            HeaderName::from_static(FORMAT_VERSION_HEADER),
+            HeaderValue::from(file.format_version),
+        );
+        // 旧版 CBC 文件没有附加认证数据
+        if !cipher_suite.is_deprecated() {
+            headers.insert(
+                HeaderName::from_static(ENCRYPTION_AAD_HEADER),
+                HeaderValue::from_str(&STANDARD.encode(&aad))
+                    .map_err(|e| HttpError::server_error(e.to_string()))?,
+            );
+        }
+        if let Some(segment_size) = file.segment_size {
+            headers.insert(
+                HeaderName::from_static(SEGMENT_SIZE_HEADER),
+                HeaderValue::from(segment_size),
+            );
+        }
+    }
+
+    // 在发送响应头之前解开文件密钥，私钥不匹配时直接返回 400
+    let aes_key = private_key
+        .map(|private_key| unwrap_key(&file_key.encrypted_aes_key, &private_key, key_wrap_label))
+        .transpose()?;
+
+    if is_stream {
+        return stream_file(app_state, file, aes_key, cipher_suite, aad, headers);
+    }
+
+    let body = match aes_key {
+        Some(aes_key) => decrypt_file(
+            &aes_key,
+            &file.encrypted_file,
+            &file.iv,
+            cipher_suite,
+            &aad,
+        )?,
+        None => file.encrypted_file,
+    };
+
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+
+    Ok((headers, body).into_response())
+}
+
+// 以流的形式返回流式格式的文件，逐段读取，携带私钥时逐段解密
+fn stream_file(
+    app_state: Arc<AppState>,
+    file: File,
+    aes_key: Option<Vec<u8>>,
+    cipher_suite: CipherSuite,
+    aad: Vec<u8>,
+    mut headers: HeaderMap,
+) -> Result<axum::response::Response, HttpError> {
+    // 尚未上传完成的文件没有分段数量
+    let segment_count = file.segment_count.filter(|count| *count > 0).ok_or(HttpError::new(
+        "The requested file does not exist",
+        StatusCode::NOT_FOUND,
+    ))?;
+
+    let (decryptor, content_length) = match aes_key {
+        Some(aes_key) => {
+            let decryptor = StreamDecryptor::new(&aes_key, &file.iv, cipher_suite, aad)?;
+            (Some(decryptor), file.file_size as u64)
+        }
+        // 每个密文分段比明文多一个认证标签
+        None => (
+            None,
+            file.file_size as u64 + segment_count as u64 * AEAD_TAG_LEN as u64,
+        ),
+    };
+
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(content_length));
+
+    let reader = SegmentReader {
+        app_state,
+        file_id: file.id,
+        next_seq: 0,
+        segment_count,
+        decryptor,
+    };
+
+    let file_id = file.id;
+    let body = Body::from_stream(
+        stream::try_unfold(reader, SegmentReader::read_next).inspect_err(move |e| {
+            eprintln!("Failed to stream file {}: {}", file_id, e);
+        }),
+    );
+
+    Ok((headers, body).into_response())
+}
+
+// 按顺序从数据库读取流式格式的分段
+struct SegmentReader {
+    app_state: Arc<AppState>,            // 应用状态，用于访问数据库
+    file_id: uuid::Uuid,                 // 文件 ID
+    next_seq: i32,                       // 下一个要读取的分段序号
+    segment_count: i32,                  // 分段数量
+    decryptor: Option<StreamDecryptor>,  // 在服务端解密时使用的解密器
+}
+
+impl SegmentReader {
+    // 读取下一个分段，全部读取完毕时返回 None
+    // 响应头已经发出，出错时中断连接，客户端收到的内容会少于 Content-Length
+    async fn read_next(mut self) -> Result<Option<(Vec<u8>, Self)>, HttpError> {
+        if self.next_seq >= self.segment_count {
+            return Ok(None);
+        }
+
+        let seq = self.next_seq;
+        let segment = self
+            .app_state
+            .db_client
+            .get_file_segment(self.file_id, seq)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?
+            .ok_or_else(|| {
+                HttpError::server_error(format!("Segment {} of file {} is missing", seq, self.file_id))
+            })?;
+
+        self.next_seq += 1;
+
+        let data = match self.decryptor.take() {
+            None => segment,
+            // 最后一段必须通过结束标记的校验
+            Some(decryptor) if self.next_seq == self.segment_count => {
+                decryptor.decrypt_last(&segment)?
+            }
+            Some(mut decryptor) => {
+                let data = decryptor.decrypt_next(&segment)?;
+                self.decryptor = Some(decryptor);
+                data
+            }
+        };
+
+        Ok(Some((data, self)))
+    }
+}
+
+// 构造 Content-Disposition 响应头
+// filename 参数只保留可打印的 ASCII 字符，完整的文件名通过 RFC 5987 的 filename* 参数传递
+fn content_disposition(file_name: &str) -> HeaderValue {
+    let ascii_name: String = file_name
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            c if c.is_ascii_graphic() || c == ' ' => c,
+            _ => '_',
+        })
+        .collect();
+
+    let encoded_name: String = file_name
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+
+    HeaderValue::from_str(&format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        ascii_name, encoded_name
+    ))
+    .unwrap_or_else(|_| HeaderValue::from_static("attachment"))
+}
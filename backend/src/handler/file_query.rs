@@ -0,0 +1,116 @@
+// 引入标准库中的 Arc，用于共享应用状态
+use std::sync::Arc;
+
+// 引入 axum 的路由、查询参数和扩展类型
+use axum::{
+    extract::Query,
+    response::IntoResponse,
+    routing::get,
+    Extension, Json, Router,
+};
+// 引入 validator 库，用于请求数据校验
+use validator::Validate;
+
+use crate::{
+    db::UserExt,
+    dtos::{
+        RequestQueryDto, UserReceiveFileDto, UserReceiveFileListResponseDto, UserSendFileDto,
+        UserSendFileListResponseDto,
+    },
+    error::HttpError,
+    middleware::JWTAuthMiddleware,
+    AppState,
+};
+
+// 未指定分页参数时使用的默认值
+const DEFAULT_PAGE: usize = 1;
+const DEFAULT_LIMIT: usize = 10;
+
+/// 创建文件列表相关的路由
+///
+/// 所有接口都需要经过认证中间件，在 `routes.rs` 中与文件路由合并后统一挂载。
+///
+/// # 返回
+/// 返回包含已发送和已接收文件列表接口的 `Router`。
+pub fn get_file_list_handler() -> Router {
+    Router::new()
+        .route("/sent", get(get_user_shared_files))
+        .route("/received", get(get_receive_shared_files))
+}
+
+// 获取当前用户发送的文件列表
+pub async fn get_user_shared_files(
+    Query(query_params): Query<RequestQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    // page 必须在 1 和 10000 之间，避免计算偏移量时溢出
+    query_params
+        .validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let user_id = user.user.id;
+
+    let page = query_params.page.unwrap_or(DEFAULT_PAGE);
+    let limit = query_params.limit.unwrap_or(DEFAULT_LIMIT);
+
+    let (shared_files, total_count) = app_state
+        .db_client
+        .get_sent_files(user_id, page, limit)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let filtered_send_files = UserSendFileDto::filter_send_user_files(&shared_files);
+
+    let response = UserSendFileListResponseDto {
+        status: "success".to_string(),
+        files: filtered_send_files,
+        results: total_count,
+        page,
+        limit,
+        total_pages: total_pages(total_count, limit),
+    };
+
+    Ok(Json(response))
+}
+
+// 获取当前用户接收的文件列表
+pub async fn get_receive_shared_files(
+    Query(query_params): Query<RequestQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    // page 必须在 1 和 10000 之间，避免计算偏移量时溢出
+    query_params
+        .validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let user_id = user.user.id;
+
+    let page = query_params.page.unwrap_or(DEFAULT_PAGE);
+    let limit = query_params.limit.unwrap_or(DEFAULT_LIMIT);
+
+    let (receive_files, total_count) = app_state
+        .db_client
+        .get_receive_files(user_id, page, limit)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let filtered_receive_files = UserReceiveFileDto::filter_receive_user_files(&receive_files);
+
+    let response = UserReceiveFileListResponseDto {
+        status: "success".to_string(),
+        files: filtered_receive_files,
+        results: total_count,
+        page,
+        limit,
+        total_pages: total_pages(total_count, limit),
+    };
+
+    Ok(Json(response))
+}
+
+// 根据总记录数和每页条目数计算总页数
+fn total_pages(total_count: i64, limit: usize) -> i64 {
+    (total_count + limit as i64 - 1) / limit as i64
+}
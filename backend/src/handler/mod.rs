@@ -0,0 +1,5 @@
+pub mod auth;
+pub mod file;
+pub mod file_query;
+pub mod oidc;
+pub mod user;
@@ -0,0 +1,167 @@
+// 引入标准库中的 Arc，用于共享应用状态
+use std::sync::Arc;
+
+// 引入 axum 的路由、查询参数、响应和扩展类型
+use axum::{
+    extract::Query,
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect},
+    routing::get,
+    Extension, Router,
+};
+// 引入 axum-extra 的 Cookie 类型
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+// 引入 chrono 库，用于计算授权请求的过期时间
+use chrono::{Duration, Utc};
+
+use crate::{
+    auth_provider,
+    db::UserExt,
+    dtos::OidcCallbackQueryDto,
+    error::{ErrorMessage, HttpError},
+    handler::auth::{issue_tokens, token_cookies},
+    oidc::OidcProvider,
+    utils::{password, token},
+    AppState,
+};
+
+// 保存 state 参数的 Cookie 名称和路径，只在单点登录接口下发送
+const OIDC_STATE_COOKIE: &str = "oidc_state";
+const OIDC_STATE_COOKIE_PATH: &str = "/auth/oidc";
+
+// 授权请求的有效期（分钟），用户需要在此时间内完成身份提供方的登录
+const OIDC_AUTH_REQUEST_MAXAGE: i64 = 10;
+
+/// 创建 OIDC 单点登录相关的路由
+///
+/// # 返回
+/// 返回包含跳转登录和回调接口的 `Router`。
+pub fn oidc_handler() -> Router {
+    Router::new()
+        .route("/oidc/login", get(oidc_login))
+        .route("/oidc/callback", get(oidc_callback))
+}
+
+// 获取已配置的身份提供方，未配置时返回 404
+fn get_provider(app_state: &AppState) -> Result<Arc<OidcProvider>, HttpError> {
+    app_state.oidc.clone().ok_or_else(|| {
+        HttpError::new(
+            ErrorMessage::OidcNotConfigured.to_string(),
+            StatusCode::NOT_FOUND,
+        )
+    })
+}
+
+// 发起单点登录：保存 PKCE 校验码和 nonce，并重定向到身份提供方
+pub async fn oidc_login(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, HttpError> {
+    let provider = get_provider(&app_state)?;
+
+    let request = provider.authorize().await.map_err(|e| {
+        eprintln!("Failed to start OIDC login: {}", e);
+        HttpError::server_error(ErrorMessage::OidcLoginFailed.to_string())
+    })?;
+
+    // 服务端只保存 state 的摘要，校验码和 nonce 不会出现在浏览器中
+    let expires_at = Utc::now() + Duration::minutes(OIDC_AUTH_REQUEST_MAXAGE);
+
+    app_state
+        .db_client
+        .create_oidc_auth_request(
+            token::hash_token(&request.state),
+            request.nonce,
+            request.pkce_verifier,
+            expires_at,
+        )
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    // 将 state 绑定到当前浏览器，防止攻击者诱导用户登录攻击者的账户
+    // 使用 Lax 使 Cookie 在身份提供方重定向回来时仍会发送
+    let state_cookie = Cookie::build((OIDC_STATE_COOKIE, request.state))
+        .path(OIDC_STATE_COOKIE_PATH)
+        .max_age(time::Duration::minutes(OIDC_AUTH_REQUEST_MAXAGE))
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .build();
+
+    Ok((
+        [(header::SET_COOKIE, state_cookie.to_string())],
+        Redirect::to(&request.url),
+    ))
+}
+
+// 单点登录回调：校验 state，换取并验证 ID Token，然后登录对应的本地账户
+// 身份提供方已完成用户认证，这里不再要求本地的两步验证
+pub async fn oidc_callback(
+    cookie_jar: CookieJar,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Query(params): Query<OidcCallbackQueryDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    let provider = get_provider(&app_state)?;
+
+    // 用户在身份提供方拒绝授权或登录失败
+    if let Some(error) = params.error {
+        eprintln!(
+            "OIDC provider returned an error: {} {}",
+            error,
+            params.error_description.unwrap_or_default()
+        );
+        return Err(HttpError::unauthorized(
+            ErrorMessage::OidcLoginFailed.to_string(),
+        ));
+    }
+
+    let invalid_state = || HttpError::bad_request(ErrorMessage::InvalidOidcState.to_string());
+
+    let (Some(code), Some(state)) = (params.code, params.state) else {
+        return Err(invalid_state());
+    };
+
+    // 回调中的 state 必须与发起登录的浏览器中保存的一致
+    let cookie_state = cookie_jar
+        .get(OIDC_STATE_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .ok_or_else(invalid_state)?;
+
+    if !password::constant_time_eq(cookie_state.as_bytes(), state.as_bytes()) {
+        return Err(invalid_state());
+    }
+
+    // 授权请求只能使用一次
+    let auth_request = app_state
+        .db_client
+        .consume_oidc_auth_request(&token::hash_token(&state))
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or_else(invalid_state)?;
+
+    let identity = provider
+        .exchange(code, auth_request.pkce_verifier, auth_request.nonce)
+        .await
+        .map_err(|e| {
+            eprintln!("OIDC login failed: {}", e);
+            HttpError::unauthorized(ErrorMessage::OidcLoginFailed.to_string())
+        })?;
+
+    let user = auth_provider::sync_external_user(&app_state.db_client, &identity).await?;
+
+    let (access_token, refresh_token) = issue_tokens(&app_state, &user, None).await?;
+
+    // 写入登录 Cookie 并清除 state Cookie，然后回到前端
+    let mut headers = token_cookies(&app_state, &access_token, &refresh_token);
+
+    let clear_state_cookie = Cookie::build((OIDC_STATE_COOKIE, ""))
+        .path(OIDC_STATE_COOKIE_PATH)
+        .max_age(time::Duration::ZERO)
+        .http_only(true)
+        .build();
+
+    headers.append(
+        header::SET_COOKIE,
+        clear_state_cookie.to_string().parse().unwrap(),
+    );
+
+    Ok((headers, Redirect::to(&app_state.env.app_url)))
+}
@@ -0,0 +1,842 @@
+// 引入标准库中的 Arc 和 SocketAddr，用于共享应用状态和获取客户端地址
+use std::{net::SocketAddr, sync::Arc};
+
+// 引入 axum 的路由、响应和扩展类型
+use axum::{
+    extract::{ConnectInfo, Path, Query},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{delete, get, post, put},
+    Extension, Json, Router,
+};
+// 引入 base64 库，用于传递加密后的挑战和文件密钥
+use base64::{engine::general_purpose::STANDARD, Engine};
+// 引入 chrono 库，用于计算挑战的过期时间
+use chrono::{Duration, Utc};
+// 引入 rsa 库，用于获取公钥的模数长度
+use rsa::traits::PublicKeyParts;
+// 引入 validator 库，用于请求数据校验
+use validator::Validate;
+
+use crate::{
+    auth_provider::AuthOutcome,
+    db::UserExt,
+    dtos::{
+        CreatePersonalAccessTokenDto, DisableMfaDto, EmailListResponseDto, EnableMfaDto, FileKeyDto, FilterEmailDto,
+        FilterUserDto, KeyRotationChallengeResponseDto, NameUpdateDto, PersonalAccessTokenCreatedResponseDto, PersonalAccessTokenDto,
+        PersonalAccessTokenListResponseDto, PublicKeyDto, PublicKeyResponseDto,
+        RecipientKeysQueryDto, RecoveryCodesResponseDto, Response, RotateUserKeyDto, SearchQueryByEmailDTO,
+        TotpSetupResponseDto, UserData, UserKeyDto, UserKeyListResponseDto, UserPasswordUpdateDto,
+        UserResponseDto,
+    },
+    error::{ErrorMessage, HttpError},
+    handler::auth::{ensure_login_allowed, record_login_failure, verify_second_factor},
+    middleware::JWTAuthMiddleware,
+    models::{KeyRotationOutcome, User, UserKey},
+    utils::{
+        access_token::{self, Scope},
+        cipher, encrypt, keys,
+        login_throttle::ThrottleScope,
+        password, token, totp,
+    },
+    AppState,
+};
+
+// 邮箱搜索接口单次返回的最大结果数
+const MAX_SEARCH_RESULTS: i64 = 10;
+
+// 每个用户最多同时拥有的有效设备公钥数量，上传文件时需为每一把公钥包装文件密钥
+const MAX_ACTIVE_USER_KEYS: usize = 10;
+
+// 未提供设备名称时使用的默认名称
+const DEFAULT_KEY_LABEL: &str = "Default";
+
+// 公钥轮换挑战的有效期（分钟）
+const KEY_ROTATION_CHALLENGE_MAXAGE: i64 = 10;
+
+/// 创建用户相关的路由
+///
+/// 所有接口都需要经过认证中间件，在 `routes.rs` 中统一挂载。
+///
+/// # 返回
+/// 返回包含个人信息、修改用户名、修改密码、设备公钥管理和轮换、查询接收者公钥、搜索接收者、退出所有设备、个人访问令牌管理和两步验证管理接口的 `Router`。
+pub fn users_handler() -> Router {
+    Router::new()
+        .route("/me", get(get_me))
+        .route("/name", put(update_user_name))
+        .route("/password", put(update_user_password))
+        .route("/keys", get(get_user_keys).post(save_user_key))
+        .route("/keys/:id", delete(revoke_user_key))
+        .route("/keys/:id/rotation/challenge", post(start_key_rotation))
+        .route("/keys/:id/rotation", post(rotate_user_key))
+        .route("/public-keys", get(get_recipient_keys))
+        .route("/search", get(search_by_email))
+        .route("/logout-all", post(logout_all))
+        .route(
+            "/tokens",
+            get(get_personal_access_tokens).post(create_personal_access_token),
+        )
+        .route("/tokens/:id", delete(revoke_personal_access_token))
+        .route("/mfa/setup", post(setup_mfa))
+        .route("/mfa/enable", post(enable_mfa))
+        .route("/mfa/disable", post(disable_mfa))
+}
+
+// 获取当前登录用户的信息
+pub async fn get_me(
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let filtered_user = FilterUserDto::filter_user(&user.user);
+
+    let response_data = UserResponseDto {
+        status: "success".to_string(),
+        data: UserData {
+            user: filtered_user,
+        },
+    };
+
+    Ok(Json(response_data))
+}
+
+// 修改当前登录用户的用户名
+pub async fn update_user_name(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<NameUpdateDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let user = &user.user;
+
+    let result = app_state
+        .db_client
+        .update_user_name(user.id, &body.name)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let filtered_user = FilterUserDto::filter_user(&result);
+
+    let response = UserResponseDto {
+        status: "success".to_string(),
+        data: UserData {
+            user: filtered_user,
+        },
+    };
+
+    Ok(Json(response))
+}
+
+// 修改当前登录用户的密码，修改前需校验旧密码
+pub async fn update_user_password(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<UserPasswordUpdateDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let user = &user.user;
+
+    // 重新读取用户，确保使用数据库中最新的密码哈希
+    let result = app_state
+        .db_client
+        .get_user(Some(user.id), None, None)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let user = result.ok_or(HttpError::unauthorized(
+        ErrorMessage::InvalidToken.to_string(),
+    ))?;
+
+    // 密码由 LDAP 等外部目录管理的账户不修改本地密码
+    if app_state
+        .auth_provider
+        .manages_password(&app_state.db_client, user.id)
+        .await?
+    {
+        return Err(HttpError::forbidden(
+            ErrorMessage::PasswordManagedByDirectory.to_string(),
+        ));
+    }
+
+    let password_match = password::compare(&body.old_password, &user.password)
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    if !password_match {
+        return Err(HttpError::bad_request(
+            "Old password is incorrect".to_string(),
+        ));
+    }
+
+    let hash_password = password::hash(&body.new_password)
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    app_state
+        .db_client
+        .update_user_password(user.id, hash_password)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    // 修改密码后所有设备（包括当前设备）都需要重新登录
+    let response = Response {
+        message: "Password updated Successfully, please log in again".to_string(),
+        status: "success",
+    };
+
+    Ok(Json(response))
+}
+
+// 为当前登录用户添加一把设备公钥，校验格式和长度后保存，并返回公钥指纹
+pub async fn save_user_key(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<PublicKeyDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let user = &user.user;
+
+    ensure_email_verified(user)?;
+
+    let public_key = keys::parse_public_key(&body.public_key)
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    // 统一以 SPKI PEM 格式存储公钥
+    let public_key_pem = keys::to_pem(&public_key)
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let fingerprint = keys::fingerprint(&public_key)
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let active_keys = app_state
+        .db_client
+        .get_active_user_keys(user.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    if active_keys.len() >= MAX_ACTIVE_USER_KEYS {
+        return Err(HttpError::bad_request(format!(
+            "At most {} active keys are allowed, please revoke an unused key first",
+            MAX_ACTIVE_USER_KEYS
+        )));
+    }
+
+    let label = body.label.unwrap_or_else(|| DEFAULT_KEY_LABEL.to_string());
+
+    let user_key = app_state
+        .db_client
+        .save_user_key(user.id, label, keys::KEY_ALGORITHM, public_key_pem, fingerprint.clone())
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or(HttpError::unique_constraint_violation(
+            "This public key has already been added".to_string(),
+        ))?;
+
+    let response = PublicKeyResponseDto {
+        status: "success".to_string(),
+        fingerprint,
+        data: UserKeyDto::filter_key(&user_key),
+    };
+
+    Ok(Json(response))
+}
+
+// 获取当前登录用户的全部设备公钥，包括已吊销的公钥
+pub async fn get_user_keys(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let user_keys = app_state
+        .db_client
+        .get_user_keys(user.user.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let response = UserKeyListResponseDto {
+        status: "success".to_string(),
+        keys: UserKeyDto::filter_keys(&user_keys),
+    };
+
+    Ok(Json(response))
+}
+
+// 吊销当前用户的设备公钥，之后上传的文件不再为其包装密钥
+pub async fn revoke_user_key(
+    Path(key_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let revoked = app_state
+        .db_client
+        .revoke_user_key(user.user.id, key_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    if !revoked {
+        return Err(HttpError::new(
+            "Public key not found".to_string(),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+
+    let response = Response {
+        message: "Public key revoked".to_string(),
+        status: "success",
+    };
+
+    Ok(Json(response))
+}
+
+// 开始轮换设备公钥：返回使用旧公钥加密的挑战，以及使用旧公钥包装、对应共享仍未过期的文件密钥
+// 客户端用旧私钥解开挑战和文件密钥，再用新公钥重新包装后调用完成轮换接口
+pub async fn start_key_rotation(
+    Path(key_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let user_id = user.user.id;
+
+    let old_key = get_active_user_key(&app_state, user_id, key_id).await?;
+
+    let public_key = keys::parse_public_key(&old_key.public_key)
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    // 挑战与文件密钥使用相同的方式加密，只有持有旧私钥才能解开
+    let challenge = token::generate_random_token();
+    let encrypted_challenge = encrypt::wrap_key(challenge.as_bytes(), &public_key, None)?;
+
+    let expires_at = Utc::now() + Duration::minutes(KEY_ROTATION_CHALLENGE_MAXAGE);
+
+    app_state
+        .db_client
+        .create_key_rotation_challenge(user_id, key_id, token::hash_token(&challenge), expires_at)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let file_keys = app_state
+        .db_client
+        .get_pending_file_keys(key_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let response = KeyRotationChallengeResponseDto {
+        status: "success".to_string(),
+        challenge: STANDARD.encode(encrypted_challenge),
+        expires_at,
+        file_keys: file_keys
+            .iter()
+            .map(|file_key| FileKeyDto {
+                file_id: file_key.file_id.to_string(),
+                encrypted_aes_key: STANDARD.encode(&file_key.encrypted_aes_key),
+                key_wrap_label: cipher::key_wrap_label(
+                    file_key.key_wrap_version,
+                    file_key.file_id,
+                    user_id,
+                ),
+            })
+            .collect(),
+    };
+
+    Ok(Json(response))
+}
+
+// 完成设备公钥轮换：校验挑战后添加新公钥，原子地替换重新包装的文件密钥，并吊销旧公钥
+pub async fn rotate_user_key(
+    Path(key_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<RotateUserKeyDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let user_id = user.user.id;
+
+    let old_key = get_active_user_key(&app_state, user_id, key_id).await?;
+
+    let public_key = keys::parse_public_key(&body.public_key)
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    // 统一以 SPKI PEM 格式存储公钥
+    let public_key_pem = keys::to_pem(&public_key)
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let fingerprint = keys::fingerprint(&public_key)
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let file_keys = body
+        .file_keys
+        .iter()
+        .map(|file_key| {
+            let file_id = uuid::Uuid::parse_str(&file_key.file_id)
+                .map_err(|_| HttpError::bad_request("Invalid file id".to_string()))?;
+
+            let encrypted_aes_key = STANDARD
+                .decode(&file_key.encrypted_aes_key)
+                .map_err(|_| HttpError::bad_request("Invalid encrypted AES key".to_string()))?;
+
+            // RSA-OAEP 密文的长度与新公钥的模数长度相同
+            if encrypted_aes_key.len() != public_key.size() {
+                return Err(HttpError::bad_request(
+                    "Encrypted AES key does not match the new public key".to_string(),
+                ));
+            }
+
+            Ok((file_id, encrypted_aes_key))
+        })
+        .collect::<Result<Vec<_>, HttpError>>()?;
+
+    let label = body.label.unwrap_or(old_key.label);
+
+    let outcome = app_state
+        .db_client
+        .rotate_user_key(
+            user_id,
+            key_id,
+            &token::hash_token(&body.challenge),
+            label,
+            keys::KEY_ALGORITHM,
+            public_key_pem,
+            fingerprint.clone(),
+            file_keys,
+        )
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let new_key = match outcome {
+        KeyRotationOutcome::Rotated(new_key) => new_key,
+        KeyRotationOutcome::InvalidChallenge => {
+            return Err(HttpError::bad_request(
+                "Invalid or expired rotation challenge".to_string(),
+            ))
+        }
+        KeyRotationOutcome::DuplicateKey => {
+            return Err(HttpError::unique_constraint_violation(
+                "This public key has already been added".to_string(),
+            ))
+        }
+        KeyRotationOutcome::StaleFileKeys => {
+            return Err(HttpError::new(
+                "The pending file keys have changed, please start the rotation again".to_string(),
+                StatusCode::CONFLICT,
+            ))
+        }
+    };
+
+    let response = PublicKeyResponseDto {
+        status: "success".to_string(),
+        fingerprint,
+        data: UserKeyDto::filter_key(&new_key),
+    };
+
+    Ok(Json(response))
+}
+
+// 邮箱未验证的账户可能由他人抢先注册，不允许添加设备公钥或创建个人访问令牌，
+// 避免抢注者在真正的邮箱所有者接管账户后仍能解密文件或访问接口
+fn ensure_email_verified(user: &User) -> Result<(), HttpError> {
+    if user.email_verified_at.is_none() {
+        return Err(HttpError::forbidden(
+            ErrorMessage::EmailNotVerified.to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+// 查询当前用户未吊销的某把设备公钥
+async fn get_active_user_key(
+    app_state: &AppState,
+    user_id: uuid::Uuid,
+    key_id: uuid::Uuid,
+) -> Result<UserKey, HttpError> {
+    let user_keys = app_state
+        .db_client
+        .get_active_user_keys(user_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    user_keys
+        .into_iter()
+        .find(|user_key| user_key.id == key_id)
+        .ok_or(HttpError::new(
+            "Public key not found".to_string(),
+            StatusCode::NOT_FOUND,
+        ))
+}
+
+// 查询接收者的有效设备公钥，供客户端转发文件时自行包装文件密钥
+pub async fn get_recipient_keys(
+    Query(params): Query<RecipientKeysQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    params.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    // 与搜索接口共用限流，防止批量探测用户
+    if !app_state.search_limiter.check(&user.user.id.to_string()) {
+        return Err(HttpError::too_many_requests(
+            "Too many search requests, please try again later".to_string(),
+        ));
+    }
+
+    let recipient = app_state
+        .db_client
+        .get_user(None, None, Some(&params.email))
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    // 邮箱未验证的用户与不存在的用户返回相同的结果
+    let user_keys = match recipient {
+        Some(recipient) if recipient.email_verified_at.is_some() => app_state
+            .db_client
+            .get_active_user_keys(recipient.id)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?,
+        _ => Vec::new(),
+    };
+
+    let response = UserKeyListResponseDto {
+        status: "success".to_string(),
+        keys: UserKeyDto::filter_keys(&user_keys),
+    };
+
+    Ok(Json(response))
+}
+
+// 按邮箱前缀搜索可以接收文件的用户（已上传公钥）
+pub async fn search_by_email(
+    Query(params): Query<SearchQueryByEmailDTO>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    params.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let user_id = user.user.id;
+
+    // 按用户限流，防止通过搜索接口批量获取用户邮箱
+    if !app_state.search_limiter.check(&user_id.to_string()) {
+        return Err(HttpError::too_many_requests(
+            "Too many search requests, please try again later".to_string(),
+        ));
+    }
+
+    let users = app_state
+        .db_client
+        .search_by_email(user_id, params.query, MAX_SEARCH_RESULTS)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let filtered_email = FilterEmailDto::filter_emails(&users);
+
+    let response_data = EmailListResponseDto {
+        status: "success".to_string(),
+        emails: filtered_email,
+    };
+
+    Ok(Json(response_data))
+}
+
+// 退出所有设备：使当前用户之前签发的全部令牌（包括个人访问令牌）和会话失效
+pub async fn logout_all(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    app_state
+        .db_client
+        .invalidate_user_tokens(user.user.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let response = Response {
+        message: "Logged out from all devices".to_string(),
+        status: "success",
+    };
+
+    Ok(Json(response))
+}
+
+// 创建个人访问令牌，令牌明文只在本次响应中返回
+pub async fn create_personal_access_token(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<CreatePersonalAccessTokenDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    ensure_email_verified(&user.user)?;
+
+    // 校验并去重权限范围
+    let mut scopes: Vec<Scope> = Vec::new();
+    for scope in &body.scopes {
+        let scope: Scope = scope.parse().map_err(HttpError::bad_request)?;
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+
+    let expires_at = chrono::Utc::now() + chrono::Duration::days(body.expires_in_days);
+
+    let (token, token_hash, token_prefix) = access_token::generate();
+
+    let result = app_state
+        .db_client
+        .create_personal_access_token(
+            user.user.id,
+            body.name,
+            token_hash,
+            token_prefix,
+            scopes.iter().map(|scope| scope.to_string()).collect(),
+            expires_at,
+        )
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let response = PersonalAccessTokenCreatedResponseDto {
+        status: "success".to_string(),
+        token,
+        data: PersonalAccessTokenDto::filter_token(&result),
+    };
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+// 获取当前用户的全部个人访问令牌（不包含令牌明文）
+pub async fn get_personal_access_tokens(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let tokens = app_state
+        .db_client
+        .get_personal_access_tokens(user.user.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let response = PersonalAccessTokenListResponseDto {
+        status: "success".to_string(),
+        tokens: PersonalAccessTokenDto::filter_tokens(&tokens),
+    };
+
+    Ok(Json(response))
+}
+
+// 吊销当前用户的个人访问令牌
+pub async fn revoke_personal_access_token(
+    Path(token_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let revoked = app_state
+        .db_client
+        .revoke_personal_access_token(user.user.id, token_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    if !revoked {
+        return Err(HttpError::new(
+            "Access token not found".to_string(),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+
+    let response = Response {
+        message: "Access token revoked".to_string(),
+        status: "success",
+    };
+
+    Ok(Json(response))
+}
+
+// 生成新的 TOTP 密钥和 otpauth URI，需再调用启用接口确认后才会生效
+pub async fn setup_mfa(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let user = &user.user;
+
+    let secret = totp::generate_secret();
+
+    let otpauth_uri = totp::otpauth_uri(&secret, &user.email)
+        .map_err(HttpError::server_error)?;
+
+    let saved = app_state
+        .db_client
+        .save_user_totp_secret(user.id, secret.clone())
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    // 已启用的两步验证不能被新密钥覆盖，需要先关闭
+    if !saved {
+        return Err(HttpError::new(
+            ErrorMessage::MfaAlreadyEnabled.to_string(),
+            StatusCode::CONFLICT,
+        ));
+    }
+
+    let response = TotpSetupResponseDto {
+        status: "success".to_string(),
+        secret,
+        otpauth_uri,
+    };
+
+    Ok(Json(response))
+}
+
+// 使用验证器中的验证码确认并启用两步验证，返回一次性恢复码
+pub async fn enable_mfa(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<EnableMfaDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    let user = &user.user;
+
+    let code = body
+        .code
+        .filter(|code| !code.trim().is_empty())
+        .ok_or_else(|| HttpError::bad_request(ErrorMessage::MfaCodeRequired.to_string()))?;
+
+    if !app_state.mfa_limiter.check(&user.id.to_string()) {
+        return Err(HttpError::too_many_requests(
+            ErrorMessage::TooManyMfaAttempts.to_string(),
+        ));
+    }
+
+    let totp_config = app_state
+        .db_client
+        .get_user_totp(user.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or_else(|| HttpError::bad_request(ErrorMessage::MfaNotSetUp.to_string()))?;
+
+    if totp_config.enabled_at.is_some() {
+        return Err(HttpError::new(
+            ErrorMessage::MfaAlreadyEnabled.to_string(),
+            StatusCode::CONFLICT,
+        ));
+    }
+
+    let step = totp::verify_code(&totp_config.secret, &code, None)
+        .ok_or_else(|| HttpError::bad_request(ErrorMessage::InvalidMfaCode.to_string()))?;
+
+    // 恢复码只保存摘要，明文仅在本次响应中返回
+    let recovery_codes = totp::generate_recovery_codes();
+    let recovery_code_hashes = recovery_codes
+        .iter()
+        .map(|code| token::hash_token(&totp::normalize_recovery_code(code)))
+        .collect();
+
+    app_state
+        .db_client
+        .enable_user_totp(user.id, step, recovery_code_hashes)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let response = RecoveryCodesResponseDto {
+        status: "success".to_string(),
+        recovery_codes,
+    };
+
+    Ok(Json(response))
+}
+
+// 重新确认密码后关闭两步验证，删除密钥和全部恢复码
+// 只有通过单点登录或免密码登录链接创建、没有可用密码的账户使用验证器中的当前验证码或恢复码确认
+pub async fn disable_mfa(
+    ConnectInfo(client_addr): ConnectInfo<SocketAddr>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<DisableMfaDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let user = &user.user;
+
+    let totp_config = app_state
+        .db_client
+        .get_user_totp(user.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .filter(|totp_config| totp_config.enabled_at.is_some())
+        .ok_or_else(|| HttpError::bad_request(ErrorMessage::MfaNotEnabled.to_string()))?;
+
+    // 有本地密码或密码由 LDAP 等外部目录管理的账户必须确认密码，验证码不能代替密码，
+    // 避免会话被盗后仅凭泄露的恢复码关闭两步验证
+    let password_usable = app_state
+        .db_client
+        .has_local_password(user.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        || app_state
+            .auth_provider
+            .manages_password(&app_state.db_client, user.id)
+            .await?;
+
+    if password_usable {
+        let password = body.password.ok_or_else(|| {
+            HttpError::bad_request(ErrorMessage::MfaConfirmationRequired.to_string())
+        })?;
+
+        // 与登录一样按账户和 IP 记录失败次数，锁定期间不再校验密码
+        let account_key = user.email.to_lowercase();
+        let ip_key = client_addr.ip().to_string();
+        let throttle_keys = [
+            (ThrottleScope::Account, account_key.as_str()),
+            (ThrottleScope::Ip, ip_key.as_str()),
+        ];
+
+        ensure_login_allowed(&app_state, &throttle_keys).await?;
+
+        // 通过登录使用的认证后端确认密码，LDAP 账户使用目录中的密码
+        let outcome = app_state
+            .auth_provider
+            .authenticate(&app_state.db_client, &user.email, &password)
+            .await?;
+
+        if !matches!(outcome, AuthOutcome::Authenticated(authenticated) if authenticated.id == user.id) {
+            record_login_failure(&app_state, &throttle_keys).await?;
+
+            return Err(HttpError::bad_request(
+                ErrorMessage::IncorrectPassword.to_string(),
+            ));
+        }
+
+        app_state
+            .db_client
+            .clear_login_throttle(Some(ThrottleScope::Account.as_str()), &account_key)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+    } else {
+        let code = body
+            .code
+            .filter(|code| !code.trim().is_empty())
+            .ok_or_else(|| HttpError::bad_request(ErrorMessage::MfaCodeRequired.to_string()))?;
+
+        // 与登录第二步共用按用户的限流器
+        if !app_state.mfa_limiter.check(&user.id.to_string()) {
+            return Err(HttpError::too_many_requests(
+                ErrorMessage::TooManyMfaAttempts.to_string(),
+            ));
+        }
+
+        verify_second_factor(&app_state, &totp_config, &code).await?;
+    }
+
+    app_state
+        .db_client
+        .disable_user_totp(user.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let response = Response {
+        message: "Two-factor authentication disabled".to_string(),
+        status: "success",
+    };
+
+    Ok(Json(response))
+}
@@ -0,0 +1,164 @@
+// 引入标准库中的格式化 trait 和 Arc
+use std::{fmt, sync::Arc};
+
+// 引入 async-trait 库，用于在 trait 中定义异步方法
+use async_trait::async_trait;
+// 引入 lettre 库，用于构造和发送邮件
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncFileTransport, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use crate::config::Config;
+
+// 使用隐式 TLS 的 SMTP 端口，其余端口使用 STARTTLS
+const SMTPS_PORT: u16 = 465;
+
+// 待发送的纯文本邮件
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,      // 收件人邮箱
+    pub subject: String, // 邮件主题
+    pub body: String,    // 邮件正文（纯文本）
+}
+
+/// 邮件发送接口
+///
+/// 生产环境使用 `SmtpMailer`，开发和测试环境使用 `FileMailer` 将邮件写入本地目录。
+#[async_trait]
+pub trait Mailer: fmt::Debug + Send + Sync {
+    /// 发送邮件
+    ///
+    /// # 参数
+    /// - `email`: 待发送的邮件。
+    ///
+    /// # 返回
+    /// 发送成功返回 `Ok(())`，否则返回错误信息。
+    async fn send(&self, email: Email) -> Result<(), String>;
+}
+
+/// 根据配置创建邮件发送器
+///
+/// 设置了 `SMTP_HOST` 时使用 SMTP，否则将邮件写入 `MAIL_DROP_DIR` 目录。
+///
+/// # 参数
+/// - `config`: 应用配置。
+///
+/// # 返回
+/// 返回邮件发送器或错误信息。
+pub fn from_config(config: &Config) -> Result<Arc<dyn Mailer>, String> {
+    match &config.smtp_host {
+        Some(host) => Ok(Arc::new(SmtpMailer::new(config, host)?)),
+        None => Ok(Arc::new(FileMailer::new(config)?)),
+    }
+}
+
+// 根据发件人和邮件内容构造 MIME 邮件
+fn build_message(from: &Mailbox, email: Email) -> Result<Message, String> {
+    let to: Mailbox = email.to.parse().map_err(|e| format!("Invalid recipient: {}", e))?;
+
+    Message::builder()
+        .from(from.clone())
+        .to(to)
+        .subject(email.subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(email.body)
+        .map_err(|e| e.to_string())
+}
+
+/// 通过 SMTP 服务器发送邮件
+#[derive(Debug)]
+pub struct SmtpMailer {
+    from: Mailbox,                                 // 发件人
+    transport: AsyncSmtpTransport<Tokio1Executor>, // SMTP 连接池
+}
+
+impl SmtpMailer {
+    /// 创建新的 `SmtpMailer` 实例
+    ///
+    /// # 参数
+    /// - `config`: 应用配置，读取发件人、端口和认证信息。
+    /// - `host`: SMTP 服务器地址。
+    pub fn new(config: &Config, host: &str) -> Result<Self, String> {
+        let from = config
+            .mail_from
+            .parse()
+            .map_err(|e| format!("Invalid MAIL_FROM: {}", e))?;
+
+        let builder = if config.smtp_port == SMTPS_PORT {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+        }
+        .map_err(|e| e.to_string())?
+        .port(config.smtp_port);
+
+        let builder = match (&config.smtp_username, &config.smtp_password) {
+            (Some(username), Some(password)) => {
+                builder.credentials(Credentials::new(username.clone(), password.clone()))
+            }
+            _ => builder,
+        };
+
+        Ok(SmtpMailer {
+            from,
+            transport: builder.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: Email) -> Result<(), String> {
+        let message = build_message(&self.from, email)?;
+
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// 将邮件以 `.eml` 文件写入本地目录，用于开发和测试
+#[derive(Debug)]
+pub struct FileMailer {
+    from: Mailbox,                                 // 发件人
+    transport: AsyncFileTransport<Tokio1Executor>, // 文件传输
+}
+
+impl FileMailer {
+    /// 创建新的 `FileMailer` 实例，目录不存在时自动创建
+    ///
+    /// # 参数
+    /// - `config`: 应用配置，读取发件人和邮件保存目录。
+    pub fn new(config: &Config) -> Result<Self, String> {
+        let from = config
+            .mail_from
+            .parse()
+            .map_err(|e| format!("Invalid MAIL_FROM: {}", e))?;
+
+        std::fs::create_dir_all(&config.mail_drop_dir).map_err(|e| {
+            format!("Failed to create mail directory {}: {}", config.mail_drop_dir, e)
+        })?;
+
+        Ok(FileMailer {
+            from,
+            transport: AsyncFileTransport::new(&config.mail_drop_dir),
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> Result<(), String> {
+        let message = build_message(&self.from, email)?;
+
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
@@ -3,8 +3,216 @@ pub mod models;
 pub mod dtos;
 pub mod error;
 pub mod db;
+pub mod routes;
+pub mod utils;
+pub mod middleware;
+pub mod handler;
+pub mod commands;
+pub mod mailer;
+pub mod oidc;
+pub mod auth_provider;
 
+// 引入标准库中的 Arc，用于在多个请求之间共享应用状态
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
-fn main() {
-    println!("Hello, world!");
+// 引入 axum 的 HTTP 相关类型，用于配置跨域请求
+use axum::http::{
+    header::{ACCEPT, AUTHORIZATION, CONTENT_DISPOSITION, CONTENT_TYPE},
+    HeaderName, HeaderValue, Method,
+};
+// 引入配置、数据库客户端和路由
+use auth_provider::AuthProvider;
+use config::Config;
+use db::DBClient;
+use dotenv::dotenv;
+use handler::file::{
+    CIPHER_SUITE_HEADER, ENCRYPTED_AES_KEY_HEADER, ENCRYPTION_AAD_HEADER, ENCRYPTION_IV_HEADER,
+    FORMAT_VERSION_HEADER, KEY_FINGERPRINT_HEADER, KEY_WRAP_LABEL_HEADER, SEGMENT_SIZE_HEADER,
+};
+use mailer::Mailer;
+use oidc::OidcProvider;
+use routes::create_router;
+// 引入 sqlx 的 PostgreSQL 连接池配置
+use sqlx::postgres::PgPoolOptions;
+// 引入 tower-http 的跨域中间件
+use tower_http::cors::CorsLayer;
+// 引入 tracing_subscriber 的日志级别过滤器
+use tracing_subscriber::filter::LevelFilter;
+use utils::{jwt_keys::JwtKeys, rate_limit::RateLimiter};
+
+// 应用全局状态，在所有请求处理函数之间共享
+#[derive(Debug, Clone)]
+pub struct AppState {
+    pub env: Config,          // 应用配置
+    pub db_client: DBClient,  // 数据库客户端
+    pub search_limiter: Arc<RateLimiter>, // 邮箱搜索接口的按用户限流器
+    pub mfa_limiter: Arc<RateLimiter>, // 两步验证码校验的按用户限流器
+    pub mail_limiter: Arc<RateLimiter>, // 验证邮件、重置密码邮件和登录链接邮件的按用途和邮箱限流器
+    pub mailer: Arc<dyn Mailer>, // 邮件发送器
+    pub oidc: Option<Arc<OidcProvider>>, // OIDC 身份提供方，未配置时为 None
+    pub auth_provider: Arc<dyn AuthProvider>, // 邮箱密码登录的认证后端
+    pub jwt_keys: Arc<JwtKeys>, // JWT 签名和验证密钥
+}
+
+#[tokio::main]
+async fn main() {
+    // 初始化日志输出，记录 DEBUG 及以上级别的日志
+    tracing_subscriber::fmt()
+        .with_max_level(LevelFilter::DEBUG)
+        .init();
+
+    // 从 .env 文件加载环境变量
+    dotenv().ok();
+
+    // 根据环境变量初始化配置
+    let config = Config::init();
+
+    // 创建数据库连接池，连接失败时直接退出
+    let pool = match PgPoolOptions::new()
+        .max_connections(10)
+        .connect(&config.database_url)
+        .await
+    {
+        Ok(pool) => {
+            println!("✅Connection to the database is successful!");
+            pool
+        }
+        Err(err) => {
+            println!("🔥 Failed to connect to the database: {:?}", err);
+            std::process::exit(1);
+        }
+    };
+
+    // 使用连接池创建数据库客户端
+    let db_client = DBClient::new(pool);
+
+    // 带参数运行时只执行维护命令，不启动 HTTP 服务
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some((command, args)) = args.split_first() {
+        if let Err(e) = commands::run(command, args, &db_client).await {
+            println!("🔥 {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // 加载 JWT 签名密钥，未配置密钥目录时生成临时密钥
+    let jwt_keys = match (&config.jwt_keys_dir, &config.jwt_active_kid) {
+        (Some(dir), Some(kid)) => JwtKeys::load(dir, kid),
+        (None, None) => {
+            println!("⚠️ JWT_KEYS_DIR is not set, using an ephemeral signing key. Tokens will not survive a restart.");
+            JwtKeys::ephemeral()
+        }
+        _ => Err("JWT_KEYS_DIR and JWT_ACTIVE_KID must be set together".to_string()),
+    };
+
+    let jwt_keys = match jwt_keys {
+        Ok(jwt_keys) => {
+            println!("✅Loaded JWT signing keys: {:?}", jwt_keys);
+            jwt_keys
+        }
+        Err(err) => {
+            println!("🔥 Failed to load JWT signing keys: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    // 创建邮件发送器，未配置 SMTP 时邮件写入本地目录
+    let mailer = match mailer::from_config(&config) {
+        Ok(mailer) => {
+            match &config.smtp_host {
+                Some(host) => println!("✅Sending mail through SMTP server {}", host),
+                None => println!("⚠️ SMTP_HOST is not set, writing mail to {}/", config.mail_drop_dir),
+            }
+            mailer
+        }
+        Err(err) => {
+            println!("🔥 Failed to initialize mailer: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    // 创建 OIDC 身份提供方，未配置时不启用单点登录
+    let oidc = match OidcProvider::from_config(&config) {
+        Ok(Some(oidc)) => {
+            println!("✅OIDC single sign-on enabled for {}", config.oidc_issuer_url.as_deref().unwrap_or_default());
+            Some(Arc::new(oidc))
+        }
+        Ok(None) => None,
+        Err(err) => {
+            println!("🔥 Failed to configure OIDC: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    // 创建邮箱密码登录的认证后端
+    let auth_provider = match auth_provider::from_config(&config) {
+        Ok(auth_provider) => {
+            println!("✅Password login uses providers: {}", config.auth_providers.join(", "));
+            auth_provider
+        }
+        Err(err) => {
+            println!("🔥 Failed to configure authentication providers: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    // 已弃用的加密算法只用于解密历史文件，不能用于加密新上传的文件
+    if config.file_cipher_suite.is_deprecated() {
+        println!("🔥 FILE_CIPHER_SUITE {} is deprecated, use aes-256-gcm or chacha20-poly1305", config.file_cipher_suite.as_str());
+        std::process::exit(1);
+    }
+    println!("✅Encrypting new files with {}", config.file_cipher_suite.as_str());
+
+    // 配置跨域请求，允许前端携带 Cookie 访问接口，并读取文件下载相关的响应头
+    let cors = CorsLayer::new()
+        .allow_origin("http://localhost:3000".parse::<HeaderValue>().unwrap())
+        .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE])
+        .expose_headers([
+            CONTENT_DISPOSITION,
+            HeaderName::from_static(ENCRYPTED_AES_KEY_HEADER),
+            HeaderName::from_static(ENCRYPTION_IV_HEADER),
+            HeaderName::from_static(CIPHER_SUITE_HEADER),
+            HeaderName::from_static(FORMAT_VERSION_HEADER),
+            HeaderName::from_static(ENCRYPTION_AAD_HEADER),
+            HeaderName::from_static(SEGMENT_SIZE_HEADER),
+            HeaderName::from_static(KEY_FINGERPRINT_HEADER),
+            HeaderName::from_static(KEY_WRAP_LABEL_HEADER),
+        ])
+        .allow_credentials(true)
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE]);
+
+    // 构建应用状态
+    let app_state = AppState {
+        env: config.clone(),
+        db_client,
+        // 每个用户每分钟最多搜索 30 次
+        search_limiter: Arc::new(RateLimiter::new(30, Duration::from_secs(60))),
+        // 每个用户每 5 分钟最多尝试 5 次两步验证码，防止暴力猜测
+        mfa_limiter: Arc::new(RateLimiter::new(5, Duration::from_secs(300))),
+        // 每个邮箱每 15 分钟最多发送 3 封验证或重置密码邮件
+        mail_limiter: Arc::new(RateLimiter::new(3, Duration::from_secs(900))),
+        mailer,
+        oidc,
+        auth_provider,
+        jwt_keys: Arc::new(jwt_keys),
+    };
+
+    // 创建路由并挂载跨域中间件
+    let app = create_router(Arc::new(app_state.clone())).layer(cors.clone());
+
+    println!("🚀 Server is running on http://localhost:{}", config.port);
+
+    // 绑定端口并启动 HTTP 服务
+    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", &config.port))
+        .await
+        .unwrap();
+
+    // 提供客户端地址，供登录限流按 IP 统计
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
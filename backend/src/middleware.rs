@@ -0,0 +1,182 @@
+// 引入标准库中的 Arc，用于共享应用状态
+use std::sync::Arc;
+
+// 引入 axum 的请求、中间件和扩展类型
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{header, HeaderMap, Method},
+    middleware::Next,
+    response::IntoResponse,
+    Extension,
+};
+// 引入 axum-extra 的 Cookie 提取器
+use axum_extra::extract::cookie::CookieJar;
+// 引入 serde 库，用于数据的序列化与反序列化
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::UserExt,
+    error::{ErrorMessage, HttpError},
+    models::User,
+    utils::{
+        access_token::{self, Scope},
+        token,
+    },
+    AppState,
+};
+
+// 认证通过后放入请求扩展中的用户信息
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JWTAuthMiddleware {
+    pub user: User, // 当前登录的用户
+    pub scopes: Option<Vec<Scope>>, // 个人访问令牌的权限范围，登录会话为 None 表示不受限制
+}
+
+/// 从请求中读取访问令牌
+///
+/// 优先读取名为 `token` 的 Cookie，其次读取 `Authorization` 请求头中的 Bearer 令牌。
+///
+/// # 参数
+/// - `cookie_jar`: 请求携带的 Cookie。
+/// - `headers`: 请求头。
+///
+/// # 返回
+/// 返回令牌字符串，未提供时返回 `None`。
+pub fn extract_access_token(cookie_jar: &CookieJar, headers: &HeaderMap) -> Option<String> {
+    cookie_jar
+        .get("token")
+        .map(|cookie| cookie.value().to_string())
+        .or_else(|| {
+            headers
+                .get(header::AUTHORIZATION)
+                .and_then(|auth_header| auth_header.to_str().ok())
+                .and_then(|auth_value| auth_value.strip_prefix("Bearer "))
+                .map(|token| token.to_owned())
+        })
+}
+
+/// JWT 认证中间件
+///
+/// 依次从名为 `token` 的 Cookie 和 `Authorization: Bearer` 请求头中读取令牌。
+/// 以 `ssp_` 开头的令牌按个人访问令牌处理，并按接口检查权限范围；
+/// 其余令牌按 JWT 校验签名和会话状态。认证通过后将 `JWTAuthMiddleware` 放入请求扩展。
+///
+/// # 返回
+/// 认证成功时继续处理请求，令牌无效时返回 401，权限范围不足时返回 403。
+pub async fn auth(
+    cookie_jar: CookieJar,
+    Extension(app_state): Extension<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, HttpError> {
+    let token = extract_access_token(&cookie_jar, req.headers()).ok_or_else(|| {
+        HttpError::unauthorized(ErrorMessage::TokenNotProvided.to_string())
+    })?;
+
+    let auth_user = if access_token::is_personal_access_token(&token) {
+        let path = req
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|path| path.as_str().to_owned())
+            .unwrap_or_default();
+
+        authenticate_personal_access_token(&app_state, &token, req.method(), &path).await?
+    } else {
+        authenticate_jwt(&app_state, token).await?
+    };
+
+    // 将认证用户放入请求扩展，供后续处理函数使用
+    req.extensions_mut().insert(auth_user);
+
+    Ok(next.run(req).await)
+}
+
+// 校验 JWT 访问令牌，返回拥有全部权限的认证信息
+async fn authenticate_jwt(
+    app_state: &AppState,
+    token: String,
+) -> Result<JWTAuthMiddleware, HttpError> {
+    // 解码令牌，得到用户 ID 和会话族 ID
+    let claims = token::decode_token(token, &app_state.jwt_keys)?;
+
+    let user_id = uuid::Uuid::parse_str(&claims.sub)
+        .map_err(|_| HttpError::unauthorized(ErrorMessage::InvalidToken.to_string()))?;
+
+    let session_id = uuid::Uuid::parse_str(&claims.sid)
+        .map_err(|_| HttpError::unauthorized(ErrorMessage::InvalidToken.to_string()))?;
+
+    // 会话登出或被吊销后，尚未过期的访问令牌也立即失效
+    let session_active = app_state
+        .db_client
+        .is_session_active(session_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    if !session_active {
+        return Err(HttpError::unauthorized(
+            ErrorMessage::SessionRevoked.to_string(),
+        ));
+    }
+
+    let user = get_user(app_state, user_id).await?;
+
+    // 修改密码或退出所有设备后，令牌版本号已变化，之前签发的令牌全部失效
+    if claims.ver != user.token_version {
+        return Err(HttpError::unauthorized(
+            ErrorMessage::InvalidToken.to_string(),
+        ));
+    }
+
+    Ok(JWTAuthMiddleware { user, scopes: None })
+}
+
+// 校验个人访问令牌，并检查其权限范围是否覆盖当前接口
+async fn authenticate_personal_access_token(
+    app_state: &AppState,
+    token: &str,
+    method: &Method,
+    path: &str,
+) -> Result<JWTAuthMiddleware, HttpError> {
+    // 只有未吊销且未过期的令牌才能查询到
+    let access_token = app_state
+        .db_client
+        .use_personal_access_token(&token::hash_token(token))
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::InvalidToken.to_string()))?;
+
+    let scopes: Vec<Scope> = access_token
+        .scopes
+        .iter()
+        .filter_map(|scope| scope.parse().ok())
+        .collect();
+
+    // 未在权限表中列出的接口一律拒绝
+    let allowed = access_token::required_scope(method, path)
+        .map(|required| scopes.contains(&required))
+        .unwrap_or(false);
+
+    if !allowed {
+        return Err(HttpError::forbidden(
+            ErrorMessage::InsufficientScope.to_string(),
+        ));
+    }
+
+    let user = get_user(app_state, access_token.user_id).await?;
+
+    Ok(JWTAuthMiddleware {
+        user,
+        scopes: Some(scopes),
+    })
+}
+
+// 查询令牌对应的用户，用户不存在时返回 401
+async fn get_user(app_state: &AppState, user_id: uuid::Uuid) -> Result<User, HttpError> {
+    let user = app_state
+        .db_client
+        .get_user(Some(user_id), None, None)
+        .await
+        .map_err(|_| HttpError::unauthorized(ErrorMessage::UserNoLongerExist.to_string()))?;
+
+    user.ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNoLongerExist.to_string()))
+}
@@ -12,7 +12,8 @@ pub struct User {
     pub name: String,               // 用户名
     pub email: String,              // 用户邮箱
     pub password: String,           // 用户密码
-    pub public_key: Option<String>, // 用户的公钥，可能为空
+    pub token_version: i32,         // 令牌版本号，递增后之前签发的令牌全部失效
+    pub email_verified_at: Option<DateTime<Utc>>, // 邮箱验证时间，为空表示邮箱尚未验证
     pub created_at: Option<DateTime<Utc>>, // 用户创建时间，可能为空
     pub updated_at: Option<DateTime<Utc>>,
 
@@ -25,9 +26,12 @@ pub struct File {
     pub user_id: Option<uuid::Uuid>,       // 文件所属用户的唯一标识符 (UUID)，可能为空
     pub file_name: String,                 // 文件名
     pub file_size: i64,                    // 文件大小 (字节数)
-    pub encrypted_aes_key: Vec<u8>,        // 加密后的 AES 密钥
     pub encrypted_file: Vec<u8>,           // 加密后的文件数据
-    pub iv: Vec<u8>,                       // 初始化向量 (IV) 用于加密解密
+    pub iv: Vec<u8>,                       // 初始化向量 (IV) 用于加密解密，AEAD 算法为随机数
+    pub cipher_suite: String,              // 文件内容使用的加密算法，例如 aes-256-gcm
+    pub format_version: i16,               // 密文格式版本，1 为旧版 CBC，2 为 AEAD 信封，3 为流式分段格式
+    pub segment_size: Option<i32>,         // 流式格式每个分段的明文大小，其他格式为空
+    pub segment_count: Option<i32>,        // 流式格式的分段数量，上传完成前和其他格式为空
     pub created_at: Option<DateTime<Utc>>,  // 文件上传时间，可能为空
 }
 
@@ -42,12 +46,131 @@ pub struct SharedLink {
     pub created_at: Option<DateTime<Utc>>,  // 分享链接创建时间，可能为空
 }
 
+// 用户设备公钥数据结构，每个用户可以为不同设备分别上传公钥
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow, sqlx::Type)] // 派生 Debug, Clone, Deserialize, Serialize, sqlx::FromRow 和 sqlx::Type
+pub struct UserKey {
+    pub id: uuid::Uuid,                     // 公钥唯一标识符 (UUID)
+    pub user_id: uuid::Uuid,                // 公钥所属用户的唯一标识符 (UUID)
+    pub label: String,                      // 设备名称
+    pub algorithm: String,                  // 包装文件密钥使用的算法
+    pub public_key: String,                 // SPKI PEM 格式的公钥
+    pub fingerprint: String,                // 公钥的 SHA-256 指纹
+    pub created_at: Option<DateTime<Utc>>,  // 公钥添加时间，可能为空
+    pub revoked_at: Option<DateTime<Utc>>,  // 吊销时间，可能为空
+    pub replaced_by: Option<uuid::Uuid>,    // 轮换后替代该公钥的新公钥 ID，可能为空
+}
+
+// 为某个用户的某把设备公钥包装的文件密钥
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow, sqlx::Type)] // 派生 Debug, Clone, Deserialize, Serialize, sqlx::FromRow 和 sqlx::Type
+pub struct FileKey {
+    pub user_key_id: uuid::Uuid,            // 包装文件密钥使用的设备公钥 ID
+    pub fingerprint: String,                // 该设备公钥的 SHA-256 指纹
+    pub encrypted_aes_key: Vec<u8>,         // 使用该公钥加密后的文件密钥
+    pub key_wrap_version: i16,              // 文件密钥的包装格式版本，见 cipher::key_wrap_label
+}
+
+// 轮换公钥时仍需重新包装的文件密钥，即对应文件还有未过期的共享链接
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow, sqlx::Type)] // 派生 Debug, Clone, Deserialize, Serialize, sqlx::FromRow 和 sqlx::Type
+pub struct PendingFileKey {
+    pub file_id: uuid::Uuid,                // 文件唯一标识符 (UUID)
+    pub encrypted_aes_key: Vec<u8>,         // 使用旧公钥加密后的文件密钥
+    pub key_wrap_version: i16,              // 文件密钥的包装格式版本，见 cipher::key_wrap_label
+}
+
+// 会话数据结构，每条记录对应一个签发过的刷新令牌
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow, sqlx::Type)] // 派生 Debug, Clone, Deserialize, Serialize, sqlx::FromRow 和 sqlx::Type
+pub struct Session {
+    pub id: uuid::Uuid,                     // 会话记录唯一标识符 (UUID)
+    pub user_id: uuid::Uuid,                // 会话所属用户的唯一标识符 (UUID)
+    pub family_id: uuid::Uuid,              // 会话族 ID，同一次登录轮换出的刷新令牌共享该值
+    pub refresh_token_hash: String,         // 刷新令牌的 SHA-256 摘要
+    pub expires_at: DateTime<Utc>,          // 刷新令牌的过期时间
+    pub used_at: Option<DateTime<Utc>>,     // 刷新令牌被轮换的时间，可能为空
+    pub revoked_at: Option<DateTime<Utc>>,  // 会话被吊销的时间，可能为空
+    pub created_at: Option<DateTime<Utc>>,  // 会话创建时间，可能为空
+}
+
+// 个人访问令牌数据结构
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow, sqlx::Type)] // 派生 Debug, Clone, Deserialize, Serialize, sqlx::FromRow 和 sqlx::Type
+pub struct PersonalAccessToken {
+    pub id: uuid::Uuid,                     // 令牌唯一标识符 (UUID)
+    pub user_id: uuid::Uuid,                // 令牌所属用户的唯一标识符 (UUID)
+    pub name: String,                       // 令牌名称
+    pub token_hash: String,                 // 令牌的 SHA-256 摘要
+    pub token_prefix: String,               // 令牌的展示前缀
+    pub scopes: Vec<String>,                // 令牌的权限范围
+    pub expires_at: DateTime<Utc>,          // 令牌的过期时间
+    pub last_used_at: Option<DateTime<Utc>>, // 最近一次使用时间，可能为空
+    pub revoked_at: Option<DateTime<Utc>>,  // 吊销时间，可能为空
+    pub created_at: Option<DateTime<Utc>>,  // 令牌创建时间，可能为空
+}
+
+// TOTP 两步验证配置数据结构
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow, sqlx::Type)] // 派生 Debug, Clone, Deserialize, Serialize, sqlx::FromRow 和 sqlx::Type
+pub struct UserTotp {
+    pub user_id: uuid::Uuid,                // 用户的唯一标识符 (UUID)
+    pub secret: String,                     // Base32 编码的 TOTP 密钥
+    pub enabled_at: Option<DateTime<Utc>>,  // 启用时间，为空表示尚未完成绑定
+    pub last_used_step: Option<i64>,        // 最近一次验证通过的时间步，可能为空
+    pub created_at: Option<DateTime<Utc>>,  // 创建时间，可能为空
+}
+
+// 登录限流数据结构，按账户或 IP 记录连续的登录失败
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow, sqlx::Type)] // 派生 Debug, Clone, Deserialize, Serialize, sqlx::FromRow 和 sqlx::Type
+pub struct LoginThrottle {
+    pub scope: String,                       // 限流维度：account 或 ip
+    pub throttle_key: String,                // 限流键：小写邮箱或 IP 地址
+    pub failure_count: i32,                  // 连续失败次数
+    pub last_failure_at: DateTime<Utc>,      // 最近一次失败的时间
+    pub locked_until: Option<DateTime<Utc>>, // 在此时间之前拒绝登录，可能为空
+}
+
+// OIDC 授权请求数据结构，跳转到身份提供方前保存，回调时使用一次后删除
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow, sqlx::Type)] // 派生 Debug, Clone, Deserialize, Serialize, sqlx::FromRow 和 sqlx::Type
+pub struct OidcAuthRequest {
+    pub state_hash: String,                // state 参数的 SHA-256 摘要
+    pub nonce: String,                     // ID Token 中必须携带的 nonce
+    pub pkce_verifier: String,             // PKCE 校验码
+    pub expires_at: DateTime<Utc>,         // 过期时间
+    pub created_at: Option<DateTime<Utc>>, // 创建时间，可能为空
+}
+
+// 邮件令牌的用途
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailTokenPurpose {
+    VerifyEmail,   // 验证邮箱
+    ResetPassword, // 重置密码
+    MagicLogin,    // 免密码登录链接
+}
+
+impl EmailTokenPurpose {
+    // 在数据库中保存的字符串表示
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailTokenPurpose::VerifyEmail => "verify_email",
+            EmailTokenPurpose::ResetPassword => "reset_password",
+            EmailTokenPurpose::MagicLogin => "magic_login",
+        }
+    }
+}
+
+// 轮换设备公钥的结果
+#[derive(Debug, Clone)]
+pub enum KeyRotationOutcome {
+    Rotated(UserKey),   // 轮换成功，返回新公钥
+    InvalidChallenge,   // 挑战不存在、已使用、已过期，或旧公钥已被吊销
+    DuplicateKey,       // 新公钥已是该用户的有效公钥
+    StaleFileKeys,      // 提交的文件密钥与仍需重新包装的文件密钥不一致
+}
+
 // 发送文件详情数据结构，包含了发送文件的基本信息
 #[derive(sqlx::FromRow)] // 仅派生 sqlx::FromRow，用于从数据库行中转换成结构体
 pub struct SendFileDetails {
     pub file_id: uuid::Uuid,            // 文件的唯一标识符 (UUID)
     pub file_name: String,              // 文件名
     pub recipient_email: String,       // 接收者的邮箱
+    pub encryption_deprecated: bool,   // 是否使用已弃用的加密格式
+    pub sender_copy: bool,             // 发送者是否保留了文件密钥副本，可以下载和转发
     pub expiration_date: Option<DateTime<Utc>>, // 文件过期时间，可能为空
     pub created_at: Option<DateTime<Utc>>, // 文件发送时间，可能为空
 }
@@ -58,6 +181,7 @@ pub struct ReceiveFileDetails {
     pub file_id: uuid::Uuid,            // 文件的唯一标识符 (UUID)
     pub file_name: String,              // 文件名
     pub sender_email: String,          // 发送者的邮箱
+    pub encryption_deprecated: bool,   // 是否使用已弃用的加密格式
     pub expiration_date: Option<DateTime<Utc>>, // 文件过期时间，可能为空
     pub created_at: Option<DateTime<Utc>>, // 文件接收时间，可能为空
 }
\ No newline at end of file
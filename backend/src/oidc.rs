@@ -0,0 +1,226 @@
+// 引入 openidconnect 库，实现 OIDC 授权码 + PKCE 登录
+use openidconnect::{
+    core::{CoreAuthenticationFlow, CoreClient, CoreProviderMetadata},
+    reqwest, AccessTokenHash, AuthorizationCode, ClaimsVerificationError, ClientId, ClientSecret,
+    CsrfToken, EndpointMaybeSet, EndpointNotSet, EndpointSet, IssuerUrl, Nonce,
+    OAuth2TokenResponse, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope, TokenResponse,
+};
+// 引入 tokio 的读写锁，用于缓存身份提供方的元数据
+use tokio::sync::RwLock;
+
+use crate::{auth_provider::ExternalIdentity, config::Config};
+
+// 经过身份提供方元数据配置后的 OIDC 客户端类型
+type ConfiguredClient = CoreClient<
+    EndpointSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointMaybeSet,
+    EndpointMaybeSet,
+>;
+
+// 跳转到身份提供方前生成的授权请求
+#[derive(Debug)]
+pub struct AuthorizationRequest {
+    pub url: String,           // 身份提供方的授权地址
+    pub state: String,         // CSRF 防护用的 state 参数
+    pub nonce: String,         // ID Token 中必须携带的 nonce
+    pub pkce_verifier: String, // PKCE 校验码
+}
+
+/// OIDC 身份提供方
+///
+/// 首次使用时通过 `/.well-known/openid-configuration` 获取并缓存身份提供方的元数据，
+/// ID Token 签名校验失败时重新获取一次，以支持身份提供方轮换签名密钥。
+#[derive(Debug)]
+pub struct OidcProvider {
+    issuer_url: IssuerUrl,                          // 身份提供方的 issuer URL
+    client_id: ClientId,                            // 客户端 ID
+    client_secret: Option<ClientSecret>,            // 客户端密钥，公共客户端可为空
+    redirect_url: RedirectUrl,                      // 回调地址
+    http_client: reqwest::Client,                   // 访问身份提供方的 HTTP 客户端
+    metadata: RwLock<Option<CoreProviderMetadata>>, // 缓存的身份提供方元数据
+}
+
+impl OidcProvider {
+    /// 根据配置创建 OIDC 身份提供方
+    ///
+    /// # 参数
+    /// - `config`: 应用配置。
+    ///
+    /// # 返回
+    /// 未配置 `OIDC_ISSUER_URL` 时返回 `Ok(None)`，配置不完整或无效时返回错误信息。
+    pub fn from_config(config: &Config) -> Result<Option<Self>, String> {
+        let Some(issuer_url) = &config.oidc_issuer_url else {
+            return Ok(None);
+        };
+
+        let client_id = config
+            .oidc_client_id
+            .clone()
+            .ok_or_else(|| "OIDC_CLIENT_ID must be set when OIDC_ISSUER_URL is set".to_string())?;
+
+        let redirect_url = config.oidc_redirect_url.clone().ok_or_else(|| {
+            "OIDC_REDIRECT_URL must be set when OIDC_ISSUER_URL is set".to_string()
+        })?;
+
+        // 不跟随重定向，避免 SSRF
+        let http_client = reqwest::ClientBuilder::new()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        Ok(Some(OidcProvider {
+            issuer_url: IssuerUrl::new(issuer_url.clone())
+                .map_err(|e| format!("Invalid OIDC_ISSUER_URL: {}", e))?,
+            client_id: ClientId::new(client_id),
+            client_secret: config.oidc_client_secret.clone().map(ClientSecret::new),
+            redirect_url: RedirectUrl::new(redirect_url)
+                .map_err(|e| format!("Invalid OIDC_REDIRECT_URL: {}", e))?,
+            http_client,
+            metadata: RwLock::new(None),
+        }))
+    }
+
+    // 获取身份提供方元数据并构造客户端，refresh 为 true 时忽略缓存重新获取
+    async fn client(&self, refresh: bool) -> Result<ConfiguredClient, String> {
+        let cached = self.metadata.read().await.clone();
+
+        let metadata = match cached {
+            Some(metadata) if !refresh => metadata,
+            _ => {
+                let metadata = CoreProviderMetadata::discover_async(
+                    self.issuer_url.clone(),
+                    &self.http_client,
+                )
+                .await
+                .map_err(|e| format!("Failed to discover OIDC provider: {}", e))?;
+
+                *self.metadata.write().await = Some(metadata.clone());
+                metadata
+            }
+        };
+
+        Ok(CoreClient::from_provider_metadata(
+            metadata,
+            self.client_id.clone(),
+            self.client_secret.clone(),
+        )
+        .set_redirect_uri(self.redirect_url.clone()))
+    }
+
+    /// 生成跳转到身份提供方的授权请求
+    ///
+    /// # 返回
+    /// 返回授权地址以及需要保存到服务端的 state、nonce 和 PKCE 校验码。
+    pub async fn authorize(&self) -> Result<AuthorizationRequest, String> {
+        let client = self.client(false).await?;
+
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+        let (url, state, nonce) = client
+            .authorize_url(
+                CoreAuthenticationFlow::AuthorizationCode,
+                CsrfToken::new_random,
+                Nonce::new_random,
+            )
+            .add_scope(Scope::new("email".to_string()))
+            .add_scope(Scope::new("profile".to_string()))
+            .set_pkce_challenge(pkce_challenge)
+            .url();
+
+        Ok(AuthorizationRequest {
+            url: url.to_string(),
+            state: state.secret().to_owned(),
+            nonce: nonce.secret().to_owned(),
+            pkce_verifier: pkce_verifier.secret().to_owned(),
+        })
+    }
+
+    /// 使用授权码换取令牌，并校验 ID Token
+    ///
+    /// # 参数
+    /// - `code`: 回调中的授权码。
+    /// - `pkce_verifier`: 授权请求时生成的 PKCE 校验码。
+    /// - `nonce`: 授权请求时生成的 nonce。
+    ///
+    /// # 返回
+    /// 返回 ID Token 中的用户身份，邮箱缺失或未验证时返回错误信息。
+    pub async fn exchange(
+        &self,
+        code: String,
+        pkce_verifier: String,
+        nonce: String,
+    ) -> Result<ExternalIdentity, String> {
+        let client = self.client(false).await?;
+
+        let token_response = client
+            .exchange_code(AuthorizationCode::new(code))
+            .map_err(|e| e.to_string())?
+            .set_pkce_verifier(PkceCodeVerifier::new(pkce_verifier))
+            .request_async(&self.http_client)
+            .await
+            .map_err(|e| format!("Failed to exchange authorization code: {}", e))?;
+
+        let id_token = token_response
+            .id_token()
+            .ok_or_else(|| "Identity provider did not return an ID token".to_string())?;
+
+        let nonce = Nonce::new(nonce);
+
+        // 签名校验失败可能是身份提供方轮换了密钥，重新获取 JWKS 后再校验一次
+        let signature_failed = matches!(
+            id_token.claims(&client.id_token_verifier(), &nonce),
+            Err(ClaimsVerificationError::SignatureVerification(_))
+        );
+
+        let client = if signature_failed {
+            self.client(true).await?
+        } else {
+            client
+        };
+
+        // 校验签名、issuer、audience、过期时间和 nonce
+        let id_token_verifier = client.id_token_verifier();
+        let claims = id_token
+            .claims(&id_token_verifier, &nonce)
+            .map_err(|e| format!("Invalid ID token: {}", e))?;
+
+        // 确认 access token 与 ID Token 绑定（ID Token 包含 at_hash 时）
+        if let Some(expected_hash) = claims.access_token_hash() {
+            let signing_alg = id_token.signing_alg().map_err(|e| e.to_string())?;
+            let signing_key = id_token
+                .signing_key(&id_token_verifier)
+                .map_err(|e| e.to_string())?;
+            let actual_hash = AccessTokenHash::from_token(
+                token_response.access_token(),
+                signing_alg,
+                signing_key,
+            )
+            .map_err(|e| e.to_string())?;
+
+            if actual_hash != *expected_hash {
+                return Err("Invalid access token hash".to_string());
+            }
+        }
+
+        let email = claims
+            .email()
+            .map(|email| email.as_str().to_string())
+            .filter(|_| claims.email_verified() == Some(true))
+            .ok_or_else(|| {
+                "Identity provider did not return a verified email address".to_string()
+            })?;
+
+        Ok(ExternalIdentity {
+            issuer: claims.issuer().as_str().to_string(),
+            subject: claims.subject().as_str().to_string(),
+            email,
+            name: claims
+                .name()
+                .and_then(|name| name.get(None))
+                .map(|name| name.as_str().to_string()),
+        })
+    }
+}
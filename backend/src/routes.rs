@@ -0,0 +1,53 @@
+// 引入标准库中的 Arc，用于共享应用状态
+use std::sync::Arc;
+
+// 引入 axum 的路由、扩展和 JSON 响应
+use axum::{middleware, response::IntoResponse, routing::get, Extension, Json, Router};
+// 引入 tower-http 的请求追踪中间件
+use tower_http::trace::TraceLayer;
+
+use crate::{
+    dtos::Response,
+    handler::{
+        auth::auth_handler, file::file_handle, file_query::get_file_list_handler,
+        oidc::oidc_handler, user::users_handler,
+    },
+    middleware::auth,
+    AppState,
+};
+
+/// 创建应用路由
+///
+/// # 参数
+/// - `app_state`: 应用全局状态。
+///
+/// # 返回
+/// 返回挂载了全部路由、请求追踪和应用状态的 `Router`。
+pub fn create_router(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/healthchecker", get(health_checker))
+        .route("/.well-known/jwks.json", get(jwks))
+        .nest("/auth", auth_handler().merge(oidc_handler()))
+        .nest("/users", users_handler().layer(middleware::from_fn(auth)))
+        .nest(
+            "/files",
+            file_handle()
+                .merge(get_file_list_handler())
+                .layer(middleware::from_fn(auth)),
+        )
+        .layer(TraceLayer::new_for_http())
+        .layer(Extension(app_state))
+}
+
+// 公布 JWT 验证公钥（JWKS），供其他服务验证 SecureShare 签发的令牌
+async fn jwks(Extension(app_state): Extension<Arc<AppState>>) -> impl IntoResponse {
+    Json(app_state.jwt_keys.jwks().clone())
+}
+
+// 健康检查接口，用于确认服务是否正常运行
+async fn health_checker() -> impl IntoResponse {
+    Json(Response {
+        status: "success",
+        message: "SecureShare server is up and running".to_string(),
+    })
+}
@@ -0,0 +1,144 @@
+// 引入标准库中的格式化和字符串解析 trait
+use std::{fmt, str::FromStr};
+
+// 引入 axum 的请求方法类型
+use axum::http::Method;
+// 引入 serde 库，用于权限范围的序列化与反序列化
+use serde::{Deserialize, Serialize};
+
+use crate::utils::token;
+
+// 个人访问令牌的前缀，用于和 JWT 区分
+pub const PERSONAL_ACCESS_TOKEN_PREFIX: &str = "ssp_";
+
+// 在列表中展示的令牌前缀长度（包含 `ssp_`）
+const DISPLAY_PREFIX_LENGTH: usize = 12;
+
+// 个人访问令牌的权限范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "files:upload")]
+    FilesUpload, // 上传文件
+    #[serde(rename = "files:read")]
+    FilesRead, // 查看文件列表和获取文件
+    #[serde(rename = "users:search")]
+    UsersSearch, // 搜索接收者
+}
+
+impl Scope {
+    // 全部可用的权限范围
+    pub const ALL: [Scope; 3] = [Scope::FilesUpload, Scope::FilesRead, Scope::UsersSearch];
+
+    // 权限范围的字符串表示
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::FilesUpload => "files:upload",
+            Scope::FilesRead => "files:read",
+            Scope::UsersSearch => "users:search",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Scope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| format!("Unknown scope: {}", s))
+    }
+}
+
+/// 生成新的个人访问令牌
+///
+/// # 返回
+/// 返回 `(令牌明文, 令牌摘要, 展示用前缀)`，令牌明文只在创建时返回给用户一次。
+pub fn generate() -> (String, String, String) {
+    let token = format!(
+        "{}{}",
+        PERSONAL_ACCESS_TOKEN_PREFIX,
+        token::generate_random_token()
+    );
+    let token_hash = token::hash_token(&token);
+    let display_prefix = token[..DISPLAY_PREFIX_LENGTH].to_string();
+
+    (token, token_hash, display_prefix)
+}
+
+/// 判断令牌是否为个人访问令牌
+///
+/// # 参数
+/// - `token`: 请求携带的令牌。
+///
+/// # 返回
+/// 以 `ssp_` 开头时返回 `true`。
+pub fn is_personal_access_token(token: &str) -> bool {
+    token.starts_with(PERSONAL_ACCESS_TOKEN_PREFIX)
+}
+
+/// 查询接口所需的权限范围
+///
+/// 个人访问令牌只能访问这里列出的接口，未列出的接口（如修改密码、管理令牌）一律拒绝。
+///
+/// # 参数
+/// - `method`: 请求方法。
+/// - `path`: 匹配到的路由模板，例如 `/files/upload`。
+///
+/// # 返回
+/// 返回接口所需的权限范围，不允许个人访问令牌访问时返回 `None`。
+pub fn required_scope(method: &Method, path: &str) -> Option<Scope> {
+    match (method.as_str(), path) {
+        ("POST", "/files/upload") | ("POST", "/files/forward") => Some(Scope::FilesUpload),
+        ("POST", "/files/retrieve")
+        | ("POST", "/files/download")
+        | ("GET", "/files/sent")
+        | ("GET", "/files/received") => Some(Scope::FilesRead),
+        ("GET", "/users/search") | ("GET", "/users/public-keys") => Some(Scope::UsersSearch),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn required_scope_maps_routes() {
+        let cases = [
+            (Method::POST, "/files/upload", Some(Scope::FilesUpload)),
+            (Method::POST, "/files/forward", Some(Scope::FilesUpload)),
+            (Method::POST, "/files/retrieve", Some(Scope::FilesRead)),
+            (Method::POST, "/files/download", Some(Scope::FilesRead)),
+            (Method::GET, "/files/sent", Some(Scope::FilesRead)),
+            (Method::GET, "/files/received", Some(Scope::FilesRead)),
+            (Method::GET, "/users/search", Some(Scope::UsersSearch)),
+            (Method::GET, "/users/public-keys", Some(Scope::UsersSearch)),
+            // 方法不匹配的已知路由
+            (Method::GET, "/files/upload", None),
+            (Method::GET, "/files/download", None),
+            (Method::DELETE, "/users/public-keys", None),
+            // 账户和密钥管理接口不允许个人访问令牌访问
+            (Method::PUT, "/users/password", None),
+            (Method::GET, "/users/me", None),
+            (Method::POST, "/users/keys", None),
+            (Method::DELETE, "/users/keys/:id", None),
+            (Method::POST, "/users/keys/:id/rotation", None),
+            (Method::POST, "/users/tokens", None),
+            // 未知路由一律拒绝
+            (Method::GET, "/files/unknown", None),
+            (Method::POST, "/files/upload/", None),
+            (Method::GET, "", None),
+        ];
+
+        for (method, path, expected) in cases {
+            assert_eq!(required_scope(&method, path), expected, "{} {}", method, path);
+        }
+    }
+}
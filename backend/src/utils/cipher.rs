@@ -0,0 +1,143 @@
+// 引入标准库中的 FromStr trait，用于从配置解析加密算法
+use std::str::FromStr;
+
+// 引入 uuid 库，用于构造附加认证数据
+use uuid::Uuid;
+
+// 密文格式版本 1 为旧版 AES-256-CBC，没有完整性校验，只用于解密历史文件
+
+// AEAD 信封格式：密文末尾附带认证标签，文件 ID 和接收者 ID 作为附加认证数据
+pub const AEAD_FORMAT_VERSION: i16 = 2;
+
+// 流式格式：按 STREAM_SEGMENT_SIZE 分段加密，分段随机数由前缀、分段序号和最后一段标记组成
+pub const STREAM_FORMAT_VERSION: i16 = 3;
+
+// 文件密钥的包装格式：版本 1 为不带标签的 RSA-OAEP，版本 2 的 OAEP 标签绑定文件和持有者
+pub const LEGACY_KEY_WRAP_VERSION: i16 = 1;
+pub const KEY_WRAP_VERSION: i16 = 2;
+
+// 流式格式每个分段的明文大小（字节），上传和下载时的内存占用以此为上限
+pub const STREAM_SEGMENT_SIZE: usize = 64 * 1024;
+
+// AEAD 认证标签的长度（字节），每个分段的密文比明文长这么多
+pub const AEAD_TAG_LEN: usize = 16;
+
+// 流式格式分段随机数中计数器（4 字节）和最后一段标记（1 字节）所占的长度
+const STREAM_NONCE_OVERHEAD: usize = 5;
+
+// 文件内容使用的对称加密算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CipherSuite {
+    Aes256Cbc,        // 旧版 AES-256-CBC，只用于解密历史文件
+    Aes256Gcm,        // AES-256-GCM
+    ChaCha20Poly1305, // ChaCha20-Poly1305，适合没有 AES 硬件加速的服务器
+}
+
+impl CipherSuite {
+    // 在数据库中保存的字符串表示
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CipherSuite::Aes256Cbc => "aes-256-cbc",
+            CipherSuite::Aes256Gcm => "aes-256-gcm",
+            CipherSuite::ChaCha20Poly1305 => "chacha20-poly1305",
+        }
+    }
+
+    // 没有完整性校验的算法已弃用，不能再用于加密新文件
+    pub fn is_deprecated(&self) -> bool {
+        *self == CipherSuite::Aes256Cbc
+    }
+
+    // 随机数（CBC 为 IV）的长度（字节）
+    pub fn nonce_len(&self) -> usize {
+        match self {
+            CipherSuite::Aes256Cbc => 16,
+            CipherSuite::Aes256Gcm | CipherSuite::ChaCha20Poly1305 => 12,
+        }
+    }
+
+    // 流式格式中随机数前缀的长度（字节），保存在 iv 列中
+    pub fn nonce_prefix_len(&self) -> usize {
+        self.nonce_len() - STREAM_NONCE_OVERHEAD
+    }
+}
+
+impl FromStr for CipherSuite {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "aes-256-cbc" => Ok(CipherSuite::Aes256Cbc),
+            "aes-256-gcm" => Ok(CipherSuite::Aes256Gcm),
+            "chacha20-poly1305" => Ok(CipherSuite::ChaCha20Poly1305),
+            other => Err(format!("Unknown cipher suite: {}", other)),
+        }
+    }
+}
+
+/// 构造 AEAD 信封的附加认证数据
+///
+/// 将密文绑定到文件和接收者，密文被替换到其他文件或其他接收者名下时解密失败。
+///
+/// # 参数
+/// - `cipher_suite`: 加密算法。
+/// - `file_id`: 文件 ID。
+/// - `recipient_id`: 接收者的用户 ID。
+///
+/// # 返回
+/// 返回 `SecureShare/v2/<算法>/<文件 ID>/<接收者 ID>` 格式的字节串。
+pub fn associated_data(cipher_suite: CipherSuite, file_id: Uuid, recipient_id: Uuid) -> Vec<u8> {
+    format!(
+        "SecureShare/v{}/{}/{}/{}",
+        AEAD_FORMAT_VERSION,
+        cipher_suite.as_str(),
+        file_id,
+        recipient_id
+    )
+    .into_bytes()
+}
+
+/// 构造流式格式每个分段的附加认证数据
+///
+/// 只绑定文件，不绑定接收者：同一份密文由发送者和所有接收者（包括转发后的接收者）共享，
+/// 转发时不重新加密内容，分段中无法写入某一个接收者。接收者改为绑定在为其单独包装的文件密钥上，
+/// 见 `key_wrap_label`。分段的顺序和截断由分段随机数中的序号和最后一段标记保护。
+///
+/// # 参数
+/// - `cipher_suite`: 加密算法。
+/// - `file_id`: 文件 ID。
+///
+/// # 返回
+/// 返回 `SecureShare/v3/<算法>/<文件 ID>` 格式的字节串。
+pub fn stream_associated_data(cipher_suite: CipherSuite, file_id: Uuid) -> Vec<u8> {
+    format!(
+        "SecureShare/v{}/{}/{}",
+        STREAM_FORMAT_VERSION,
+        cipher_suite.as_str(),
+        file_id
+    )
+    .into_bytes()
+}
+
+/// 构造包装文件密钥时使用的 RSA-OAEP 标签
+///
+/// 标签绑定文件和持有者（发送者或接收者）：包装后的文件密钥被挪到其他文件或其他用户名下时无法解开。
+/// 客户端自行包装或解开文件密钥时必须使用相同的标签。
+///
+/// # 参数
+/// - `key_wrap_version`: 文件密钥的包装格式版本。
+/// - `file_id`: 文件 ID。
+/// - `holder_id`: 持有该文件密钥的用户 ID。
+///
+/// # 返回
+/// 返回 `SecureShare/key/v2/<文件 ID>/<持有者 ID>` 格式的标签，旧版包装格式没有标签时返回 `None`。
+pub fn key_wrap_label(key_wrap_version: i16, file_id: Uuid, holder_id: Uuid) -> Option<String> {
+    if key_wrap_version == LEGACY_KEY_WRAP_VERSION {
+        return None;
+    }
+
+    Some(format!(
+        "SecureShare/key/v{}/{}/{}",
+        key_wrap_version, file_id, holder_id
+    ))
+}
@@ -0,0 +1,192 @@
+// 引入 aes 和 block-modes 库，用于解密旧版 AES-256-CBC 文件
+use aes::Aes256;
+use block_modes::{block_padding::Pkcs7, BlockMode, Cbc};
+// 引入 aes-gcm 和 chacha20poly1305 库，用于 AEAD 对称解密
+use aes_gcm::{
+    aead::{stream::DecryptorBE32, Aead, KeyInit, Payload},
+    Aes256Gcm,
+};
+use chacha20poly1305::ChaCha20Poly1305;
+// 引入 rsa 库，用于使用接收者私钥解密文件密钥
+use rsa::{Oaep, RsaPrivateKey};
+// 引入 sha2 库，作为 OAEP 填充的摘要算法
+use sha2::Sha256;
+
+use crate::{error::HttpError, utils::cipher::CipherSuite};
+
+// 使用 PKCS7 填充的 AES-256-CBC 解密模式
+type Aes256Cbc = Cbc<Aes256, Pkcs7>;
+
+/// 使用 RSA 私钥（OAEP + SHA-256）解开文件密钥
+///
+/// # 参数
+/// - `encrypted_aes_key`: 加密后的文件密钥。
+/// - `user_private_key`: 接收者的 RSA 私钥。
+/// - `label`: 包装时使用的 OAEP 标签，见 `cipher::key_wrap_label`。
+///
+/// # 返回
+/// 返回 256 位文件密钥，私钥或标签不匹配时返回 400 错误。
+pub fn unwrap_key(
+    encrypted_aes_key: &[u8],
+    user_private_key: &RsaPrivateKey,
+    label: Option<String>,
+) -> Result<Vec<u8>, HttpError> {
+    let padding = match label {
+        Some(label) => Oaep::new_with_label::<Sha256, _>(label),
+        None => Oaep::new::<Sha256>(),
+    };
+
+    let aes_key = user_private_key
+        .decrypt(padding, encrypted_aes_key)
+        .map_err(|_| {
+            HttpError::bad_request("Failed to decrypt file key, please check the private key")
+        })?;
+
+    if aes_key.len() != 32 {
+        return Err(HttpError::bad_request("Failed to decrypt file"));
+    }
+
+    Ok(aes_key)
+}
+
+/// 解密整体加密的文件（格式版本 1 和 2）
+///
+/// 按文件的加密算法解密文件内容，文件密钥见 `unwrap_key`。
+/// AEAD 算法会同时校验认证标签和附加认证数据，旧版 AES-256-CBC 文件没有完整性校验。
+///
+/// # 参数
+/// - `aes_key`: 解开后的文件密钥。
+/// - `encrypted_file`: 加密后的文件内容。
+/// - `iv`: 随机数（CBC 为初始化向量）。
+/// - `cipher_suite`: 文件的加密算法。
+/// - `aad`: 附加认证数据，旧版 CBC 文件忽略该参数。
+///
+/// # 返回
+/// 返回解密后的文件内容，数据损坏或被篡改时返回 400 错误。
+pub fn decrypt_file(
+    aes_key: &[u8],
+    encrypted_file: &[u8],
+    iv: &[u8],
+    cipher_suite: CipherSuite,
+    aad: &[u8],
+) -> Result<Vec<u8>, HttpError> {
+    if aes_key.len() != 32 || iv.len() != cipher_suite.nonce_len() {
+        return Err(HttpError::bad_request("Failed to decrypt file"));
+    }
+
+    let payload = Payload {
+        msg: encrypted_file,
+        aad,
+    };
+
+    let decrypted_data = match cipher_suite {
+        CipherSuite::Aes256Gcm => Aes256Gcm::new(aes_key.into())
+            .decrypt(iv.into(), payload)
+            .ok(),
+        CipherSuite::ChaCha20Poly1305 => ChaCha20Poly1305::new(aes_key.into())
+            .decrypt(iv.into(), payload)
+            .ok(),
+        // 旧版文件使用 AES-256-CBC 解密
+        CipherSuite::Aes256Cbc => Aes256Cbc::new_from_slices(aes_key, iv)
+            .ok()
+            .and_then(|cipher| cipher.decrypt_vec(encrypted_file).ok()),
+    };
+
+    decrypted_data.ok_or_else(|| HttpError::bad_request("Failed to decrypt file"))
+}
+
+// 按加密算法区分的分段解密器
+enum SegmentDecryptor {
+    // AES 的轮密钥较大，装箱以免枚举整体过大
+    Aes256Gcm(Box<DecryptorBE32<Aes256Gcm>>),
+    ChaCha20Poly1305(DecryptorBE32<ChaCha20Poly1305>),
+}
+
+/// 流式格式的文件解密器
+///
+/// 按顺序逐段解密，最后一段必须使用 `decrypt_last`，分段被重排、替换或截断时解密失败。
+pub struct StreamDecryptor {
+    aad: Vec<u8>, // 每个分段的附加认证数据
+    decryptor: SegmentDecryptor,
+}
+
+impl StreamDecryptor {
+    /// 创建新的 `StreamDecryptor` 实例
+    ///
+    /// # 参数
+    /// - `aes_key`: 文件密钥，见 `unwrap_key`。
+    /// - `nonce_prefix`: 随机数前缀。
+    /// - `cipher_suite`: 文件的加密算法。
+    /// - `aad`: 每个分段的附加认证数据，见 `cipher::stream_associated_data`。
+    ///
+    /// # 返回
+    /// 返回解密器，参数与算法不匹配时返回 400 错误。
+    pub fn new(
+        aes_key: &[u8],
+        nonce_prefix: &[u8],
+        cipher_suite: CipherSuite,
+        aad: Vec<u8>,
+    ) -> Result<Self, HttpError> {
+        if aes_key.len() != 32 || nonce_prefix.len() != cipher_suite.nonce_prefix_len() {
+            return Err(HttpError::bad_request("Failed to decrypt file"));
+        }
+
+        let decryptor = match cipher_suite {
+            CipherSuite::Aes256Gcm => SegmentDecryptor::Aes256Gcm(Box::new(
+                DecryptorBE32::from_aead(Aes256Gcm::new(aes_key.into()), nonce_prefix.into()),
+            )),
+            CipherSuite::ChaCha20Poly1305 => {
+                SegmentDecryptor::ChaCha20Poly1305(DecryptorBE32::from_aead(
+                    ChaCha20Poly1305::new(aes_key.into()),
+                    nonce_prefix.into(),
+                ))
+            }
+            // 流式格式不支持旧版 CBC
+            CipherSuite::Aes256Cbc => {
+                return Err(HttpError::bad_request("Failed to decrypt file"));
+            }
+        };
+
+        Ok(StreamDecryptor { aad, decryptor })
+    }
+
+    /// 解密除最后一段以外的分段
+    ///
+    /// # 参数
+    /// - `segment`: 密文分段。
+    ///
+    /// # 返回
+    /// 返回解密后的明文，分段损坏或被篡改时返回 400 错误。
+    pub fn decrypt_next(&mut self, segment: &[u8]) -> Result<Vec<u8>, HttpError> {
+        let payload = Payload {
+            msg: segment,
+            aad: &self.aad,
+        };
+
+        match &mut self.decryptor {
+            SegmentDecryptor::Aes256Gcm(decryptor) => decryptor.decrypt_next(payload),
+            SegmentDecryptor::ChaCha20Poly1305(decryptor) => decryptor.decrypt_next(payload),
+        }
+        .map_err(|_| HttpError::bad_request("Failed to decrypt file"))
+    }
+
+    /// 解密最后一段
+    ///
+    /// # 参数
+    /// - `segment`: 最后一个密文分段。
+    ///
+    /// # 返回
+    /// 返回解密后的明文，分段不是最后一段、损坏或被篡改时返回 400 错误。
+    pub fn decrypt_last(self, segment: &[u8]) -> Result<Vec<u8>, HttpError> {
+        let payload = Payload {
+            msg: segment,
+            aad: &self.aad,
+        };
+
+        match self.decryptor {
+            SegmentDecryptor::Aes256Gcm(decryptor) => (*decryptor).decrypt_last(payload),
+            SegmentDecryptor::ChaCha20Poly1305(decryptor) => decryptor.decrypt_last(payload),
+        }
+        .map_err(|_| HttpError::bad_request("Failed to decrypt file"))
+    }
+}
@@ -0,0 +1,331 @@
+// 引入 aes-gcm 和 chacha20poly1305 库，用于 AEAD 流式加密
+use aes_gcm::{
+    aead::{stream::EncryptorBE32, KeyInit, Payload},
+    Aes256Gcm,
+};
+use chacha20poly1305::ChaCha20Poly1305;
+// 引入 rand 库，用于生成随机的文件密钥和随机数前缀
+use rand::{rngs::OsRng, RngCore};
+// 引入 rsa 库，用于使用接收者公钥加密文件密钥
+use rsa::{Oaep, RsaPublicKey};
+// 引入 sha2 库，作为 OAEP 填充的摘要算法
+use sha2::Sha256;
+
+use crate::{
+    error::HttpError,
+    utils::cipher::{CipherSuite, STREAM_SEGMENT_SIZE},
+};
+
+/// 使用 RSA 公钥（OAEP + SHA-256）包装文件密钥
+///
+/// # 参数
+/// - `aes_key`: 文件密钥。
+/// - `user_public_key`: 持有者（接收者或发送者）的 RSA 公钥。
+/// - `label`: OAEP 标签，见 `cipher::key_wrap_label`，为空时不带标签。
+///
+/// # 返回
+/// 返回加密后的文件密钥，加密失败时返回 500 错误。
+pub fn wrap_key(
+    aes_key: &[u8],
+    user_public_key: &RsaPublicKey,
+    label: Option<String>,
+) -> Result<Vec<u8>, HttpError> {
+    let padding = match label {
+        Some(label) => Oaep::new_with_label::<Sha256, _>(label),
+        None => Oaep::new::<Sha256>(),
+    };
+
+    user_public_key
+        .encrypt(&mut OsRng, padding, aes_key)
+        .map_err(|e| HttpError::server_error(e.to_string()))
+}
+
+// 按加密算法区分的分段加密器
+enum SegmentEncryptor {
+    // AES 的轮密钥较大，装箱以免枚举整体过大
+    Aes256Gcm(Box<EncryptorBE32<Aes256Gcm>>),
+    ChaCha20Poly1305(EncryptorBE32<ChaCha20Poly1305>),
+}
+
+/// 流式格式的文件加密器
+///
+/// 按 `STREAM_SEGMENT_SIZE` 将明文切分为分段并逐段加密，内存中最多缓存一个分段。
+/// 分段随机数由随机前缀、分段序号和最后一段标记组成，分段被重排或截断时解密失败。
+pub struct StreamEncryptor {
+    aes_key: [u8; 32],     // 随机生成的 256 位文件密钥
+    nonce_prefix: Vec<u8>, // 随机数前缀
+    aad: Vec<u8>,          // 每个分段的附加认证数据
+    encryptor: SegmentEncryptor,
+    buffer: Vec<u8>, // 尚未凑满一个分段的明文
+}
+
+impl StreamEncryptor {
+    /// 创建新的 `StreamEncryptor` 实例，生成随机的文件密钥和随机数前缀
+    ///
+    /// # 参数
+    /// - `cipher_suite`: 加密算法，不能是已弃用的算法。
+    /// - `aad`: 每个分段的附加认证数据，见 `cipher::stream_associated_data`。
+    ///
+    /// # 返回
+    /// 返回加密器，算法已弃用时返回 500 错误。
+    pub fn new(cipher_suite: CipherSuite, aad: Vec<u8>) -> Result<Self, HttpError> {
+        let mut rng = OsRng;
+
+        // 每个文件使用不同的密钥和随机数前缀
+        let mut aes_key = [0u8; 32];
+        let mut nonce_prefix = vec![0u8; cipher_suite.nonce_prefix_len()];
+        rng.fill_bytes(&mut aes_key);
+        rng.fill_bytes(&mut nonce_prefix);
+
+        let encryptor = match cipher_suite {
+            CipherSuite::Aes256Gcm => {
+                SegmentEncryptor::Aes256Gcm(Box::new(EncryptorBE32::from_aead(
+                    Aes256Gcm::new((&aes_key).into()),
+                    nonce_prefix.as_slice().into(),
+                )))
+            }
+            CipherSuite::ChaCha20Poly1305 => {
+                SegmentEncryptor::ChaCha20Poly1305(EncryptorBE32::from_aead(
+                    ChaCha20Poly1305::new((&aes_key).into()),
+                    nonce_prefix.as_slice().into(),
+                ))
+            }
+            CipherSuite::Aes256Cbc => {
+                return Err(HttpError::server_error(format!(
+                    "Cipher suite {} is deprecated and cannot be used for new files",
+                    cipher_suite.as_str()
+                )))
+            }
+        };
+
+        Ok(StreamEncryptor {
+            aes_key,
+            nonce_prefix,
+            aad,
+            encryptor,
+            buffer: Vec::with_capacity(STREAM_SEGMENT_SIZE),
+        })
+    }
+
+    /// 随机数前缀，保存在 iv 列中
+    pub fn nonce_prefix(&self) -> &[u8] {
+        &self.nonce_prefix
+    }
+
+    /// 使用 RSA 公钥包装本文件的密钥，见 `wrap_key`
+    pub fn wrap_key(
+        &self,
+        user_public_key: &RsaPublicKey,
+        label: Option<String>,
+    ) -> Result<Vec<u8>, HttpError> {
+        wrap_key(&self.aes_key, user_public_key, label)
+    }
+
+    /// 写入一段明文，返回已凑满并加密完成的分段
+    ///
+    /// 缓冲区恰好凑满一个分段时不会立即加密，因为此时还不知道它是否是最后一段。
+    ///
+    /// # 参数
+    /// - `data`: 明文数据。
+    ///
+    /// # 返回
+    /// 返回按顺序排列的密文分段，加密失败时返回 500 错误。
+    pub fn update(&mut self, data: &[u8]) -> Result<Vec<Vec<u8>>, HttpError> {
+        self.buffer.extend_from_slice(data);
+
+        let mut segments = Vec::new();
+
+        while self.buffer.len() > STREAM_SEGMENT_SIZE {
+            let rest = self.buffer.split_off(STREAM_SEGMENT_SIZE);
+            let segment = std::mem::replace(&mut self.buffer, rest);
+
+            let payload = Payload {
+                msg: &segment,
+                aad: &self.aad,
+            };
+
+            let encrypted_segment = match &mut self.encryptor {
+                SegmentEncryptor::Aes256Gcm(encryptor) => encryptor.encrypt_next(payload),
+                SegmentEncryptor::ChaCha20Poly1305(encryptor) => encryptor.encrypt_next(payload),
+            }
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+            segments.push(encrypted_segment);
+        }
+
+        Ok(segments)
+    }
+
+    /// 加密缓冲区中剩余的明文作为最后一段
+    ///
+    /// 最后一段使用带结束标记的随机数，缺少最后一段的密文无法通过校验。
+    ///
+    /// # 返回
+    /// 返回最后一个密文分段，加密失败时返回 500 错误。
+    pub fn finish(self) -> Result<Vec<u8>, HttpError> {
+        let payload = Payload {
+            msg: &self.buffer,
+            aad: &self.aad,
+        };
+
+        match self.encryptor {
+            SegmentEncryptor::Aes256Gcm(encryptor) => (*encryptor).encrypt_last(payload),
+            SegmentEncryptor::ChaCha20Poly1305(encryptor) => encryptor.encrypt_last(payload),
+        }
+        .map_err(|e| HttpError::server_error(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::utils::{cipher, decrypt::StreamDecryptor};
+
+    // 加密后的文件：文件密钥、随机数前缀和按顺序排列的密文分段
+    struct EncryptedStream {
+        aes_key: [u8; 32],
+        nonce_prefix: Vec<u8>,
+        segments: Vec<Vec<u8>>,
+    }
+
+    // 按不与分段边界对齐的块写入明文，模拟上传时的分块读取
+    fn encrypt_stream(cipher_suite: CipherSuite, aad: &[u8], plaintext: &[u8]) -> EncryptedStream {
+        let mut encryptor = StreamEncryptor::new(cipher_suite, aad.to_vec()).unwrap();
+        let aes_key = encryptor.aes_key;
+        let nonce_prefix = encryptor.nonce_prefix().to_vec();
+
+        let mut segments = Vec::new();
+        for chunk in plaintext.chunks(10_000) {
+            segments.extend(encryptor.update(chunk).unwrap());
+        }
+        segments.push(encryptor.finish().unwrap());
+
+        EncryptedStream {
+            aes_key,
+            nonce_prefix,
+            segments,
+        }
+    }
+
+    // 按顺序解密全部分段，最后一段使用 decrypt_last
+    fn decrypt_stream(
+        cipher_suite: CipherSuite,
+        aad: &[u8],
+        stream: &EncryptedStream,
+        segments: &[Vec<u8>],
+    ) -> Result<Vec<u8>, HttpError> {
+        let mut decryptor = StreamDecryptor::new(
+            &stream.aes_key,
+            &stream.nonce_prefix,
+            cipher_suite,
+            aad.to_vec(),
+        )?;
+
+        let (last, rest) = segments.split_last().expect("at least one segment");
+        let mut plaintext = Vec::new();
+        for segment in rest {
+            plaintext.extend(decryptor.decrypt_next(segment)?);
+        }
+        plaintext.extend(decryptor.decrypt_last(last)?);
+
+        Ok(plaintext)
+    }
+
+    fn test_aad(cipher_suite: CipherSuite) -> Vec<u8> {
+        cipher::stream_associated_data(cipher_suite, Uuid::new_v4())
+    }
+
+    fn plaintext(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn round_trip_across_segment_boundaries() {
+        // 空文件、恰好一个分段、三个分段再多 1 字节
+        let cases = [
+            (0, 1),
+            (STREAM_SEGMENT_SIZE, 1),
+            (3 * STREAM_SEGMENT_SIZE + 1, 4),
+        ];
+
+        for cipher_suite in [CipherSuite::Aes256Gcm, CipherSuite::ChaCha20Poly1305] {
+            for (len, segment_count) in cases {
+                let aad = test_aad(cipher_suite);
+                let data = plaintext(len);
+
+                let stream = encrypt_stream(cipher_suite, &aad, &data);
+
+                assert_eq!(
+                    stream.segments.len(),
+                    segment_count,
+                    "{:?} {}",
+                    cipher_suite,
+                    len
+                );
+                assert_eq!(
+                    decrypt_stream(cipher_suite, &aad, &stream, &stream.segments).unwrap(),
+                    data,
+                    "{:?} {}",
+                    cipher_suite,
+                    len
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_stream() {
+        let cipher_suite = CipherSuite::Aes256Gcm;
+        let aad = test_aad(cipher_suite);
+        let stream = encrypt_stream(cipher_suite, &aad, &plaintext(2 * STREAM_SEGMENT_SIZE + 1));
+
+        // 丢弃最后一段后，倒数第二段不带结束标记，不能作为最后一段通过校验
+        let truncated = &stream.segments[..stream.segments.len() - 1];
+
+        assert!(decrypt_stream(cipher_suite, &aad, &stream, truncated).is_err());
+    }
+
+    #[test]
+    fn rejects_reordered_segments() {
+        let cipher_suite = CipherSuite::Aes256Gcm;
+        let aad = test_aad(cipher_suite);
+        let stream = encrypt_stream(cipher_suite, &aad, &plaintext(2 * STREAM_SEGMENT_SIZE + 1));
+
+        let mut reordered = stream.segments.clone();
+        reordered.swap(0, 1);
+
+        assert!(decrypt_stream(cipher_suite, &aad, &stream, &reordered).is_err());
+    }
+
+    #[test]
+    fn rejects_tampered_segment() {
+        let cipher_suite = CipherSuite::ChaCha20Poly1305;
+        let aad = test_aad(cipher_suite);
+        let stream = encrypt_stream(cipher_suite, &aad, &plaintext(2 * STREAM_SEGMENT_SIZE + 1));
+
+        for index in 0..stream.segments.len() {
+            let mut tampered = stream.segments.clone();
+            tampered[index][0] ^= 1;
+
+            assert!(
+                decrypt_stream(cipher_suite, &aad, &stream, &tampered).is_err(),
+                "segment {}",
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_aad() {
+        let cipher_suite = CipherSuite::Aes256Gcm;
+        let aad = test_aad(cipher_suite);
+        let stream = encrypt_stream(cipher_suite, &aad, &plaintext(STREAM_SEGMENT_SIZE + 1));
+
+        // 其他文件的附加认证数据
+        let other_aad = test_aad(cipher_suite);
+
+        assert!(decrypt_stream(cipher_suite, &other_aad, &stream, &stream.segments).is_err());
+    }
+}
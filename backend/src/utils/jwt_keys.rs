@@ -0,0 +1,178 @@
+// 引入标准库中的集合、格式化和文件系统相关类型
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+// 引入 base64 库，用于编码 JWK 中的 RSA 参数
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+// 引入 jsonwebtoken 库的密钥和 JWK 类型
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters, CommonParameters, Jwk, JwkSet, KeyAlgorithm, PublicKeyUse,
+        RSAKeyParameters, RSAKeyType,
+    },
+    DecodingKey, EncodingKey,
+};
+// 引入 rand 库，用于生成临时签名密钥
+use rand::rngs::OsRng;
+// 引入 rsa 库，用于解析密钥并导出公钥参数
+use rsa::{pkcs1::EncodeRsaPrivateKey, traits::PublicKeyParts, RsaPrivateKey, RsaPublicKey};
+
+use crate::utils::keys;
+
+// 未配置密钥目录时临时生成的签名密钥 ID
+const EPHEMERAL_KID: &str = "ephemeral";
+
+/// JWT 签名和验证密钥集合
+///
+/// 使用 RS256 签名，令牌头部的 `kid` 指明签名密钥。
+/// 当前签名密钥之外的其他密钥只用于验证，以便在轮换密钥时不让已登录的用户失效。
+pub struct JwtKeys {
+    active_kid: String,                          // 当前用于签名的密钥 ID
+    encoding_key: EncodingKey,                   // 当前签名私钥
+    decoding_keys: HashMap<String, DecodingKey>, // 全部可用于验证的公钥，按密钥 ID 索引
+    jwks: JwkSet,                                // 对外公布的 JWKS
+}
+
+impl fmt::Debug for JwtKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // 不输出密钥内容，只输出密钥 ID
+        f.debug_struct("JwtKeys")
+            .field("active_kid", &self.active_kid)
+            .field("kids", &self.decoding_keys.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl JwtKeys {
+    /// 从目录加载密钥
+    ///
+    /// 目录中的每个 `<kid>.pem` 文件对应一个密钥，文件名即密钥 ID。
+    /// 私钥（PKCS#8 或 PKCS#1）既可签名也可验证，公钥（SPKI 或 PKCS#1）只用于验证。
+    ///
+    /// # 参数
+    /// - `dir`: 密钥目录。
+    /// - `active_kid`: 当前用于签名的密钥 ID，对应的文件必须是私钥。
+    ///
+    /// # 返回
+    /// 返回加载后的 `JwtKeys`，目录无法读取、密钥无法解析或签名密钥不存在时返回错误信息。
+    pub fn load(dir: &str, active_kid: &str) -> Result<JwtKeys, String> {
+        let mut private_keys = HashMap::new();
+        let mut public_keys = HashMap::new();
+
+        let entries = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir, e))?;
+
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+
+            if path.extension().and_then(|ext| ext.to_str()) != Some("pem") {
+                continue;
+            }
+
+            let kid = kid_from_path(&path)?;
+            let pem = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+            if let Ok(private_key) = keys::parse_private_key(&pem) {
+                public_keys.insert(kid.clone(), RsaPublicKey::from(&private_key));
+                private_keys.insert(kid, private_key);
+            } else {
+                let public_key = keys::parse_public_key(&pem)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                public_keys.insert(kid, public_key);
+            }
+        }
+
+        let signing_key = private_keys.get(active_kid).ok_or_else(|| {
+            format!("No private key found for active kid \"{}\" in {}", active_kid, dir)
+        })?;
+
+        JwtKeys::build(active_kid, signing_key, public_keys)
+    }
+
+    /// 生成临时签名密钥
+    ///
+    /// 仅用于开发环境，服务重启后之前签发的令牌全部失效。
+    ///
+    /// # 返回
+    /// 返回只包含一个临时密钥的 `JwtKeys`。
+    pub fn ephemeral() -> Result<JwtKeys, String> {
+        let private_key =
+            RsaPrivateKey::new(&mut OsRng, keys::MIN_RSA_KEY_BITS).map_err(|e| e.to_string())?;
+
+        let mut public_keys = HashMap::new();
+        public_keys.insert(EPHEMERAL_KID.to_string(), RsaPublicKey::from(&private_key));
+
+        JwtKeys::build(EPHEMERAL_KID, &private_key, public_keys)
+    }
+
+    // 根据签名私钥和全部验证公钥构建密钥集合
+    fn build(
+        active_kid: &str,
+        signing_key: &RsaPrivateKey,
+        public_keys: HashMap<String, RsaPublicKey>,
+    ) -> Result<JwtKeys, String> {
+        let der = signing_key.to_pkcs1_der().map_err(|e| e.to_string())?;
+        let encoding_key = EncodingKey::from_rsa_der(der.as_bytes());
+
+        let mut decoding_keys = HashMap::new();
+        let mut jwks = Vec::new();
+
+        for (kid, public_key) in public_keys {
+            let n = URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be());
+            let e = URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be());
+
+            let decoding_key = DecodingKey::from_rsa_components(&n, &e).map_err(|e| e.to_string())?;
+            decoding_keys.insert(kid.clone(), decoding_key);
+
+            jwks.push(Jwk {
+                common: CommonParameters {
+                    public_key_use: Some(PublicKeyUse::Signature),
+                    key_algorithm: Some(KeyAlgorithm::RS256),
+                    key_id: Some(kid),
+                    ..Default::default()
+                },
+                algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n,
+                    e,
+                }),
+            });
+        }
+
+        // 按密钥 ID 排序，保证 JWKS 输出稳定
+        jwks.sort_by(|a, b| a.common.key_id.cmp(&b.common.key_id));
+
+        Ok(JwtKeys {
+            active_kid: active_kid.to_string(),
+            encoding_key,
+            decoding_keys,
+            jwks: JwkSet { keys: jwks },
+        })
+    }
+
+    // 当前用于签名的密钥 ID
+    pub fn active_kid(&self) -> &str {
+        &self.active_kid
+    }
+
+    // 当前签名私钥
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    // 根据密钥 ID 获取验证公钥
+    pub fn decoding_key(&self, kid: &str) -> Option<&DecodingKey> {
+        self.decoding_keys.get(kid)
+    }
+
+    // 对外公布的 JWKS
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+}
+
+// 从文件路径中取出密钥 ID（去掉 .pem 后缀的文件名）
+fn kid_from_path(path: &Path) -> Result<String, String> {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .map(|stem| stem.to_string())
+        .ok_or_else(|| format!("Invalid key file name: {}", path.display()))
+}
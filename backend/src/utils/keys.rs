@@ -0,0 +1,88 @@
+// 引入 rsa 库，用于解析和编码 RSA 公钥
+use rsa::{
+    pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey},
+    pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePublicKey, LineEnding},
+    traits::PublicKeyParts,
+    RsaPrivateKey, RsaPublicKey,
+};
+// 引入 sha2 库，用于计算公钥指纹
+use sha2::{Digest, Sha256};
+
+use crate::error::ErrorMessage;
+
+// RSA 公钥模数的最小位数
+pub const MIN_RSA_KEY_BITS: usize = 2048;
+
+// 使用用户公钥包装文件密钥的算法，记录在每把设备公钥上
+pub const KEY_ALGORITHM: &str = "RSA-OAEP-SHA256";
+
+/// 解析 PEM 格式的 RSA 公钥
+///
+/// 依次尝试 SPKI（`BEGIN PUBLIC KEY`）和 PKCS#1（`BEGIN RSA PUBLIC KEY`）两种格式，
+/// 并要求模数不少于 `MIN_RSA_KEY_BITS` 位。
+///
+/// # 参数
+/// - `pem`: PEM 格式的公钥字符串。
+///
+/// # 返回
+/// 返回解析后的 `RsaPublicKey`，无法解析或长度不足时返回对应的 `ErrorMessage`。
+pub fn parse_public_key(pem: &str) -> Result<RsaPublicKey, ErrorMessage> {
+    let pem = pem.trim();
+
+    let public_key = RsaPublicKey::from_public_key_pem(pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+        .map_err(|_| ErrorMessage::InvalidPublicKey)?;
+
+    if public_key.n().bits() < MIN_RSA_KEY_BITS {
+        return Err(ErrorMessage::PublicKeyTooSmall(MIN_RSA_KEY_BITS));
+    }
+
+    Ok(public_key)
+}
+
+/// 解析 PEM 格式的 RSA 私钥
+///
+/// 依次尝试 PKCS#8（`BEGIN PRIVATE KEY`）和 PKCS#1（`BEGIN RSA PRIVATE KEY`）两种格式。
+///
+/// # 参数
+/// - `pem`: PEM 格式的私钥字符串。
+///
+/// # 返回
+/// 返回解析后的 `RsaPrivateKey`，无法解析时返回 `ErrorMessage::InvalidPrivateKey`。
+pub fn parse_private_key(pem: &str) -> Result<RsaPrivateKey, ErrorMessage> {
+    let pem = pem.trim();
+
+    RsaPrivateKey::from_pkcs8_pem(pem)
+        .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+        .map_err(|_| ErrorMessage::InvalidPrivateKey)
+}
+
+/// 将公钥编码为统一的 SPKI PEM 格式，便于存储
+///
+/// # 参数
+/// - `public_key`: RSA 公钥。
+///
+/// # 返回
+/// 返回 SPKI PEM 字符串或编码错误。
+pub fn to_pem(public_key: &RsaPublicKey) -> Result<String, ErrorMessage> {
+    public_key
+        .to_public_key_pem(LineEnding::LF)
+        .map_err(|_| ErrorMessage::InvalidPublicKey)
+}
+
+/// 计算公钥指纹
+///
+/// 指纹为公钥 SPKI DER 编码的 SHA-256 摘要，以小写十六进制表示。
+///
+/// # 参数
+/// - `public_key`: RSA 公钥。
+///
+/// # 返回
+/// 返回 64 个字符的十六进制指纹或编码错误。
+pub fn fingerprint(public_key: &RsaPublicKey) -> Result<String, ErrorMessage> {
+    let der = public_key
+        .to_public_key_der()
+        .map_err(|_| ErrorMessage::InvalidPublicKey)?;
+
+    Ok(hex::encode(Sha256::digest(der.as_bytes())))
+}
@@ -0,0 +1,122 @@
+// 引入 chrono 库，用于计算锁定时长
+use chrono::Duration;
+
+use crate::config::Config;
+
+// 退避时长的最大指数，避免移位溢出
+const MAX_BACKOFF_EXPONENT: i32 = 20;
+
+// 登录限流的维度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThrottleScope {
+    Account, // 按账户（小写邮箱）
+    Ip,      // 按客户端 IP
+}
+
+impl ThrottleScope {
+    // 在数据库中保存的字符串表示
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThrottleScope::Account => "account",
+            ThrottleScope::Ip => "ip",
+        }
+    }
+
+    // 该维度允许的最大连续失败次数
+    fn max_failures(&self, config: &Config) -> i32 {
+        match self {
+            ThrottleScope::Account => config.login_max_failures,
+            ThrottleScope::Ip => config.login_ip_max_failures,
+        }
+    }
+}
+
+/// 根据连续失败次数计算锁定时长
+///
+/// 第一次失败不锁定；之后按 `LOGIN_BACKOFF_SECONDS` 指数退避（1、2、4…倍），
+/// 达到最大失败次数后锁定 `LOGIN_LOCKOUT_MINUTES` 分钟，退避时长也不会超过该值。
+///
+/// # 参数
+/// - `scope`: 限流维度。
+/// - `failure_count`: 统计窗口内的连续失败次数。
+/// - `config`: 应用配置。
+///
+/// # 返回
+/// 返回需要锁定的时长，不需要锁定时返回 `None`。
+pub fn lock_duration(scope: ThrottleScope, failure_count: i32, config: &Config) -> Option<Duration> {
+    let lockout = Duration::minutes(config.login_lockout_minutes);
+
+    if failure_count >= scope.max_failures(config) {
+        return Some(lockout);
+    }
+
+    if failure_count < 2 {
+        return None;
+    }
+
+    let exponent = (failure_count - 2).min(MAX_BACKOFF_EXPONENT);
+    // 先按秒数截断到锁定时长，过大的退避时长无法构造 Duration
+    let backoff_seconds = config
+        .login_backoff_seconds
+        .saturating_mul(1 << exponent)
+        .min(lockout.num_seconds());
+
+    Some(Duration::seconds(backoff_seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 构造测试用配置：账户 5 次、IP 20 次后锁定 15 分钟，退避基础时长 1 秒
+    fn test_config() -> Config {
+        std::env::set_var("DATABASE_URL", "postgresql://localhost/test");
+        std::env::set_var("JWT_MAXAGE", "15");
+        std::env::set_var("REFRESH_TOKEN_MAXAGE", "10080");
+
+        let mut config = Config::init();
+        config.login_max_failures = 5;
+        config.login_ip_max_failures = 20;
+        config.login_lockout_minutes = 15;
+        config.login_backoff_seconds = 1;
+        config
+    }
+
+    #[test]
+    fn lock_duration_below_threshold() {
+        let config = test_config();
+
+        assert_eq!(lock_duration(ThrottleScope::Account, 0, &config), None);
+        assert_eq!(lock_duration(ThrottleScope::Account, 1, &config), None);
+        assert_eq!(lock_duration(ThrottleScope::Account, 2, &config), Some(Duration::seconds(1)));
+        assert_eq!(lock_duration(ThrottleScope::Account, 3, &config), Some(Duration::seconds(2)));
+        assert_eq!(lock_duration(ThrottleScope::Account, 4, &config), Some(Duration::seconds(4)));
+    }
+
+    #[test]
+    fn lock_duration_at_threshold() {
+        let config = test_config();
+
+        assert_eq!(lock_duration(ThrottleScope::Account, 5, &config), Some(Duration::minutes(15)));
+        assert_eq!(lock_duration(ThrottleScope::Account, 6, &config), Some(Duration::minutes(15)));
+        // IP 维度的阈值更高，账户阈值处仍只是退避
+        assert_eq!(lock_duration(ThrottleScope::Ip, 5, &config), Some(Duration::seconds(8)));
+        assert_eq!(lock_duration(ThrottleScope::Ip, 20, &config), Some(Duration::minutes(15)));
+    }
+
+    #[test]
+    fn lock_duration_is_capped() {
+        let mut config = test_config();
+
+        // 退避时长不超过锁定时长
+        assert_eq!(lock_duration(ThrottleScope::Ip, 19, &config), Some(Duration::minutes(15)));
+
+        config.login_backoff_seconds = 600;
+        assert_eq!(lock_duration(ThrottleScope::Account, 4, &config), Some(Duration::minutes(15)));
+
+        // 指数有上限，退避基础时长很大时也不会溢出
+        config.login_ip_max_failures = i32::MAX;
+        config.login_backoff_seconds = i64::MAX;
+        assert_eq!(lock_duration(ThrottleScope::Ip, 1000, &config), Some(Duration::minutes(15)));
+    }
+}
@@ -0,0 +1,11 @@
+pub mod access_token;
+pub mod cipher;
+pub mod decrypt;
+pub mod encrypt;
+pub mod jwt_keys;
+pub mod keys;
+pub mod login_throttle;
+pub mod password;
+pub mod rate_limit;
+pub mod token;
+pub mod totp;
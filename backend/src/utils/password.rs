@@ -0,0 +1,97 @@
+// 引入 argon2 库，用于密码的哈希与校验
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+
+use crate::error::ErrorMessage;
+
+// 密码允许的最大长度，避免超长输入拖慢哈希计算
+const MAX_PASSWORD_LENGTH: usize = 64;
+
+// Argon2 PHC 格式哈希字符串的前缀
+const ARGON2_PREFIX: &str = "$argon2";
+
+/// 使用 Argon2 对密码进行哈希
+///
+/// # 参数
+/// - `password`: 明文密码。
+///
+/// # 返回
+/// 返回 PHC 格式的哈希字符串，密码为空、超长或哈希失败时返回对应的 `ErrorMessage`。
+pub fn hash(password: impl Into<String>) -> Result<String, ErrorMessage> {
+    let password = password.into();
+
+    if password.is_empty() {
+        return Err(ErrorMessage::EmptyPassword);
+    }
+
+    if password.len() > MAX_PASSWORD_LENGTH {
+        return Err(ErrorMessage::ExceededMaxPasswordLength(MAX_PASSWORD_LENGTH));
+    }
+
+    // 为每个密码生成随机盐值
+    let salt = SaltString::generate(&mut OsRng);
+    let hashed_password = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|_| ErrorMessage::HashingError)?
+        .to_string();
+
+    Ok(hashed_password)
+}
+
+/// 校验明文密码与哈希是否匹配
+///
+/// # 参数
+/// - `password`: 明文密码。
+/// - `hashed_password`: 已存储的 PHC 格式哈希字符串。
+///
+/// # 返回
+/// 返回是否匹配，密码为空、超长或哈希格式无效时返回对应的 `ErrorMessage`。
+pub fn compare(password: &str, hashed_password: &str) -> Result<bool, ErrorMessage> {
+    if password.is_empty() {
+        return Err(ErrorMessage::EmptyPassword);
+    }
+
+    if password.len() > MAX_PASSWORD_LENGTH {
+        return Err(ErrorMessage::ExceededMaxPasswordLength(MAX_PASSWORD_LENGTH));
+    }
+
+    let parsed_hash =
+        PasswordHash::new(hashed_password).map_err(|_| ErrorMessage::InvalidHashFormat)?;
+
+    let password_matched = Argon2::default()
+        .verify_password(password.as_bytes(), &parsed_hash)
+        .is_ok();
+
+    Ok(password_matched)
+}
+
+/// 判断存储的密码是否已经是 Argon2 哈希
+///
+/// 旧版本的共享链接密码以明文存储，没有 `$argon2` 前缀。
+///
+/// # 参数
+/// - `stored_password`: 数据库中存储的密码字段。
+///
+/// # 返回
+/// 是 Argon2 PHC 格式哈希时返回 `true`。
+pub fn is_hashed(stored_password: &str) -> bool {
+    stored_password.starts_with(ARGON2_PREFIX)
+}
+
+/// 以常量时间比较两个字节串，用于校验旧版明文密码
+///
+/// # 参数
+/// - `a`: 第一个字节串。
+/// - `b`: 第二个字节串。
+///
+/// # 返回
+/// 两者完全相同时返回 `true`，比较耗时与内容无关。
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
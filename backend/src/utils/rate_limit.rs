@@ -0,0 +1,64 @@
+// 引入标准库中的集合、互斥锁和时间类型
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+// 超过该数量的记录时清理已过期的窗口，避免内存无限增长
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// 基于固定时间窗口的内存限流器
+///
+/// 以任意字符串（如用户 ID）为键，统计每个窗口内的请求次数。
+#[derive(Debug)]
+pub struct RateLimiter {
+    max_requests: u32,                              // 每个窗口内允许的最大请求数
+    window: Duration,                               // 窗口长度
+    entries: Mutex<HashMap<String, (Instant, u32)>>, // 每个键的窗口开始时间和已用次数
+}
+
+impl RateLimiter {
+    /// 创建新的 `RateLimiter` 实例
+    ///
+    /// # 参数
+    /// - `max_requests`: 每个窗口内允许的最大请求数。
+    /// - `window`: 窗口长度。
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        RateLimiter {
+            max_requests,
+            window,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 记录一次请求并判断是否允许
+    ///
+    /// # 参数
+    /// - `key`: 限流的键。
+    ///
+    /// # 返回
+    /// 当前窗口内未超过限制时返回 `true`，否则返回 `false`。
+    pub fn check(&self, key: &str) -> bool {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+
+        if entries.len() > PRUNE_THRESHOLD {
+            entries.retain(|_, (started_at, _)| now.duration_since(*started_at) < self.window);
+        }
+
+        let entry = entries.entry(key.to_string()).or_insert((now, 0));
+
+        // 窗口已过期时重新开始计数
+        if now.duration_since(entry.0) >= self.window {
+            *entry = (now, 0);
+        }
+
+        if entry.1 >= self.max_requests {
+            return false;
+        }
+
+        entry.1 += 1;
+        true
+    }
+}
@@ -0,0 +1,197 @@
+// 引入 base64 库，用于编码随机生成的令牌
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+// 引入 chrono 库，用于计算令牌的签发时间和过期时间
+use chrono::{Duration, Utc};
+// 引入 jsonwebtoken 库，用于 JWT 的编码与解码
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, Header, Validation};
+// 引入 rand 库，用于生成随机令牌
+use rand::{rngs::OsRng, RngCore};
+// 引入 serde 库，用于令牌声明的序列化与反序列化
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+// 引入 sha2 库，用于计算随机令牌的摘要
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{ErrorMessage, HttpError},
+    utils::jwt_keys::JwtKeys,
+};
+
+// 令牌签发者，其他服务验证令牌时需要校验
+pub const TOKEN_ISSUER: &str = "SecureShare";
+
+// 两步验证令牌的受众，用于和访问令牌区分
+const MFA_TOKEN_AUDIENCE: &str = "mfa";
+
+// JWT 令牌中携带的声明信息
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenClaims {
+    pub iss: String, // 令牌签发者
+    pub sub: String, // 令牌主体，即用户 ID
+    pub sid: String, // 会话族 ID，用于服务端吊销
+    pub ver: i32,    // 签发时用户的令牌版本号
+    pub iat: usize,  // 令牌签发时间（Unix 时间戳）
+    pub exp: usize,  // 令牌过期时间（Unix 时间戳）
+}
+
+// 两步验证令牌中携带的声明信息
+// 密码验证通过但尚未输入验证码时签发，只能用于完成两步验证，不能访问其他接口
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MfaTokenClaims {
+    pub iss: String, // 令牌签发者
+    pub aud: String, // 令牌受众，固定为 mfa
+    pub sub: String, // 令牌主体，即用户 ID
+    pub ver: i32,    // 签发时用户的令牌版本号
+    pub iat: usize,  // 令牌签发时间（Unix 时间戳）
+    pub exp: usize,  // 令牌过期时间（Unix 时间戳）
+}
+
+/// 创建 JWT 访问令牌
+///
+/// # 参数
+/// - `user_id`: 用户唯一标识符。
+/// - `session_id`: 会话族 ID。
+/// - `token_version`: 用户当前的令牌版本号。
+/// - `jwt_keys`: 签名密钥集合，使用其中当前的签名密钥。
+/// - `expires_in_minutes`: 令牌有效期（分钟）。
+///
+/// # 返回
+/// 返回签名后的令牌字符串或编码错误。
+pub fn create_token(
+    user_id: &str,
+    session_id: &str,
+    token_version: i32,
+    jwt_keys: &JwtKeys,
+    expires_in_minutes: i64,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    // 用户 ID 为空时拒绝签发令牌
+    if user_id.is_empty() {
+        return Err(jsonwebtoken::errors::ErrorKind::InvalidSubject.into());
+    }
+
+    let now = Utc::now();
+    let iat = now.timestamp() as usize;
+    let exp = (now + Duration::minutes(expires_in_minutes)).timestamp() as usize;
+    let claims = TokenClaims {
+        iss: TOKEN_ISSUER.to_string(),
+        sub: user_id.to_string(),
+        sid: session_id.to_string(),
+        ver: token_version,
+        iat,
+        exp,
+    };
+
+    // 在令牌头部写入签名密钥 ID，验证方据此从 JWKS 中选择公钥
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(jwt_keys.active_kid().to_string());
+
+    encode(&header, &claims, jwt_keys.encoding_key())
+}
+
+/// 解码并校验 JWT 访问令牌
+///
+/// # 参数
+/// - `token`: 令牌字符串。
+/// - `jwt_keys`: 验证密钥集合，根据令牌头部的 `kid` 选择公钥。
+///
+/// # 返回
+/// 返回令牌中的声明信息，令牌无效、已过期或 `kid` 未知时返回 401 错误。
+pub fn decode_token<T: Into<String>>(token: T, jwt_keys: &JwtKeys) -> Result<TokenClaims, HttpError> {
+    let validation = Validation::new(Algorithm::RS256);
+
+    decode_claims(token.into(), jwt_keys, validation)
+}
+
+/// 创建两步验证令牌
+///
+/// # 参数
+/// - `user_id`: 用户唯一标识符。
+/// - `token_version`: 用户当前的令牌版本号。
+/// - `jwt_keys`: 签名密钥集合，使用其中当前的签名密钥。
+/// - `expires_in_minutes`: 令牌有效期（分钟）。
+///
+/// # 返回
+/// 返回签名后的令牌字符串或编码错误。
+pub fn create_mfa_token(
+    user_id: &str,
+    token_version: i32,
+    jwt_keys: &JwtKeys,
+    expires_in_minutes: i64,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let now = Utc::now();
+    let claims = MfaTokenClaims {
+        iss: TOKEN_ISSUER.to_string(),
+        aud: MFA_TOKEN_AUDIENCE.to_string(),
+        sub: user_id.to_string(),
+        ver: token_version,
+        iat: now.timestamp() as usize,
+        exp: (now + Duration::minutes(expires_in_minutes)).timestamp() as usize,
+    };
+
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(jwt_keys.active_kid().to_string());
+
+    encode(&header, &claims, jwt_keys.encoding_key())
+}
+
+/// 解码并校验两步验证令牌
+///
+/// # 参数
+/// - `token`: 令牌字符串。
+/// - `jwt_keys`: 验证密钥集合。
+///
+/// # 返回
+/// 返回令牌中的声明信息，令牌无效、已过期或不是两步验证令牌时返回 401 错误。
+pub fn decode_mfa_token(token: &str, jwt_keys: &JwtKeys) -> Result<MfaTokenClaims, HttpError> {
+    let mut validation = Validation::new(Algorithm::RS256);
+    validation.set_audience(&[MFA_TOKEN_AUDIENCE]);
+
+    decode_claims(token.to_string(), jwt_keys, validation)
+}
+
+// 根据令牌头部的 kid 选择公钥，校验签名和签发者后解码声明
+fn decode_claims<C: DeserializeOwned>(
+    token: String,
+    jwt_keys: &JwtKeys,
+    mut validation: Validation,
+) -> Result<C, HttpError> {
+    let invalid_token = || HttpError::unauthorized(ErrorMessage::InvalidToken.to_string());
+
+    let header = decode_header(&token).map_err(|_| invalid_token())?;
+    let decoding_key = header
+        .kid
+        .as_deref()
+        .and_then(|kid| jwt_keys.decoding_key(kid))
+        .ok_or_else(invalid_token)?;
+
+    validation.set_issuer(&[TOKEN_ISSUER]);
+
+    let decoded = decode::<C>(&token, decoding_key, &validation);
+
+    match decoded {
+        Ok(token) => Ok(token.claims),
+        Err(_) => Err(invalid_token()),
+    }
+}
+
+/// 生成随机令牌（刷新令牌、个人访问令牌等）
+///
+/// # 返回
+/// 返回 32 字节随机数的 URL 安全 Base64 编码。
+pub fn generate_random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// 计算随机令牌的摘要
+///
+/// 随机令牌本身是高熵随机数，使用 SHA-256 摘要即可安全存储并按摘要查询。
+///
+/// # 参数
+/// - `token`: 随机令牌。
+///
+/// # 返回
+/// 返回十六进制的 SHA-256 摘要。
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
@@ -0,0 +1,196 @@
+// 引入标准库中的时间类型，用于计算当前时间步
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// 引入 rand 库，用于生成 TOTP 密钥和恢复码
+use rand::{rngs::OsRng, Rng, RngCore};
+// 引入 totp-rs 库，实现 RFC 6238 TOTP 算法
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::utils::password::constant_time_eq;
+
+// 验证器中显示的签发者名称
+const TOTP_ISSUER: &str = "SecureShare";
+
+// 验证码位数和时间步长（秒），与主流验证器的默认值一致
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP: u64 = 30;
+
+// 允许前后各偏差一个时间步，容忍客户端时钟误差
+const TOTP_SKEW_STEPS: i64 = 1;
+
+// TOTP 密钥长度（字节），RFC 4226 推荐 160 位
+const SECRET_LENGTH: usize = 20;
+
+// 每次生成的恢复码数量和每个恢复码的字符数
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LENGTH: usize = 16;
+
+// 恢复码字符集，去掉了容易混淆的 0/O 和 1/I
+const RECOVERY_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// 生成新的 TOTP 密钥
+///
+/// # 返回
+/// 返回 Base32 编码的密钥。
+pub fn generate_secret() -> String {
+    let mut bytes = vec![0u8; SECRET_LENGTH];
+    OsRng.fill_bytes(&mut bytes);
+
+    match Secret::Raw(bytes).to_encoded() {
+        Secret::Encoded(secret) => secret,
+        Secret::Raw(_) => unreachable!(),
+    }
+}
+
+// 根据 Base32 密钥构造 TOTP 实例
+fn build_totp(secret: &str, account_name: &str) -> Result<TOTP, String> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| e.to_string())?;
+
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        0,
+        TOTP_STEP,
+        secret,
+        Some(TOTP_ISSUER.to_string()),
+        account_name.to_string(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// 生成供验证器扫描的 otpauth URI
+///
+/// # 参数
+/// - `secret`: Base32 编码的密钥。
+/// - `account_name`: 验证器中显示的账户名，通常为用户邮箱。
+///
+/// # 返回
+/// 返回 `otpauth://totp/...` 格式的 URI 或错误信息。
+pub fn otpauth_uri(secret: &str, account_name: &str) -> Result<String, String> {
+    Ok(build_totp(secret, account_name)?.get_url())
+}
+
+/// 校验 TOTP 验证码
+///
+/// 只接受比 `last_used_step` 更新的时间步，同一验证码不能被使用两次。
+///
+/// # 参数
+/// - `secret`: Base32 编码的密钥。
+/// - `code`: 用户输入的验证码。
+/// - `last_used_step`: 最近一次验证通过的时间步。
+///
+/// # 返回
+/// 验证通过时返回匹配的时间步，否则返回 `None`。
+pub fn verify_code(secret: &str, code: &str, last_used_step: Option<i64>) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let totp = build_totp(secret, "").ok()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let current_step = (now / TOTP_STEP) as i64;
+
+    (-TOTP_SKEW_STEPS..=TOTP_SKEW_STEPS)
+        .map(|offset| current_step + offset)
+        .filter(|step| *step >= 0 && last_used_step.is_none_or(|last| *step > last))
+        .find(|step| {
+            let expected = totp.generate(*step as u64 * TOTP_STEP);
+            constant_time_eq(expected.as_bytes(), code.as_bytes())
+        })
+}
+
+/// 生成一组一次性恢复码
+///
+/// # 返回
+/// 返回 `XXXX-XXXX-XXXX-XXXX` 格式的恢复码列表，只在生成时展示给用户一次。
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let chars: Vec<char> = (0..RECOVERY_CODE_LENGTH)
+                .map(|_| {
+                    let index = OsRng.gen_range(0..RECOVERY_CODE_ALPHABET.len());
+                    RECOVERY_CODE_ALPHABET[index] as char
+                })
+                .collect();
+
+            chars
+                .chunks(4)
+                .map(|chunk| chunk.iter().collect::<String>())
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+        .collect()
+}
+
+/// 规范化恢复码
+///
+/// 忽略大小写、空格和连字符，保存摘要和校验时都使用规范化后的值。
+///
+/// # 参数
+/// - `code`: 用户输入的恢复码。
+///
+/// # 返回
+/// 返回只包含大写字母和数字的恢复码。
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 当前时间步
+    fn current_step() -> i64 {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        (now / TOTP_STEP) as i64
+    }
+
+    // 生成指定时间步的验证码
+    fn code_at(secret: &str, step: i64) -> String {
+        build_totp(secret, "").unwrap().generate(step as u64 * TOTP_STEP)
+    }
+
+    #[test]
+    fn verify_code_accepts_current_code() {
+        let secret = generate_secret();
+        let step = current_step();
+
+        let verified = verify_code(&secret, &code_at(&secret, step), None);
+
+        // 跨过时间步边界时当前验证码仍在允许的偏差范围内
+        assert!(verified.is_some_and(|verified| (verified - step).abs() <= TOTP_SKEW_STEPS));
+    }
+
+    #[test]
+    fn verify_code_rejects_replayed_step() {
+        let secret = generate_secret();
+        let code = code_at(&secret, current_step());
+
+        let step = verify_code(&secret, &code, None).unwrap();
+
+        assert_eq!(verify_code(&secret, &code, Some(step)), None);
+    }
+
+    #[test]
+    fn verify_code_rejects_step_outside_window() {
+        let secret = generate_secret();
+        let step = current_step();
+
+        assert_eq!(verify_code(&secret, &code_at(&secret, step - 3), None), None);
+        assert_eq!(verify_code(&secret, &code_at(&secret, step + 3), None), None);
+    }
+
+    #[test]
+    fn verify_code_rejects_malformed_code() {
+        let secret = generate_secret();
+
+        assert_eq!(verify_code(&secret, "12345", None), None);
+        assert_eq!(verify_code(&secret, "abcdef", None), None);
+    }
+}